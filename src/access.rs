@@ -0,0 +1,762 @@
+//! Who may join: the whitelist in `white-list.txt` and the bans in `banned-players.txt` and `banned-ips.txt`.
+//!
+//! The whitelist has a name per line. The ban lists use the format of vanilla 1.3 and later, a line
+//! per ban in the form `name|created|source|expires|reason` with dates like
+//! `2013-01-15 20:39:32 +0100` and `Forever` for bans that do not expire. Lists of Alpha and Beta
+//! servers with only the names are read as well.
+use crate::command::{
+    Arguments, Command, CommandContext, CommandError, CommandRegistry, CommandResult,
+};
+use crate::config::{Config, Settings};
+use crate::entity::{connection_state, RemoteAddress};
+use crate::permission::{self, Permissions};
+use bevy::prelude::{Entity, Resource};
+use log::info;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const WHITELIST_FILE: &str = "./white-list.txt";
+pub const BANNED_PLAYERS_FILE: &str = "./banned-players.txt";
+pub const BANNED_IPS_FILE: &str = "./banned-ips.txt";
+
+/// Seconds since the unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Formats seconds since the unix epoch as `yyyy-MM-dd HH:mm:ss Z` in UTC, like vanilla writes dates.
+fn format_date(seconds: u64) -> String {
+    let (days, time) = ((seconds / 86400) as i64, seconds % 86400);
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} +0000",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Reads a date written by `format_date` or by vanilla in any time zone.
+fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.split(' ');
+    let mut numbers = |separator: char| -> Option<Vec<i64>> {
+        let part = parts.next()?;
+        part.split(separator).map(|n| n.parse().ok()).collect()
+    };
+    let [year, month, day] = <[i64; 3]>::try_from(numbers('-')?).ok()?;
+    let [hour, minute, second] = <[i64; 3]>::try_from(numbers(':')?).ok()?;
+    let zone = parts.next()?;
+    let sign = match zone.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let offset = zone.get(1..)?.parse::<i64>().ok()?;
+    let offset = sign * (offset / 100 * 3600 + offset % 100 * 60);
+    // Days from civil, the inverse of `format_date`.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    let seconds = days * 86400 + hour * 3600 + minute * 60 + second - offset;
+    u64::try_from(seconds).ok()
+}
+
+/// A duration like `30m`, `12h` or `1d12h`, in seconds.
+fn parse_duration(duration: &str) -> Option<u64> {
+    let mut seconds = 0u64;
+    let mut number = None::<u64>;
+    for c in duration.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            number = Some(number.unwrap_or(0).checked_mul(10)? + digit as u64);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        seconds = seconds.checked_add(number.take()?.checked_mul(unit)?)?;
+    }
+    match number {
+        None if seconds > 0 => Some(seconds),
+        _ => None,
+    }
+}
+
+/// Formats seconds the way `parse_duration` reads them, rounded to the two largest units.
+fn format_duration(seconds: u64) -> String {
+    const UNITS: [(u64, char); 4] = [(24 * 60 * 60, 'd'), (60 * 60, 'h'), (60, 'm'), (1, 's')];
+    let parts = UNITS
+        .iter()
+        .scan(seconds, |left, &(unit, name)| {
+            let count = *left / unit;
+            *left %= unit;
+            Some((count, name))
+        })
+        .skip_while(|(count, _)| *count == 0)
+        .take(2)
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, name)| format!("{count}{name}"))
+        .collect::<Vec<_>>();
+    match parts.is_empty() {
+        true => "0s".to_string(),
+        false => parts.join(" "),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ban {
+    pub created: u64,
+    /// Who banned, a player name or `Server`.
+    pub source: String,
+    /// When the ban runs out, `None` for bans that do not.
+    pub expires: Option<u64>,
+    pub reason: String,
+}
+
+impl Ban {
+    /// The reason shown on the kick screen.
+    pub fn message(&self, now: u64) -> String {
+        match self.expires {
+            None => format!("Banned: {}", self.reason),
+            Some(expires) => format!(
+                "Banned for {}: {}",
+                format_duration(expires.saturating_sub(now)),
+                self.reason
+            ),
+        }
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+/// Bans by lowercase player name or address.
+#[derive(Debug, Default)]
+pub struct BanList {
+    path: PathBuf,
+    bans: BTreeMap<String, Ban>,
+}
+
+impl BanList {
+    pub fn load(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let bans = match fs::read_to_string(&path) {
+            Ok(bans) => parse_bans(&bans),
+            Err(err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self { path, bans })
+    }
+
+    fn save(&self) -> io::Result<()> {
+        fs::write(&self.path, format_bans(&self.bans, now()))
+    }
+
+    /// The ban that keeps `key` out right now, if any.
+    pub fn get(&self, key: &str, now: u64) -> Option<&Ban> {
+        self.bans
+            .get(&key.to_lowercase())
+            .filter(|ban| !ban.is_expired(now))
+    }
+
+    /// Bans `key`, replacing an earlier ban, and drops the expired ones.
+    pub fn ban(&mut self, key: &str, ban: Ban) -> io::Result<()> {
+        let now = ban.created;
+        self.bans.retain(|_, ban| !ban.is_expired(now));
+        self.bans.insert(key.to_lowercase(), ban);
+        self.save()
+    }
+
+    /// Lifts the ban of `key`, returns whether there was one.
+    pub fn pardon(&mut self, key: &str) -> io::Result<bool> {
+        let pardoned = self.bans.remove(&key.to_lowercase()).is_some();
+        if pardoned {
+            self.save()?;
+        }
+        Ok(pardoned)
+    }
+
+    /// The bans that did not run out yet.
+    pub fn active(&self, now: u64) -> impl Iterator<Item = (&String, &Ban)> {
+        self.bans
+            .iter()
+            .filter(move |(_, ban)| !ban.is_expired(now))
+    }
+}
+
+fn parse_bans(bans: &str) -> BTreeMap<String, Ban> {
+    bans.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            // Alpha and Beta lists only have the names.
+            let mut fields = line.splitn(5, '|').map(str::trim);
+            let key = fields.next().unwrap_or_default().to_lowercase();
+            let created = fields.next().and_then(parse_date).unwrap_or_else(now);
+            let source = fields.next().unwrap_or("(Unknown)").to_string();
+            let expires = fields.next().and_then(parse_date);
+            let reason = fields
+                .next()
+                .unwrap_or("Banned by an operator.")
+                .to_string();
+            (
+                key,
+                Ban {
+                    created,
+                    source,
+                    expires,
+                    reason,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Writes the bans with the header vanilla writes.
+fn format_bans(bans: &BTreeMap<String, Ban>, now: u64) -> String {
+    let mut lines = format!(
+        "# Updated {} by betalpha\n# victim name | ban date | banned by | banned until | reason\n\n",
+        format_date(now)
+    );
+    for (key, ban) in bans {
+        let expires = ban.expires.map_or("Forever".to_string(), format_date);
+        lines += &format!(
+            "{key}|{}|{}|{expires}|{}\n",
+            format_date(ban.created),
+            ban.source,
+            ban.reason
+        );
+    }
+    lines
+}
+
+#[derive(Debug, Default)]
+pub struct Whitelist {
+    path: PathBuf,
+    /// Only whitelisted players and operators may join while enabled, set from `white-list` in the settings.
+    pub enabled: bool,
+    /// Lowercase names in the order of the file.
+    names: Vec<String>,
+}
+
+impl Whitelist {
+    pub fn load(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let mut whitelist = Self {
+            path: path.into(),
+            ..Self::default()
+        };
+        whitelist.reload()?;
+        Ok(whitelist)
+    }
+
+    pub fn reload(&mut self) -> io::Result<()> {
+        self.names = match fs::read_to_string(&self.path) {
+            Ok(names) => names
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect(),
+            Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains(&name.to_lowercase())
+    }
+
+    /// Adds or removes a name and writes the file, returns whether anything changed.
+    pub fn set(&mut self, name: &str, listed: bool) -> io::Result<bool> {
+        if self.contains(name) == listed {
+            return Ok(false);
+        }
+        let name = name.to_lowercase();
+        if listed {
+            self.names.push(name);
+        } else {
+            self.names.retain(|listed| listed != &name);
+        }
+        let mut names = self.names.join("\n");
+        names.push('\n');
+        fs::write(&self.path, names)?;
+        Ok(true)
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct AccessLists {
+    pub whitelist: Whitelist,
+    pub banned_players: BanList,
+    pub banned_ips: BanList,
+}
+
+impl AccessLists {
+    pub fn load() -> io::Result<Self> {
+        Ok(Self {
+            whitelist: Whitelist::load(WHITELIST_FILE)?,
+            banned_players: BanList::load(BANNED_PLAYERS_FILE)?,
+            banned_ips: BanList::load(BANNED_IPS_FILE)?,
+        })
+    }
+
+    /// Why a connection from `address` may not join, checked as soon as it is accepted.
+    pub fn check_address(&self, address: IpAddr) -> Result<(), String> {
+        match self.banned_ips.get(&address.to_string(), now()) {
+            Some(ban) => Err(ban.message(now())),
+            None => Ok(()),
+        }
+    }
+
+    /// Why a player may not join, checked when the login arrives.
+    pub fn check_player(&self, name: &str, permissions: &Permissions) -> Result<(), String> {
+        if let Some(ban) = self.banned_players.get(name, now()) {
+            return Err(ban.message(now()));
+        }
+        if self.whitelist.enabled
+            && !self.whitelist.contains(name)
+            && !permissions.is_operator(name)
+        {
+            return Err("You are not white-listed on this server!".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What happens when a player logs in while a player with the same name is online.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateLogin {
+    /// The older session is kicked.
+    KickOld,
+    /// The newer session is refused.
+    RefuseNew,
+}
+
+impl FromStr for DuplicateLogin {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "kick-old" => Ok(Self::KickOld),
+            "refuse-new" => Ok(Self::RefuseNew),
+            _ => Err(format!("Unknown duplicate login policy: {policy}")),
+        }
+    }
+}
+
+/// Decides who may join during one run of the login system.
+pub struct Admission<'a> {
+    pub access_lists: &'a AccessLists,
+    pub permissions: &'a Permissions,
+    pub settings: &'a Settings,
+    /// Players that are in the world or about to join it.
+    pub online: Vec<(Entity, String)>,
+}
+
+impl Admission<'_> {
+    /// Admits a player, the returned entity is an older session of the same player that has to be kicked.
+    pub fn admit(&mut self, entity: Entity, name: &str) -> Result<Option<Entity>, String> {
+        self.access_lists.check_player(name, self.permissions)?;
+        let existing = self
+            .online
+            .iter()
+            .position(|(_, online)| online.eq_ignore_ascii_case(name));
+        let replaced = match (existing, self.settings.duplicate_login) {
+            (Some(_), DuplicateLogin::RefuseNew) => {
+                return Err("You are already logged in from another location!".to_string())
+            }
+            (Some(index), DuplicateLogin::KickOld) => Some(index),
+            (None, _) => None,
+        };
+        let players = self.online.len() - replaced.iter().len();
+        if players >= self.settings.max_players
+            && !self.permissions.has(name, permission::JOIN_FULL)
+        {
+            return Err("The server is full!".to_string());
+        }
+        let replaced = replaced.map(|index| self.online.remove(index).0);
+        self.online.push((entity, name.to_string()));
+        Ok(replaced)
+    }
+}
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(
+        "ban",
+        Command::new(
+            "betalpha.command.ban",
+            "/ban <player> [duration] [reason]",
+            "Bans a player, for a duration like 30m, 12h or 7d if given.",
+            ban,
+        ),
+    );
+    registry.register(
+        "pardon",
+        Command::new(
+            "betalpha.command.pardon",
+            "/pardon <player>",
+            "Lifts the ban of a player.",
+            pardon,
+        ),
+    );
+    registry.register(
+        "ban-ip",
+        Command::new(
+            "betalpha.command.ban-ip",
+            "/ban-ip <address | player> [duration] [reason]",
+            "Bans an address, for a duration like 30m, 12h or 7d if given.",
+            ban_ip,
+        ),
+    );
+    registry.register(
+        "pardon-ip",
+        Command::new(
+            "betalpha.command.pardon-ip",
+            "/pardon-ip <address>",
+            "Lifts the ban of an address.",
+            pardon_ip,
+        ),
+    );
+    registry.register(
+        "banlist",
+        Command::new(
+            "betalpha.command.banlist",
+            "/banlist [players | ips]",
+            "Lists the banned players or addresses.",
+            banlist,
+        ),
+    );
+    registry.register(
+        "whitelist",
+        Command::new(
+            "betalpha.command.whitelist",
+            "/whitelist <on | off | list | reload | add <player> | remove <player>>",
+            "Manages who may join while the whitelist is on.",
+            whitelist,
+        ),
+    );
+}
+
+/// The optional duration and reason following the target of a ban.
+fn parse_ban(context: &CommandContext, arguments: &mut Arguments) -> Ban {
+    let rest = arguments.rest().unwrap_or_default();
+    let (first, reason) = rest.split_once(' ').unwrap_or((&rest, ""));
+    let (duration, reason) = match parse_duration(first) {
+        Some(duration) => (Some(duration), reason),
+        None => (None, rest.as_str()),
+    };
+    let created = now();
+    Ban {
+        created,
+        source: context.name(context.sender),
+        expires: duration.map(|duration| created + duration),
+        reason: match reason.is_empty() {
+            true => "Banned by an operator.".to_string(),
+            false => reason.to_string(),
+        },
+    }
+}
+
+/// Online players with their addresses.
+fn addresses(context: &mut CommandContext) -> Vec<(Entity, String, Option<IpAddr>)> {
+    context
+        .players()
+        .into_iter()
+        .map(|(entity, name)| {
+            let address = context
+                .ecs
+                .get::<RemoteAddress>(entity)
+                .map(|address| address.0.ip());
+            (entity, name, address)
+        })
+        .collect()
+}
+
+fn disconnect(context: &mut CommandContext, entity: Entity, reason: String) {
+    context
+        .ecs
+        .entity_mut(entity)
+        .remove::<connection_state::Playing>()
+        .insert(connection_state::Disconnecting { reason });
+}
+
+fn ban(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let name = arguments.word()?.to_string();
+    let ban = parse_ban(context, arguments);
+    let message = ban.message(ban.created);
+    context
+        .ecs
+        .resource_mut::<AccessLists>()
+        .banned_players
+        .ban(&name, ban)
+        .map_err(|err| format!("Could not write the bans: {err}"))?;
+    let online = context
+        .players()
+        .into_iter()
+        .filter(|(_, player)| player.eq_ignore_ascii_case(&name));
+    for (entity, _) in online.collect::<Vec<_>>() {
+        disconnect(context, entity, message.clone());
+    }
+    info!("{} banned {name}: {message}", context.name(context.sender));
+    Ok(vec![format!("Banned {name}.")])
+}
+
+fn pardon(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let name = arguments.word()?;
+    arguments.end()?;
+    let pardoned = context
+        .ecs
+        .resource_mut::<AccessLists>()
+        .banned_players
+        .pardon(name)
+        .map_err(|err| format!("Could not write the bans: {err}"))?;
+    if !pardoned {
+        return Err(format!("{name} is not banned.").into());
+    }
+    info!("{} pardoned {name}.", context.name(context.sender));
+    Ok(vec![format!("Pardoned {name}.")])
+}
+
+fn ban_ip(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let target = arguments.word()?;
+    let players = addresses(context);
+    let address = match target.parse::<IpAddr>() {
+        Ok(address) => address,
+        Err(_) => players
+            .iter()
+            .find(|(_, name, _)| name.eq_ignore_ascii_case(target))
+            .ok_or(format!("{target} is not online and not an address."))?
+            .2
+            .ok_or(format!("The address of {target} is unknown."))?,
+    };
+    let ban = parse_ban(context, arguments);
+    let message = ban.message(ban.created);
+    context
+        .ecs
+        .resource_mut::<AccessLists>()
+        .banned_ips
+        .ban(&address.to_string(), ban)
+        .map_err(|err| format!("Could not write the bans: {err}"))?;
+    let mut kicked = 0;
+    for (entity, _, _) in players.iter().filter(|(_, _, a)| *a == Some(address)) {
+        disconnect(context, *entity, message.clone());
+        kicked += 1;
+    }
+    info!(
+        "{} banned {address}: {message}",
+        context.name(context.sender)
+    );
+    Ok(vec![format!("Banned {address}, kicking {kicked} players.")])
+}
+
+fn pardon_ip(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let address = arguments.word()?;
+    arguments.end()?;
+    let address = address
+        .parse::<IpAddr>()
+        .map_err(|_| format!("Not a valid address: {address}"))?;
+    let pardoned = context
+        .ecs
+        .resource_mut::<AccessLists>()
+        .banned_ips
+        .pardon(&address.to_string())
+        .map_err(|err| format!("Could not write the bans: {err}"))?;
+    if !pardoned {
+        return Err(format!("{address} is not banned.").into());
+    }
+    info!("{} pardoned {address}.", context.name(context.sender));
+    Ok(vec![format!("Pardoned {address}.")])
+}
+
+fn banlist(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let access = context.ecs.resource::<AccessLists>();
+    let (kind, list) = match arguments.optional() {
+        None | Some("players") => ("players", &access.banned_players),
+        Some("ips") => ("addresses", &access.banned_ips),
+        Some(_) => return Err(CommandError::Usage),
+    };
+    arguments.end()?;
+    let now = now();
+    let mut lines = vec![format!("Banned {kind}:")];
+    lines.extend(list.active(now).map(|(key, ban)| {
+        let expires = ban.expires.map_or(String::new(), |expires| {
+            format!(" §7for {}", format_duration(expires.saturating_sub(now)))
+        });
+        format!("{key}{expires} §7by {}: {}", ban.source, ban.reason)
+    }));
+    Ok(lines)
+}
+
+fn whitelist(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let sender = context.name(context.sender);
+    let action = arguments.word()?;
+    let name = match action {
+        "add" | "remove" => Some(arguments.word()?),
+        _ => None,
+    };
+    arguments.end()?;
+    if let "on" | "off" = action {
+        let enabled = action == "on";
+        // Kept in server.properties, so it survives a restart.
+        context
+            .ecs
+            .resource_mut::<Config>()
+            .set("white-list", &enabled.to_string())
+            .map_err(|err| format!("Could not write the settings: {err}"))?;
+        context.ecs.resource_mut::<AccessLists>().whitelist.enabled = enabled;
+        info!("{sender} turned the whitelist {action}.");
+        return Ok(vec![format!("The whitelist is now {action}.")]);
+    }
+    let mut access = context.ecs.resource_mut::<AccessLists>();
+    let whitelist = &mut access.whitelist;
+    let io_error = |err: io::Error| format!("Could not write the whitelist: {err}");
+    let line = match (action, name) {
+        ("list", _) => {
+            let state = if whitelist.enabled { "on" } else { "off" };
+            return Ok(vec![
+                format!("The whitelist is {state}:"),
+                whitelist.names.join(", "),
+            ]);
+        }
+        ("reload", _) => {
+            whitelist
+                .reload()
+                .map_err(|err| format!("Could not read the whitelist: {err}"))?;
+            "Whitelist reloaded.".to_string()
+        }
+        ("add", Some(name)) => match whitelist.set(name, true).map_err(io_error)? {
+            true => {
+                info!("{sender} added {name} to the whitelist.");
+                format!("Added {name} to the whitelist.")
+            }
+            false => return Err(format!("{name} is already on the whitelist.").into()),
+        },
+        ("remove", Some(name)) => match whitelist.set(name, false).map_err(io_error)? {
+            true => {
+                info!("{sender} removed {name} from the whitelist.");
+                format!("Removed {name} from the whitelist.")
+            }
+            false => return Err(format!("{name} is not on the whitelist.").into()),
+        },
+        _ => return Err(CommandError::Usage),
+    };
+    Ok(vec![line])
+}
+
+#[test]
+fn test_durations() {
+    assert_eq!(parse_duration("30m"), Some(30 * 60));
+    assert_eq!(parse_duration("1d12h"), Some(36 * 60 * 60));
+    assert_eq!(parse_duration("2w"), Some(14 * 24 * 60 * 60));
+    assert_eq!(parse_duration("12"), None);
+    assert_eq!(parse_duration("h"), None);
+    assert_eq!(parse_duration("griefing"), None);
+    assert_eq!(format_duration(36 * 60 * 60 + 59), "1d 12h");
+    assert_eq!(format_duration(90), "1m 30s");
+    assert_eq!(format_duration(3600), "1h");
+    assert_eq!(format_duration(0), "0s");
+}
+
+#[test]
+fn test_ban_list() {
+    let bans = parse_bans(
+        "# comment\nNotch\njeb_|1970-01-01 00:01:40 +0000|Alice|1970-01-01 00:03:20 +0000|Griefing | twice\n",
+    );
+    let list = BanList {
+        path: PathBuf::new(),
+        bans,
+    };
+    assert_eq!(list.get("notch", 1000).unwrap().expires, None);
+    let ban = list.get("Jeb_", 150).unwrap();
+    assert_eq!(ban.source, "Alice");
+    assert_eq!(ban.reason, "Griefing | twice");
+    assert_eq!(ban.message(150), "Banned for 50s: Griefing | twice");
+    assert!(list.get("jeb_", 200).is_none());
+    assert_eq!(list.active(200).count(), 1);
+
+    assert_eq!(format_date(1358278772), "2013-01-15 19:39:32 +0000");
+    assert_eq!(parse_date("2013-01-15 20:39:32 +0100"), Some(1358278772));
+    assert_eq!(parse_date("1969-12-31 19:00:00 -0500"), Some(0));
+    assert_eq!(parse_date("Forever"), None);
+    // A list written by a vanilla 1.4.7 server.
+    let vanilla = "# Updated 1/15/13 8:39 PM by Minecraft 1.4.7\n\
+        # victim name | ban date | banned by | banned until | reason\n\n\
+        griefer|2013-01-15 20:39:32 +0100|Notch|2013-01-16 20:39:32 +0100|Griefing the spawn\n\
+        spammer|2013-01-15 20:40:05 +0100|Server|Forever|Banned by an operator.\n";
+    let bans = parse_bans(vanilla);
+    assert_eq!(bans["griefer"].created, 1358278772);
+    assert_eq!(bans["griefer"].expires, Some(1358278772 + 24 * 60 * 60));
+    assert_eq!(bans["spammer"].expires, None);
+    assert_eq!(bans["spammer"].reason, "Banned by an operator.");
+    let written = format_bans(&bans, 1358278900);
+    assert_eq!(
+        written,
+        "# Updated 2013-01-15 19:41:40 +0000 by betalpha\n\
+        # victim name | ban date | banned by | banned until | reason\n\n\
+        griefer|2013-01-15 19:39:32 +0000|Notch|2013-01-16 19:39:32 +0000|Griefing the spawn\n\
+        spammer|2013-01-15 19:40:05 +0000|Server|Forever|Banned by an operator.\n"
+    );
+    assert_eq!(parse_bans(&written), bans);
+}
+
+#[test]
+fn test_admission() {
+    use crate::config::Properties;
+
+    let mut properties = Properties::parse("max-players=2\nduplicate-login=kick-old");
+    let settings = Settings::parse(&properties).unwrap();
+    let access_lists = AccessLists::default();
+    let permissions = Permissions::default();
+    let mut admission = Admission {
+        access_lists: &access_lists,
+        permissions: &permissions,
+        settings: &settings,
+        online: vec![(Entity::from_raw(1), "Notch".to_string())],
+    };
+    assert_eq!(admission.admit(Entity::from_raw(2), "jeb_"), Ok(None));
+    assert_eq!(
+        admission.admit(Entity::from_raw(3), "Steve"),
+        Err("The server is full!".to_string())
+    );
+    // Replacing a session works on a full server.
+    assert_eq!(
+        admission.admit(Entity::from_raw(4), "notch"),
+        Ok(Some(Entity::from_raw(1)))
+    );
+    assert_eq!(admission.online.len(), 2);
+
+    properties.set("duplicate-login", "refuse-new");
+    let refusing = Settings::parse(&properties).unwrap();
+    let mut admission = Admission {
+        settings: &refusing,
+        online: vec![(Entity::from_raw(1), "Notch".to_string())],
+        ..admission
+    };
+    assert!(admission.admit(Entity::from_raw(2), "NOTCH").is_err());
+}
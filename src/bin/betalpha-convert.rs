@@ -0,0 +1,121 @@
+use betalpha_mc::world::storage::{ChunkStorage, FsStorage, StorageFormat, WorldStorage};
+use betalpha_mc::world::Chunk;
+use clap::{Parser, ValueEnum};
+use log::{error, info, warn, Level};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Converts a world between the Alpha chunk directory layout and McRegion files.
+#[derive(Parser)]
+#[command(name = "betalpha-convert")]
+struct Args {
+    /// World to read from, its format is detected automatically.
+    source: PathBuf,
+    /// Directory the converted world is written to.
+    destination: PathBuf,
+    /// Format of the converted world.
+    #[arg(short, long, value_enum, default_value_t = Format::Region)]
+    format: Format,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Format {
+    Alpha,
+    Region,
+}
+
+impl From<Format> for StorageFormat {
+    fn from(value: Format) -> Self {
+        match value {
+            Format::Alpha => StorageFormat::Alpha,
+            Format::Region => StorageFormat::Region,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    simple_logger::init_with_level(Level::Info).expect("Failed to initialize logging!");
+    let args = Args::parse();
+    match convert(&args) {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(mismatches) => {
+            error!("{mismatches} chunks do not match after conversion!");
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            error!("Conversion failed: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Copies every chunk and returns the number of chunks whose block data differs afterwards.
+fn convert(args: &Args) -> std::io::Result<usize> {
+    let source_format = StorageFormat::detect(&args.source);
+    let source_world = FsStorage::with_format(&args.source, source_format);
+    let destination_world = FsStorage::with_format(&args.destination, args.format.into());
+    let (source, destination) = (source_world.chunks(), destination_world.chunks());
+    info!(
+        "Converting {:?} from {source_format:?} to {:?} in {:?}.",
+        args.source,
+        StorageFormat::from(args.format),
+        args.destination
+    );
+
+    destination_world.write_level(&source_world.read_level()?)?;
+    copy_players(&args.source, &args.destination)?;
+
+    let chunks = source.list_chunks()?;
+    info!("Found {} chunks.", chunks.len());
+    for (index, (x, z)) in chunks.iter().enumerate() {
+        match source.read_chunk(*x, *z) {
+            Ok(blob) => destination.write_chunk(*x, *z, &blob)?,
+            Err(err) => warn!("Skipping unreadable chunk at (x: {x}, z: {z}): {err}"),
+        }
+        if (index + 1) % 1000 == 0 {
+            info!("Converted {}/{} chunks.", index + 1, chunks.len());
+        }
+    }
+
+    info!("Verifying block data...");
+    let mismatches = chunks
+        .iter()
+        .filter(|(x, z)| !blocks_match(source, destination, *x, *z))
+        .count();
+    info!("Done.");
+    Ok(mismatches)
+}
+
+fn blocks_match(source: &dyn ChunkStorage, destination: &dyn ChunkStorage, x: i32, z: i32) -> bool {
+    match (Chunk::load(source, x, z), Chunk::load(destination, x, z)) {
+        (Ok(a), Ok(b)) => {
+            let matches = a.blocks() == b.blocks() && a.data() == b.data();
+            if !matches {
+                warn!("Block data of chunk at (x: {x}, z: {z}) differs!");
+            }
+            matches
+        }
+        // Chunks that were unreadable to begin with are reported during the copy.
+        (Err(_), _) => true,
+        (Ok(_), Err(err)) => {
+            warn!("Converted chunk at (x: {x}, z: {z}) can not be read: {err}");
+            false
+        }
+    }
+}
+
+fn copy_players(source: &Path, destination: &Path) -> std::io::Result<()> {
+    let players = source.join("players");
+    if !players.is_dir() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(destination.join("players"))?;
+    for entry in std::fs::read_dir(players)? {
+        let entry = entry?;
+        std::fs::copy(
+            entry.path(),
+            destination.join("players").join(entry.file_name()),
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use betalpha_mc::render::{self, Layout};
+use betalpha_mc::world::storage;
+use clap::Parser;
+use log::{error, info, Level};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Instant;
+
+/// Renders a top-down map of a world into PNG images.
+#[derive(Parser)]
+#[command(name = "betalpha-render")]
+struct Args {
+    /// World to render, its format is detected automatically.
+    world: PathBuf,
+    /// Directory the images are written to.
+    output: PathBuf,
+    /// Write one image of the whole world instead of tiles.
+    #[arg(long)]
+    stitch: bool,
+    /// Render every chunk, not only the ones changed since the last run.
+    #[arg(long)]
+    full: bool,
+}
+
+fn main() -> ExitCode {
+    simple_logger::init_with_level(Level::Info).expect("Failed to initialize logging!");
+    let args = Args::parse();
+    let layout = if args.stitch {
+        Layout::Stitched
+    } else {
+        Layout::Tiles
+    };
+    let start = Instant::now();
+    match render::render_world(
+        storage::open(&args.world).as_ref(),
+        &args.output,
+        layout,
+        !args.full,
+    ) {
+        Ok(stats) => {
+            info!(
+                "Rendered {} chunks ({} unchanged, {} failed) into {} images in {:.2?}.",
+                stats.rendered,
+                stats.unchanged,
+                stats.failed,
+                stats.images,
+                start.elapsed()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            error!("Rendering failed: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
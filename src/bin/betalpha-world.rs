@@ -0,0 +1,300 @@
+use betalpha_mc::registry;
+use betalpha_mc::world::edit::{self, Clipboard, Cuboid};
+use betalpha_mc::world::storage::{FsStorage, WorldStorage};
+use betalpha_mc::world::{schematic, Chunk, World};
+use clap::{Parser, Subcommand};
+use rayon::prelude::*;
+use serde_json::json;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Inspects and edits worlds without starting the server.
+#[derive(Parser)]
+#[command(name = "betalpha-world")]
+struct Args {
+    /// Directory of the world, its chunk format is detected automatically.
+    world: PathBuf,
+    /// Print JSON instead of human-readable text.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the content of `level.dat`.
+    Level,
+    /// List the coordinates of every chunk in the world.
+    Chunks,
+    /// Dump the blocks of a chunk layer by layer, skipping layers that only contain air.
+    Dump {
+        /// Chunk x coordinate.
+        #[arg(allow_hyphen_values = true)]
+        x: i32,
+        /// Chunk z coordinate.
+        #[arg(allow_hyphen_values = true)]
+        z: i32,
+    },
+    /// Count how often each block type occurs across the world.
+    Count,
+    /// Move the world spawn.
+    SetSpawn {
+        #[arg(allow_hyphen_values = true)]
+        x: i32,
+        y: i32,
+        #[arg(allow_hyphen_values = true)]
+        z: i32,
+    },
+    /// Change the world seed.
+    SetSeed {
+        #[arg(allow_hyphen_values = true)]
+        seed: i64,
+    },
+    /// Paste an MCEdit schematic into the world, blocks in chunks that do not exist are skipped.
+    Paste {
+        schematic: PathBuf,
+        #[arg(allow_hyphen_values = true)]
+        x: i32,
+        y: i32,
+        #[arg(allow_hyphen_values = true)]
+        z: i32,
+        /// Put the corner with the smallest coordinates at the position instead of applying the offset stored in the schematic.
+        #[arg(long)]
+        at_corner: bool,
+    },
+    /// Save the blocks between two corners as an MCEdit schematic.
+    Export {
+        schematic: PathBuf,
+        #[arg(allow_hyphen_values = true)]
+        x1: i32,
+        y1: i32,
+        #[arg(allow_hyphen_values = true)]
+        z1: i32,
+        #[arg(allow_hyphen_values = true)]
+        x2: i32,
+        y2: i32,
+        #[arg(allow_hyphen_values = true)]
+        z2: i32,
+    },
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args, &mut std::io::stdout().lock()) {
+        // Piping into tools like `head` closes stdout early, which is not worth an error.
+        Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+fn run(args: &Args, out: &mut impl Write) -> std::io::Result<()> {
+    let storage = FsStorage::open(&args.world);
+    match args.command {
+        Command::Level => {
+            let blob = storage.read_level()?;
+            if args.json {
+                writeln!(out, "{}", serde_json::to_string_pretty(&blob)?)?;
+            } else {
+                writeln!(out, "{blob}")?;
+            }
+        }
+        Command::Chunks => {
+            let mut chunks = storage.chunks().list_chunks()?;
+            chunks.sort();
+            if args.json {
+                let chunks = chunks
+                    .iter()
+                    .map(|(x, z)| json!({ "x": x, "z": z }))
+                    .collect::<Vec<_>>();
+                writeln!(out, "{}", serde_json::to_string_pretty(&chunks)?)?;
+            } else {
+                for (x, z) in &chunks {
+                    writeln!(out, "{x:>6} {z:>6}")?;
+                }
+                writeln!(out, "{} chunks", chunks.len())?;
+            }
+        }
+        Command::Dump { x, z } => dump(out, &Chunk::load(storage.chunks(), x, z)?, args.json)?,
+        Command::Count => count(out, &storage, args.json)?,
+        Command::SetSpawn { x, y, z } => {
+            let mut world = World::load(Box::new(storage))?;
+            world.set_spawn([x, y, z]);
+            world.save_level()?;
+            if args.json {
+                let result = json!({ "spawn": { "x": x, "y": y, "z": z } });
+                writeln!(out, "{}", serde_json::to_string_pretty(&result)?)?;
+            } else {
+                writeln!(out, "Spawn set to (x: {x}, y: {y}, z: {z}).")?;
+            }
+        }
+        Command::SetSeed { seed } => {
+            let mut world = World::load(Box::new(storage))?;
+            world.set_seed(seed);
+            world.save_level()?;
+            if args.json {
+                let result = json!({ "seed": seed });
+                writeln!(out, "{}", serde_json::to_string_pretty(&result)?)?;
+            } else {
+                writeln!(out, "Seed set to {seed}.")?;
+            }
+        }
+        Command::Paste {
+            ref schematic,
+            x,
+            y,
+            z,
+            at_corner,
+        } => {
+            let mut clipboard = schematic::load(schematic)?;
+            if at_corner {
+                clipboard.offset = [0, 0, 0];
+            }
+            let mut world = World::load(Box::new(storage))?;
+            let changes = clipboard.paste([x, y, z]);
+            let changed = edit::apply(&mut world, &changes).len();
+            let tile_entities = clipboard
+                .paste_tile_entities([x, y, z])
+                .into_iter()
+                .filter(|tile_entity| world.set_tile_entity(tile_entity.clone()))
+                .count();
+            world.save()?;
+            let skipped = changes.len() - changed;
+            if args.json {
+                let result = json!({
+                    "changed": changed,
+                    "skipped": skipped,
+                    "tile_entities": tile_entities,
+                });
+                writeln!(out, "{}", serde_json::to_string_pretty(&result)?)?;
+            } else {
+                writeln!(
+                    out,
+                    "{changed} blocks and {tile_entities} tile entities pasted, {skipped} blocks outside of the world skipped."
+                )?;
+            }
+        }
+        Command::Export {
+            ref schematic,
+            x1,
+            y1,
+            z1,
+            x2,
+            y2,
+            z2,
+        } => {
+            let cuboid = Cuboid::new([x1, y1, z1], [x2, y2, z2]);
+            let mut world = World::load(Box::new(storage))?;
+            let clipboard = Clipboard::copy(&mut world, &cuboid, cuboid.min);
+            schematic::save(&clipboard, schematic)?;
+            let [x, y, z] = clipboard.size();
+            if args.json {
+                let result = json!({
+                    "size": { "x": x, "y": y, "z": z },
+                    "tile_entities": clipboard.tile_entities.len(),
+                });
+                writeln!(out, "{}", serde_json::to_string_pretty(&result)?)?;
+            } else {
+                writeln!(
+                    out,
+                    "Exported {x}x{y}x{z} blocks and {} tile entities.",
+                    clipboard.tile_entities.len()
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn dump(out: &mut impl Write, chunk: &Chunk, as_json: bool) -> std::io::Result<()> {
+    let (chunk_x, chunk_z) = chunk.get_position();
+    let layers = (0..128u8)
+        .rev()
+        .map(|y| {
+            let rows = (0..16u8)
+                .map(|z| {
+                    (0..16u8)
+                        .map(|x| chunk.get_block(x, y, z).unwrap_or_default())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            (y, rows)
+        })
+        .filter(|(_, rows)| rows.iter().flatten().any(|id| *id != registry::block::AIR))
+        .collect::<Vec<_>>();
+
+    if as_json {
+        let layers = layers
+            .iter()
+            .map(|(y, rows)| json!({ "y": y, "blocks": rows }))
+            .collect::<Vec<_>>();
+        let dump = json!({ "x": chunk_x, "z": chunk_z, "layers": layers });
+        // Pretty printing would put every block id on its own line.
+        writeln!(out, "{}", serde_json::to_string(&dump)?)?;
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "Chunk (x: {chunk_x}, z: {chunk_z}), rows are z, columns are x"
+    )?;
+    for (y, rows) in &layers {
+        writeln!(out, "y = {y}")?;
+        for row in rows {
+            let row = row.iter().map(|id| format!("{id:>3}")).collect::<Vec<_>>();
+            writeln!(out, "  {}", row.join(" "))?;
+        }
+    }
+    Ok(())
+}
+
+fn count(out: &mut impl Write, storage: &FsStorage, as_json: bool) -> std::io::Result<()> {
+    let chunks = storage.chunks().list_chunks()?;
+    let counts = chunks
+        .par_iter()
+        .map(|(x, z)| {
+            let mut counts = [0u64; 256];
+            // Unreadable chunks are skipped instead of failing the whole count.
+            if let Ok(chunk) = Chunk::load(storage.chunks(), *x, *z) {
+                for id in chunk.blocks() {
+                    counts[*id as usize] += 1;
+                }
+            }
+            counts
+        })
+        .reduce(
+            || [0u64; 256],
+            |mut a, b| {
+                a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                a
+            },
+        );
+
+    let mut counts = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(id, count)| (id as u8, *count))
+        .collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let name = |id: u8| registry::block::get(id).map_or("unknown", |b| b.name);
+    if as_json {
+        let counts = counts
+            .iter()
+            .map(|(id, count)| json!({ "id": id, "name": name(*id), "count": count }))
+            .collect::<Vec<_>>();
+        let result = json!({ "chunks": chunks.len(), "blocks": counts });
+        writeln!(out, "{}", serde_json::to_string_pretty(&result)?)?;
+    } else {
+        writeln!(out, "{} chunks", chunks.len())?;
+        for (id, count) in &counts {
+            writeln!(out, "{id:>3} {:<24} {count:>12}", name(*id))?;
+        }
+    }
+    Ok(())
+}
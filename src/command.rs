@@ -0,0 +1,613 @@
+//! Chat commands, a message starting with `/` runs the command registered under its first word.
+//!
+//! Commands parse their own arguments with [`Arguments`] and return the lines to reply to the sender.
+use crate::config::Config;
+use crate::entity::{connection_state, Look, Named, Position};
+use crate::event::{CommandEvent, SendPacketEvent};
+use crate::packet::{to_client_packets, Serialize};
+use crate::permission::Permissions;
+use crate::registry;
+use crate::world::World;
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::{Component, Entity, Events, Local, Mut, Resource, With};
+use log::info;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+pub type CommandResult = Result<Vec<String>, CommandError>;
+
+type Handler = Box<dyn Fn(&mut CommandContext, &mut Arguments) -> CommandResult + Send + Sync>;
+
+pub enum CommandError {
+    /// The arguments do not fit the command, the sender gets its usage.
+    Usage,
+    Failed(String),
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        Self::Failed(message.to_string())
+    }
+}
+
+pub struct Command {
+    /// Node a player needs to run the command.
+    pub permission: &'static str,
+    pub usage: &'static str,
+    pub description: &'static str,
+    handler: Handler,
+}
+
+impl Command {
+    pub fn new(
+        permission: &'static str,
+        usage: &'static str,
+        description: &'static str,
+        handler: impl Fn(&mut CommandContext, &mut Arguments) -> CommandResult + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            permission,
+            usage,
+            description,
+            handler: Box::new(handler),
+        }
+    }
+}
+
+/// Every command by the name it is run with.
+#[derive(Resource, Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<&'static str, Command>,
+}
+
+impl CommandRegistry {
+    pub fn register(&mut self, name: &'static str, command: Command) {
+        self.commands.insert(name, command);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.get(name)
+    }
+}
+
+/// A sender that is not a player, like the remote console, it may use every command.
+#[derive(Component)]
+pub struct Console {
+    pub name: String,
+}
+
+/// What a command gets to work with, the whole ECS world and who ran it.
+pub struct CommandContext<'a> {
+    pub ecs: &'a mut bevy::prelude::World,
+    pub sender: Entity,
+    pub registry: &'a CommandRegistry,
+}
+
+impl CommandContext<'_> {
+    pub fn has_permission(&self, node: &str) -> bool {
+        if self.ecs.get::<Console>(self.sender).is_some() {
+            return true;
+        }
+        let name = self.name(self.sender);
+        self.ecs.resource::<Permissions>().has(&name, node)
+    }
+
+    pub fn send<T: Serialize>(&mut self, entity: Entity, packet: T) {
+        self.ecs
+            .send_event(SendPacketEvent::new(entity, packet).unwrap());
+    }
+
+    pub fn message(&mut self, entity: Entity, message: String) {
+        self.send(entity, to_client_packets::ChatMessagePacket { message });
+    }
+
+    /// Names of the players in the world.
+    pub fn players(&mut self) -> Vec<(Entity, String)> {
+        let mut query = self
+            .ecs
+            .query_filtered::<(Entity, &Named), With<connection_state::Playing>>();
+        query
+            .iter(self.ecs)
+            .map(|(entity, named)| (entity, named.name.clone()))
+            .collect()
+    }
+
+    pub fn name(&self, entity: Entity) -> String {
+        match self.ecs.get::<Console>(entity) {
+            Some(console) => console.name.clone(),
+            None => self
+                .ecs
+                .get::<Named>(entity)
+                .map_or_else(String::new, |named| named.name.clone()),
+        }
+    }
+
+    pub fn position(&self, entity: Entity) -> Option<[f64; 3]> {
+        let position = self.ecs.get::<Position>(entity)?;
+        Some([position.x, position.y, position.z])
+    }
+
+    /// Moves a player, the chunks around the new position are sent by `load_chunks`.
+    pub fn teleport(&mut self, entity: Entity, [x, y, z]: [f64; 3]) {
+        let (yaw, pitch) = self
+            .ecs
+            .get::<Look>(entity)
+            .map_or((0.0, 0.0), |look| (look.yaw, look.pitch));
+        if let Some(mut position) = self.ecs.get_mut::<Position>(entity) {
+            position.x = x;
+            position.y = y;
+            position.z = z;
+            position.stance = y + 1.65;
+        }
+        let packet = to_client_packets::ServerPositionLookPacket {
+            x,
+            stance: y + 1.75,
+            y,
+            z,
+            yaw,
+            pitch,
+            on_ground: false,
+        };
+        self.send(entity, packet);
+    }
+}
+
+/// The words following the name of a command.
+pub struct Arguments<'a> {
+    words: Vec<&'a str>,
+    next: usize,
+}
+
+impl<'a> Arguments<'a> {
+    pub fn new(line: &'a str) -> Self {
+        Self {
+            words: line.split_whitespace().collect(),
+            next: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.next >= self.words.len()
+    }
+
+    /// Number of arguments that were not taken yet.
+    pub fn remaining(&self) -> usize {
+        self.words.len().saturating_sub(self.next)
+    }
+
+    pub fn optional(&mut self) -> Option<&'a str> {
+        let word = self.words.get(self.next).copied();
+        self.next += 1;
+        word
+    }
+
+    pub fn word(&mut self) -> Result<&'a str, CommandError> {
+        self.optional().ok_or(CommandError::Usage)
+    }
+
+    /// Every argument that is left, joined by spaces.
+    pub fn rest(&mut self) -> Option<String> {
+        let rest = self.words.get(self.next..).filter(|rest| !rest.is_empty());
+        self.next = self.words.len();
+        rest.map(|rest| rest.join(" "))
+    }
+
+    /// Fails with the usage if there are arguments left.
+    pub fn end(&self) -> Result<(), CommandError> {
+        match self.is_empty() {
+            true => Ok(()),
+            false => Err(CommandError::Usage),
+        }
+    }
+
+    pub fn integer<T: FromStr>(&mut self) -> Result<T, CommandError> {
+        let word = self.word()?;
+        word.parse()
+            .map_err(|_| CommandError::Failed(format!("Not a valid number: {word}")))
+    }
+
+    /// A player in the world by name, ignoring case.
+    pub fn player(&mut self, context: &mut CommandContext) -> Result<Entity, CommandError> {
+        let name = self.word()?;
+        context
+            .players()
+            .into_iter()
+            .find(|(_, player)| player.eq_ignore_ascii_case(name))
+            .map(|(entity, _)| entity)
+            .ok_or(CommandError::Failed(format!("{name} is not online.")))
+    }
+
+    /// Three coordinates, where `~` and `~<offset>` are relative to `origin`.
+    pub fn coordinates(&mut self, origin: [f64; 3]) -> Result<[f64; 3], CommandError> {
+        let mut coordinates = [0.0; 3];
+        for (coordinate, origin) in coordinates.iter_mut().zip(origin) {
+            let word = self.word()?;
+            *coordinate = parse_coordinate(word, origin).ok_or(CommandError::Failed(format!(
+                "Not a valid coordinate: {word}"
+            )))?;
+        }
+        Ok(coordinates)
+    }
+
+    /// A block by name or id, optionally followed by `:` and its metadata, e.g. `wool:14`.
+    pub fn block(&mut self) -> Result<(u8, Option<u8>), CommandError> {
+        Ok(parse_block(self.word()?)?)
+    }
+
+    /// An item or block by name or id.
+    pub fn item(&mut self) -> Result<u16, CommandError> {
+        let word = self.word()?;
+        let item = match word.parse::<u16>() {
+            Ok(id) => registry::item::get(id).map(|item| item.id).or_else(|| {
+                let block = registry::block::get(u8::try_from(id).ok()?)?;
+                Some(block.id as u16)
+            }),
+            Err(_) => registry::item::by_name(word)
+                .map(|item| item.id)
+                .or_else(|| registry::block::by_name(word).map(|block| block.id as u16)),
+        };
+        item.ok_or(CommandError::Failed(format!("Unknown item: {word}")))
+    }
+}
+
+fn parse_coordinate(word: &str, origin: f64) -> Option<f64> {
+    match word.strip_prefix('~') {
+        Some("") => Some(origin),
+        Some(offset) => Some(origin + offset.parse::<f64>().ok()?),
+        None => word.parse().ok(),
+    }
+    .filter(|coordinate| coordinate.is_finite())
+}
+
+/// Parses a block given by name or id, optionally followed by `:` and its metadata, e.g. `wool:14`.
+pub fn parse_block(argument: &str) -> Result<(u8, Option<u8>), String> {
+    let (block, data) = match argument.split_once(':') {
+        Some((block, data)) => {
+            let data = data
+                .parse::<u8>()
+                .ok()
+                .filter(|data| *data < 16)
+                .ok_or(format!("Invalid block metadata: {data}"))?;
+            (block, Some(data))
+        }
+        None => (argument, None),
+    };
+    let block = match block.parse::<u8>() {
+        Ok(id) => registry::block::get(id),
+        Err(_) => registry::block::by_name(block),
+    };
+    Ok((block.ok_or(format!("Unknown block: {argument}"))?.id, data))
+}
+
+/// Runs the commands players sent, outside of the other systems since commands may touch anything.
+pub fn run_commands(
+    ecs: &mut bevy::prelude::World,
+    mut reader: Local<ManualEventReader<CommandEvent>>,
+) {
+    let events = reader
+        .read(ecs.resource::<Events<CommandEvent>>())
+        .map(|event| (event.entity, event.command.clone()))
+        .collect::<Vec<_>>();
+    if events.is_empty() {
+        return;
+    }
+    ecs.resource_scope(|ecs, registry: Mut<CommandRegistry>| {
+        for (sender, line) in events {
+            let lines = execute(ecs, &registry, sender, &line);
+            let mut context = CommandContext {
+                ecs,
+                sender,
+                registry: &registry,
+            };
+            for line in lines {
+                context.message(sender, line);
+            }
+        }
+    });
+}
+
+/// Runs a command line without the leading `/` and returns the lines to reply to the sender.
+pub fn execute(
+    ecs: &mut bevy::prelude::World,
+    registry: &CommandRegistry,
+    sender: Entity,
+    line: &str,
+) -> Vec<String> {
+    let mut arguments = Arguments::new(line);
+    let Some(name) = arguments.optional() else {
+        return Vec::new();
+    };
+    let mut context = CommandContext {
+        ecs,
+        sender,
+        registry,
+    };
+    info!("{} ran /{line}", context.name(sender));
+    let result = match registry.get(name) {
+        None => Err(CommandError::Failed(format!(
+            "Unknown command: /{name}, see /help."
+        ))),
+        Some(command) if !context.has_permission(command.permission) => Err(CommandError::Failed(
+            format!("You are not allowed to use /{name}."),
+        )),
+        Some(command) => (command.handler)(&mut context, &mut arguments),
+    };
+    match result {
+        Ok(lines) => lines,
+        Err(CommandError::Usage) => {
+            let usage = registry.get(name).map_or("", |command| command.usage);
+            vec![format!("§cUsage: {usage}")]
+        }
+        Err(CommandError::Failed(message)) => vec![format!("§c{message}")],
+    }
+}
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(
+        "help",
+        Command::new(
+            "betalpha.command.help",
+            "/help [command]",
+            "Lists the commands you can use or explains one.",
+            help,
+        ),
+    );
+    registry.register(
+        "list",
+        Command::new(
+            "betalpha.command.list",
+            "/list",
+            "Lists the players in the world.",
+            list,
+        ),
+    );
+    registry.register(
+        "spawn",
+        Command::new(
+            "betalpha.command.spawn",
+            "/spawn",
+            "Teleports you to the spawn of the world.",
+            spawn,
+        ),
+    );
+    registry.register(
+        "tp",
+        Command::new(
+            "betalpha.command.tp",
+            "/tp [player] <target player | x y z>",
+            "Teleports you or a player to another player or to coordinates, ~ is relative.",
+            teleport,
+        ),
+    );
+    registry.register(
+        "give",
+        Command::new(
+            "betalpha.command.give",
+            "/give <player> <item> [count]",
+            "Gives a player up to 64 of an item.",
+            give,
+        ),
+    );
+    registry.register(
+        "kick",
+        Command::new(
+            "betalpha.command.kick",
+            "/kick <player> [reason]",
+            "Disconnects a player.",
+            kick,
+        ),
+    );
+    registry.register(
+        "time",
+        Command::new(
+            "betalpha.command.time",
+            "/time [set <ticks | day | night> | add <ticks>]",
+            "Shows or changes the time of day.",
+            time,
+        ),
+    );
+}
+
+fn help(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    if let Some(name) = arguments.optional() {
+        arguments.end()?;
+        let name = name.trim_start_matches('/');
+        let command = context
+            .registry
+            .get(name)
+            .ok_or(format!("Unknown command: /{name}"))?;
+        return Ok(vec![
+            command.usage.to_string(),
+            command.description.to_string(),
+        ]);
+    }
+    Ok(context
+        .registry
+        .commands
+        .values()
+        .filter(|command| context.has_permission(command.permission))
+        .map(|command| format!("{} §7{}", command.usage, command.description))
+        .collect())
+}
+
+fn list(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    arguments.end()?;
+    let mut names = context
+        .players()
+        .into_iter()
+        .map(|(_, name)| name)
+        .collect::<Vec<_>>();
+    names.sort_by_key(|name| name.to_lowercase());
+    let max_players = context.ecs.resource::<Config>().settings.max_players;
+    Ok(vec![format!(
+        "{}/{max_players} players online: {}",
+        names.len(),
+        names.join(", ")
+    )])
+}
+
+fn spawn(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    arguments.end()?;
+    context
+        .position(context.sender)
+        .ok_or("You are not in the world.")?;
+    let [x, y, z] = context.ecs.resource::<World>().get_spawn();
+    context.teleport(context.sender, [x as f64 + 0.5, y as f64, z as f64 + 0.5]);
+    Ok(vec!["Teleported to the spawn.".to_string()])
+}
+
+fn teleport(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let (player, destination) = match arguments.remaining() {
+        1 => {
+            context
+                .position(context.sender)
+                .ok_or("You are not in the world.")?;
+            (context.sender, arguments.player(context)?)
+        }
+        2 => (arguments.player(context)?, arguments.player(context)?),
+        3 => {
+            let sender = context.sender;
+            let origin = context
+                .position(sender)
+                .ok_or("You are not in the world.")?;
+            let coordinates = arguments.coordinates(origin)?;
+            return teleport_to(context, sender, coordinates);
+        }
+        4 => {
+            let player = arguments.player(context)?;
+            let origin = context
+                .position(player)
+                .ok_or("The player is not in the world.")?;
+            let coordinates = arguments.coordinates(origin)?;
+            return teleport_to(context, player, coordinates);
+        }
+        _ => return Err(CommandError::Usage),
+    };
+    let coordinates = context
+        .position(destination)
+        .ok_or("The target is not in the world.")?;
+    context.teleport(player, coordinates);
+    Ok(vec![format!(
+        "Teleported {} to {}.",
+        context.name(player),
+        context.name(destination)
+    )])
+}
+
+fn teleport_to(context: &mut CommandContext, player: Entity, [x, y, z]: [f64; 3]) -> CommandResult {
+    if !(0.0..128.0).contains(&y) {
+        return Err("The height has to be between 0 and 128.".into());
+    }
+    context.teleport(player, [x, y, z]);
+    Ok(vec![format!(
+        "Teleported {} to ({x:.1}, {y:.1}, {z:.1}).",
+        context.name(player)
+    )])
+}
+
+fn give(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let player = arguments.player(context)?;
+    let item = arguments.item()?;
+    let count = match arguments.is_empty() {
+        true => 1,
+        false => arguments.integer::<u8>()?,
+    };
+    arguments.end()?;
+    if !(1..=64).contains(&count) {
+        return Err("The count has to be between 1 and 64.".into());
+    }
+    let packet = to_client_packets::AddToInventoryPacket {
+        item_type: item,
+        count,
+        life: 0,
+    };
+    context.send(player, packet);
+    let name = match u8::try_from(item) {
+        Ok(id) => registry::block::get(id).map(|block| block.name),
+        Err(_) => registry::item::get(item).map(|item| item.name),
+    };
+    Ok(vec![format!(
+        "Gave {count} {} to {}.",
+        name.unwrap_or_default(),
+        context.name(player)
+    )])
+}
+
+fn kick(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let player = arguments.player(context)?;
+    let reason = arguments
+        .rest()
+        .unwrap_or_else(|| "Kicked by an operator.".to_string());
+    let name = context.name(player);
+    context
+        .ecs
+        .entity_mut(player)
+        .remove::<connection_state::Playing>()
+        .insert(connection_state::Disconnecting { reason });
+    Ok(vec![format!("Kicked {name}.")])
+}
+
+fn time(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let time = context.ecs.resource::<World>().get_time();
+    let time = match (arguments.optional(), arguments.optional()) {
+        (None, _) => return Ok(vec![format!("The time is {time}.")]),
+        (Some("set"), Some("day")) => 0,
+        (Some("set"), Some("night")) => 13000,
+        (Some("set"), Some(ticks)) => ticks
+            .parse::<u64>()
+            .map_err(|_| format!("Not a valid number: {ticks}"))?,
+        (Some("add"), Some(ticks)) => {
+            let ticks = ticks
+                .parse::<u64>()
+                .map_err(|_| format!("Not a valid number: {ticks}"))?;
+            // Only the time of day is kept, which also keeps large values from overflowing.
+            time % 24000 + ticks % 24000
+        }
+        _ => return Err(CommandError::Usage),
+    };
+    arguments.end()?;
+    let mut world = context.ecs.resource_mut::<World>();
+    world.set_time(time % 24000);
+    let time = world.get_time();
+    for (player, _) in context.players() {
+        context.send(player, to_client_packets::TimeUpdatePacket { time });
+    }
+    Ok(vec![format!("Set the time to {time}.")])
+}
+
+#[test]
+fn test_arguments() {
+    let mut arguments = Arguments::new("  ~ ~-2.5 10  stone wool:14  and the rest ");
+    assert_eq!(arguments.remaining(), 8);
+    assert!(matches!(
+        arguments.coordinates([1.0, 64.0, -3.0]),
+        Ok([x, y, z]) if x == 1.0 && y == 61.5 && z == 10.0
+    ));
+    assert!(matches!(arguments.block(), Ok((1, None))));
+    assert!(matches!(arguments.block(), Ok((35, Some(14)))));
+    assert!(matches!(arguments.end(), Err(CommandError::Usage)));
+    assert_eq!(arguments.rest().as_deref(), Some("and the rest"));
+    assert!(arguments.end().is_ok());
+    assert!(matches!(arguments.word(), Err(CommandError::Usage)));
+
+    let mut arguments = Arguments::new("~x 12 nan stick 271 stone 999");
+    assert!(matches!(
+        arguments.coordinates([0.0; 3]),
+        Err(CommandError::Failed(_))
+    ));
+    assert!(matches!(arguments.integer::<u8>(), Ok(12)));
+    assert!(matches!(
+        arguments.integer::<u8>(),
+        Err(CommandError::Failed(_))
+    ));
+    assert!(matches!(arguments.item(), Ok(280)));
+    assert!(matches!(arguments.item(), Ok(271)));
+    assert!(matches!(arguments.item(), Ok(1)));
+    assert!(matches!(arguments.item(), Err(CommandError::Failed(_))));
+}
@@ -0,0 +1,487 @@
+//! Settings of the server, read from `server.properties` and overridden by command line arguments.
+//!
+//! A missing file or missing keys are written with their defaults. The file is read again when it
+//! changes, settings that can not change while the server runs keep their old value until a restart.
+use crate::access::{AccessLists, DuplicateLogin};
+use crate::proxy::TrustedProxies;
+use crate::system::ChunkBudget;
+use crate::view_distance::ViewDistanceSettings;
+use betalpha_mc::world::generator::Generator;
+use bevy::prelude::{ResMut, Resource};
+use clap::Parser;
+use log::{info, warn, LevelFilter};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// Every key with its default value, in the order they are written.
+const DEFAULTS: &[(&str, &str)] = &[
+    ("bind", "0.0.0.0:25565"),
+    ("world", "./ExampleWorld"),
+    ("view-distance", "4"),
+    ("max-view-distance", "10"),
+    ("chunk-budget", "4"),
+    ("chunk-budget-bytes", "65536"),
+    ("max-players", "20"),
+    ("duplicate-login", "kick-old"),
+    ("connection-throttle", "10"),
+    ("trusted-proxies", ""),
+    ("motd", "A Minecraft Server"),
+    ("online-mode", "false"),
+    ("spawn-protection", "16"),
+    ("autosave-interval", "300"),
+    ("generator", "none"),
+    ("white-list", "false"),
+    ("log-level", "info"),
+    ("web-map", "127.0.0.1:8123"),
+    ("enable-rcon", "false"),
+    ("rcon-bind", "127.0.0.1:25575"),
+    ("rcon-password", ""),
+];
+
+/// Keys that are only read when the server starts.
+const RESTART_KEYS: &[&str] = &[
+    "bind",
+    "world",
+    "online-mode",
+    "generator",
+    "web-map",
+    "enable-rcon",
+    "rcon-bind",
+    "rcon-password",
+];
+
+/// Runs a Minecraft Alpha 1.2.6 server.
+#[derive(Parser)]
+#[command(name = "betalpha-mc")]
+pub struct Args {
+    /// Properties file with the settings, created with defaults if it does not exist.
+    #[arg(long, default_value = "server.properties")]
+    config: PathBuf,
+    /// Address to listen on, can be given more than once.
+    #[arg(long)]
+    bind: Vec<SocketAddr>,
+    /// Directory of the world.
+    #[arg(long)]
+    world: Option<PathBuf>,
+    /// View distance in chunks new players get.
+    #[arg(long)]
+    view_distance: Option<i32>,
+    #[arg(long)]
+    max_players: Option<usize>,
+    /// Message players get when they join.
+    #[arg(long)]
+    motd: Option<String>,
+    #[arg(long)]
+    online_mode: Option<bool>,
+    /// Radius around the spawn only players with `betalpha.build.spawn` can build in, 0 turns it off.
+    #[arg(long)]
+    spawn_protection: Option<i32>,
+    /// Seconds between saves of the world, 0 turns autosaving off.
+    #[arg(long)]
+    autosave_interval: Option<u64>,
+    /// Generator for chunks that do not exist yet: none or flat.
+    #[arg(long)]
+    generator: Option<Generator>,
+    #[arg(long)]
+    log_level: Option<LevelFilter>,
+}
+
+impl Args {
+    /// The properties given on the command line, they take precedence over the file.
+    fn overrides(&self) -> Vec<(&'static str, String)> {
+        [
+            (
+                "bind",
+                (!self.bind.is_empty()).then(|| {
+                    let binds = self.bind.iter().map(SocketAddr::to_string);
+                    binds.collect::<Vec<_>>().join(",")
+                }),
+            ),
+            (
+                "world",
+                self.world.as_ref().map(|w| w.display().to_string()),
+            ),
+            ("view-distance", self.view_distance.map(|v| v.to_string())),
+            ("max-players", self.max_players.map(|m| m.to_string())),
+            ("motd", self.motd.clone()),
+            ("online-mode", self.online_mode.map(|o| o.to_string())),
+            (
+                "spawn-protection",
+                self.spawn_protection.map(|s| s.to_string()),
+            ),
+            (
+                "autosave-interval",
+                self.autosave_interval.map(|a| a.to_string()),
+            ),
+            ("generator", self.generator.map(|g| g.to_string())),
+            ("log-level", self.log_level.map(|l| l.to_string())),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+        .collect()
+    }
+}
+
+/// The lines of a properties file, `key=value` pairs and comments starting with `#`.
+#[derive(Debug, Default, Clone)]
+pub struct Properties {
+    lines: Vec<String>,
+}
+
+impl Properties {
+    pub fn parse(properties: &str) -> Self {
+        Self {
+            lines: properties.lines().map(str::to_string).collect(),
+        }
+    }
+
+    fn entry(line: &str) -> Option<(&str, &str)> {
+        let line = line.trim_start();
+        if line.starts_with('#') || line.starts_with('!') {
+            return None;
+        }
+        let (key, value) = line.split_once('=')?;
+        Some((key.trim(), value.trim()))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| Self::entry(line))
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Replaces the value of `key` or appends it if the key is missing.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let line = format!("{key}={value}");
+        match self
+            .lines
+            .iter_mut()
+            .find(|line| Self::entry(line).is_some_and(|(k, _)| k == key))
+        {
+            Some(existing) => *existing = line,
+            None => self.lines.push(line),
+        }
+    }
+}
+
+impl std::fmt::Display for Properties {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.lines.iter().try_for_each(|line| writeln!(f, "{line}"))
+    }
+}
+
+/// Addresses to listen on, separated by commas. `[::]` also accepts IPv4 clients on most systems.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindAddresses(pub Vec<SocketAddr>);
+
+impl FromStr for BindAddresses {
+    type Err = String;
+
+    fn from_str(binds: &str) -> Result<Self, Self::Err> {
+        let binds = binds
+            .split(',')
+            .map(str::trim)
+            .filter(|bind| !bind.is_empty())
+            .map(|bind| bind.parse().map_err(|_| format!("Invalid address: {bind}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        match binds.is_empty() {
+            true => Err("No address to listen on.".to_string()),
+            false => Ok(Self(binds)),
+        }
+    }
+}
+
+/// An address that may be left empty to turn off what listens on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionalAddress(pub Option<SocketAddr>);
+
+impl FromStr for OptionalAddress {
+    type Err = String;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        match address.trim() {
+            "" => Ok(Self(None)),
+            address => address
+                .parse()
+                .map(|address| Self(Some(address)))
+                .map_err(|_| format!("Invalid address: {address}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub bind: BindAddresses,
+    pub world: PathBuf,
+    pub view_distance: i32,
+    pub max_view_distance: i32,
+    /// Chunks sent to a player per tick, the first chunk of a tick is always sent.
+    pub chunk_budget: usize,
+    /// Compressed bytes of chunk data sent to a player per tick.
+    pub chunk_budget_bytes: usize,
+    pub max_players: usize,
+    pub duplicate_login: DuplicateLogin,
+    /// Connections an address may open a minute, 0 turns the throttle off.
+    pub connection_throttle: u32,
+    /// Networks of proxies whose connections start with a PROXY protocol header.
+    pub trusted_proxies: TrustedProxies,
+    pub motd: String,
+    pub online_mode: bool,
+    pub spawn_protection: i32,
+    pub autosave_interval: u64,
+    pub generator: Generator,
+    pub whitelist: bool,
+    pub log_level: LevelFilter,
+    /// Address of the web map, empty turns the map off.
+    pub web_map: OptionalAddress,
+    /// Whether the remote console listens on `rcon_bind`, it needs a password.
+    pub enable_rcon: bool,
+    pub rcon_bind: SocketAddr,
+    pub rcon_password: String,
+}
+
+impl Settings {
+    pub fn parse(properties: &Properties) -> Result<Self, String> {
+        fn value<T: FromStr>(properties: &Properties, key: &str) -> Result<T, String> {
+            let default = DEFAULTS.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+            let value = properties.get(key).or(default).unwrap_or_default();
+            value
+                .parse()
+                .map_err(|_| format!("Invalid value for {key}: {value}"))
+        }
+        let settings = Self {
+            bind: value(properties, "bind")?,
+            world: value(properties, "world")?,
+            view_distance: value(properties, "view-distance")?,
+            max_view_distance: value(properties, "max-view-distance")?,
+            chunk_budget: value(properties, "chunk-budget")?,
+            chunk_budget_bytes: value(properties, "chunk-budget-bytes")?,
+            max_players: value(properties, "max-players")?,
+            duplicate_login: value(properties, "duplicate-login")?,
+            connection_throttle: value(properties, "connection-throttle")?,
+            trusted_proxies: value(properties, "trusted-proxies")?,
+            motd: value(properties, "motd")?,
+            online_mode: value(properties, "online-mode")?,
+            spawn_protection: value(properties, "spawn-protection")?,
+            autosave_interval: value(properties, "autosave-interval")?,
+            generator: value(properties, "generator")?,
+            whitelist: value(properties, "white-list")?,
+            log_level: value(properties, "log-level")?,
+            web_map: value(properties, "web-map")?,
+            enable_rcon: value(properties, "enable-rcon")?,
+            rcon_bind: value(properties, "rcon-bind")?,
+            rcon_password: value(properties, "rcon-password")?,
+        };
+        if !(1..=settings.max_view_distance).contains(&settings.view_distance) {
+            return Err(format!(
+                "view-distance has to be between 1 and max-view-distance ({}).",
+                settings.max_view_distance
+            ));
+        }
+        Ok(settings)
+    }
+
+    /// Takes the settings that may change while the server runs from `new`, returns the keys that did not.
+    fn update(&mut self, new: Settings) -> Vec<&'static str> {
+        let restart = [
+            ("bind", self.bind != new.bind),
+            ("world", self.world != new.world),
+            ("online-mode", self.online_mode != new.online_mode),
+            ("generator", self.generator != new.generator),
+            ("web-map", self.web_map != new.web_map),
+            ("enable-rcon", self.enable_rcon != new.enable_rcon),
+            ("rcon-bind", self.rcon_bind != new.rcon_bind),
+            ("rcon-password", self.rcon_password != new.rcon_password),
+        ];
+        debug_assert!(restart.iter().map(|(key, _)| key).eq(RESTART_KEYS));
+        *self = Settings {
+            bind: self.bind.clone(),
+            world: self.world.clone(),
+            online_mode: self.online_mode,
+            generator: self.generator,
+            web_map: self.web_map,
+            enable_rcon: self.enable_rcon,
+            rcon_bind: self.rcon_bind,
+            rcon_password: self.rcon_password.clone(),
+            ..new
+        };
+        restart
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(key, _)| key)
+            .collect()
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct Config {
+    path: PathBuf,
+    /// Modification time of the file when it was last read or written.
+    modified: Option<SystemTime>,
+    properties: Properties,
+    overrides: Vec<(&'static str, String)>,
+    pub settings: Settings,
+}
+
+impl Config {
+    pub fn load(args: &Args) -> io::Result<Self> {
+        let mut config = Self {
+            path: args.config.clone(),
+            modified: None,
+            properties: Properties::default(),
+            overrides: args.overrides(),
+            settings: Settings::parse(&Properties::default()).unwrap(),
+        };
+        config.settings = config.read()?;
+        Ok(config)
+    }
+
+    /// Reads the file, adds missing keys to it and parses the settings with the overrides applied.
+    fn read(&mut self) -> io::Result<Settings> {
+        let mut properties = match fs::read_to_string(&self.path) {
+            Ok(properties) => Properties::parse(&properties),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Properties::parse("# Settings of the betalpha server.")
+            }
+            Err(err) => return Err(err),
+        };
+        let missing = DEFAULTS
+            .iter()
+            .filter(|(key, _)| properties.get(key).is_none())
+            .copied()
+            .collect::<Vec<_>>();
+        for (key, value) in &missing {
+            properties.set(key, value);
+        }
+        if !missing.is_empty() {
+            fs::write(&self.path, properties.to_string())?;
+        }
+        self.modified = self.file_modified();
+        let settings = self
+            .parse(&properties)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+        self.properties = properties;
+        Ok(settings)
+    }
+
+    /// Parses the settings with the command line overrides applied.
+    fn parse(&self, properties: &Properties) -> Result<Settings, String> {
+        let mut properties = properties.clone();
+        for (key, value) in &self.overrides {
+            properties.set(key, value);
+        }
+        Settings::parse(&properties)
+    }
+
+    /// Reads the file again, returns the keys that changed but need a restart.
+    pub fn reload(&mut self) -> io::Result<Vec<&'static str>> {
+        let settings = self.read()?;
+        Ok(self.settings.update(settings))
+    }
+
+    /// Changes a key and writes the file, the key has to be one that may change while the server runs.
+    pub fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        debug_assert!(!RESTART_KEYS.contains(&key));
+        let mut properties = self.properties.clone();
+        properties.set(key, value);
+        let settings = self
+            .parse(&properties)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err))?;
+        fs::write(&self.path, properties.to_string())?;
+        self.modified = self.file_modified();
+        self.properties = properties;
+        self.settings.update(settings);
+        Ok(())
+    }
+
+    fn file_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+/// Hands the settings to the resources that hold a copy of them.
+pub fn apply(
+    settings: &Settings,
+    view_distance: &mut ViewDistanceSettings,
+    chunk_budget: &mut ChunkBudget,
+    access_lists: &mut AccessLists,
+) {
+    log::set_max_level(settings.log_level);
+    view_distance.default = settings.view_distance;
+    view_distance.max = settings.max_view_distance;
+    chunk_budget.chunks = settings.chunk_budget;
+    chunk_budget.bytes = settings.chunk_budget_bytes;
+    access_lists.whitelist.enabled = settings.whitelist;
+}
+
+/// Reloads the settings when `server.properties` changed on disk.
+pub fn reload_changed(
+    mut config: ResMut<Config>,
+    mut view_distance: ResMut<ViewDistanceSettings>,
+    mut chunk_budget: ResMut<ChunkBudget>,
+    mut access_lists: ResMut<AccessLists>,
+) {
+    if config.modified == config.file_modified() {
+        return;
+    }
+    match config.reload() {
+        Ok(restart) => {
+            info!("Settings reloaded after {} changed.", config.path.display());
+            if !restart.is_empty() {
+                warn!("Changes to {} need a restart.", restart.join(", "));
+            }
+            apply(
+                &config.settings,
+                &mut view_distance,
+                &mut chunk_budget,
+                &mut access_lists,
+            );
+        }
+        Err(err) => warn!("Could not reload the settings, keeping the old ones: {err}"),
+    }
+}
+
+#[test]
+fn test_properties() {
+    let mut properties =
+        Properties::parse("# comment\nmotd = Hello = World\n!old=1\nmax-players=5");
+    assert_eq!(properties.get("motd"), Some("Hello = World"));
+    assert_eq!(properties.get("old"), None);
+    properties.set("max-players", "8");
+    properties.set("generator", "flat");
+    assert_eq!(
+        properties.to_string(),
+        "# comment\nmotd = Hello = World\n!old=1\nmax-players=8\ngenerator=flat\n"
+    );
+    properties.set("bind", "");
+    assert!(Settings::parse(&properties).is_err());
+
+    properties.set("bind", "0.0.0.0:25565, [::1]:25566");
+    properties.set("web-map", "");
+    let mut settings = Settings::parse(&properties).unwrap();
+    assert_eq!(settings.web_map, OptionalAddress(None));
+    assert_eq!(settings.bind.0.len(), 2);
+    assert_eq!(settings.bind.0[1], "[::1]:25566".parse().unwrap());
+    assert_eq!(settings.max_players, 8);
+    assert_eq!(settings.generator, Generator::Flat);
+    assert_eq!(settings.view_distance, 4);
+    assert_eq!(settings.chunk_budget, 4);
+    assert_eq!(settings.chunk_budget_bytes, 64 * 1024);
+    properties.set("view-distance", "12");
+    assert!(Settings::parse(&properties).is_err());
+
+    properties.set("view-distance", "6");
+    properties.set("generator", "none");
+    let restart = settings.update(Settings::parse(&properties).unwrap());
+    assert_eq!(restart, vec!["generator"]);
+    assert_eq!(settings.view_distance, 6);
+    assert_eq!(settings.generator, Generator::Flat);
+}
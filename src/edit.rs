@@ -0,0 +1,492 @@
+use crate::command::{
+    Arguments, Command, CommandContext, CommandError, CommandRegistry, CommandResult,
+};
+use crate::entity::{Holding, Named};
+use crate::event::{
+    ChunkUpdateEvent, Face, PlayerBlockPlacementEvent, PlayerDiggingEvent, SendPacketEvent,
+};
+use crate::packet::to_client_packets;
+use crate::permission::Permissions;
+use crate::world::edit::{self, BlockChange, Clipboard, Cuboid};
+use crate::world::{schematic, World};
+use bevy::prelude::{Entity, EventReader, EventWriter, Mut, Query, Res};
+use std::collections::{HashMap, VecDeque};
+
+/// Item that selects the corners of a selection: left click for the first, right click for the second.
+pub const WAND: u16 = 271;
+/// Number of edits a player can undo.
+const HISTORY_SIZE: usize = 16;
+/// Largest number of blocks a single edit may touch.
+const MAX_VOLUME: usize = 1 << 20;
+/// Directory the `.schematic` files of `//schematic` are kept in.
+const SCHEMATIC_DIRECTORY: &str = "./schematics";
+/// Node needed to select corners, with the wand or the commands.
+const SELECTION: &str = "betalpha.edit.selection";
+
+type EditHandler = fn(&mut Context, &mut Arguments) -> CommandResult;
+
+/// The edit commands with their permission, usage and description, they are run with a double slash like `//set stone`.
+const COMMANDS: &[(&str, &str, &str, &str, EditHandler)] = &[
+    (
+        "/wand",
+        "betalpha.edit.wand",
+        "//wand",
+        "Gives you the selection wand.",
+        give_wand,
+    ),
+    (
+        "/pos1",
+        SELECTION,
+        "//pos1",
+        "Selects your position as the first corner.",
+        pos1,
+    ),
+    (
+        "/pos2",
+        SELECTION,
+        "//pos2",
+        "Selects your position as the second corner.",
+        pos2,
+    ),
+    (
+        "/size",
+        SELECTION,
+        "//size",
+        "Shows the size of the selection.",
+        size,
+    ),
+    (
+        "/set",
+        "betalpha.edit.set",
+        "//set <block>",
+        "Fills the selection.",
+        set,
+    ),
+    (
+        "/walls",
+        "betalpha.edit.walls",
+        "//walls <block>",
+        "Builds walls around the selection.",
+        walls,
+    ),
+    (
+        "/replace",
+        "betalpha.edit.replace",
+        "//replace <from> <to>",
+        "Replaces one block in the selection.",
+        replace,
+    ),
+    (
+        "/copy",
+        "betalpha.edit.clipboard",
+        "//copy",
+        "Copies the selection relative to you.",
+        copy,
+    ),
+    (
+        "/paste",
+        "betalpha.edit.clipboard",
+        "//paste",
+        "Pastes the clipboard at your position.",
+        paste,
+    ),
+    (
+        "/rotate",
+        "betalpha.edit.clipboard",
+        "//rotate <90 | 180 | 270>",
+        "Rotates the clipboard clockwise.",
+        rotate,
+    ),
+    (
+        "/undo",
+        "betalpha.edit.history",
+        "//undo",
+        "Undoes your last edit.",
+        undo,
+    ),
+    (
+        "/redo",
+        "betalpha.edit.history",
+        "//redo",
+        "Redoes the last edit you undid.",
+        redo,
+    ),
+    (
+        "/schematic",
+        "betalpha.edit.schematic",
+        "//schematic <load | save | paste> <name>",
+        "Loads, saves or pastes a schematic file.",
+        schematic_file,
+    ),
+];
+
+/// Selection, clipboard and history of the edits of a player.
+#[derive(bevy::prelude::Component, Default)]
+pub struct EditSession {
+    first: Option<[i32; 3]>,
+    second: Option<[i32; 3]>,
+    pub clipboard: Option<Clipboard>,
+    undo: VecDeque<Vec<BlockChange>>,
+    redo: Vec<Vec<BlockChange>>,
+}
+
+impl EditSession {
+    fn selection(&self) -> Result<Cuboid, String> {
+        match (self.first, self.second) {
+            (Some(first), Some(second)) => Ok(Cuboid::new(first, second)),
+            _ => Err("Select two corners with //pos1 and //pos2 or the wand first.".to_string()),
+        }
+    }
+
+    /// Remembers how to undo an edit, which makes everything that was undone before final.
+    pub fn record(&mut self, undo: Vec<BlockChange>) {
+        self.undo.push_back(undo);
+        if self.undo.len() > HISTORY_SIZE {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+}
+
+/// Selects corners with the wand, the client side effects are undone by `digging` and `placing`.
+pub fn wand(
+    mut packet_event_emitter: EventWriter<SendPacketEvent>,
+    mut digging_collector: EventReader<PlayerDiggingEvent>,
+    mut placement_collector: EventReader<PlayerBlockPlacementEvent>,
+    permissions: Res<Permissions>,
+    mut query: Query<(&mut EditSession, &Named, Option<&Holding>)>,
+) {
+    for event in digging_collector.read() {
+        let PlayerDiggingEvent::Started {
+            entity, x, y, z, ..
+        } = event
+        else {
+            continue;
+        };
+        if let Ok((mut session, named, Some(Holding { item_id: WAND }))) = query.get_mut(*entity) {
+            if !permissions.has(&named.name, SELECTION) {
+                continue;
+            }
+            session.first = Some([*x, *y as i32, *z]);
+            let message = selection_message("First", [*x, *y as i32, *z], &session);
+            reply(&mut packet_event_emitter, *entity, message);
+        }
+    }
+    for event in placement_collector.read() {
+        if event.id != WAND || matches!(event.direction, Face::UNKNOWN) {
+            continue;
+        }
+        if let Ok((mut session, named, _)) = query.get_mut(event.entity) {
+            if !permissions.has(&named.name, SELECTION) {
+                continue;
+            }
+            let position = [event.x, event.y as i32, event.z];
+            session.second = Some(position);
+            let message = selection_message("Second", position, &session);
+            reply(&mut packet_event_emitter, event.entity, message);
+        }
+    }
+}
+
+pub fn register(registry: &mut CommandRegistry) {
+    for &(name, permission, usage, description, handler) in COMMANDS {
+        let run = move |context: &mut CommandContext, arguments: &mut Arguments| {
+            run(context, arguments, handler)
+        };
+        registry.register(name, Command::new(permission, usage, description, run));
+    }
+}
+
+/// Runs an edit command with the selection, clipboard and history of the player.
+fn run(
+    context: &mut CommandContext,
+    arguments: &mut Arguments,
+    handler: EditHandler,
+) -> CommandResult {
+    let entity = context.sender;
+    let position = context
+        .position(entity)
+        .ok_or("You are not in the world.")?
+        .map(|c| c.floor() as i32);
+    let mut session = match context.ecs.get_mut::<EditSession>(entity) {
+        Some(mut session) => std::mem::take(&mut *session),
+        None => return Err("You are not in the world.".into()),
+    };
+    let (result, chunk_updates, packets) =
+        context.ecs.resource_scope(|_, mut world: Mut<World>| {
+            let mut context = Context {
+                entity,
+                world: &mut world,
+                session: &mut session,
+                position,
+                chunk_updates: Vec::new(),
+                packets: Vec::new(),
+            };
+            let result = handler(&mut context, arguments);
+            (result, context.chunk_updates, context.packets)
+        });
+    context.ecs.entity_mut(entity).insert(session);
+    for event in chunk_updates {
+        context.ecs.send_event(event);
+    }
+    for event in packets {
+        context.ecs.send_event(event);
+    }
+    result
+}
+
+struct Context<'a> {
+    entity: Entity,
+    world: &'a mut World,
+    session: &'a mut EditSession,
+    /// Block position of the player.
+    position: [i32; 3],
+    /// Events that are sent once the command is done.
+    chunk_updates: Vec<ChunkUpdateEvent>,
+    packets: Vec<SendPacketEvent>,
+}
+
+impl Context<'_> {
+    /// Applies `changes` and sends them to the players, returning how to undo them.
+    fn apply(&mut self, changes: &[BlockChange]) -> Vec<BlockChange> {
+        let undo = edit::apply(self.world, changes);
+        let mut chunks = HashMap::<_, Vec<_>>::new();
+        for change in &undo {
+            chunks
+                .entry((change.x >> 4, change.z >> 4))
+                .or_default()
+                .push(((change.x & 15) as u8, change.y as u8, (change.z & 15) as u8));
+        }
+        for ((chunk_x, chunk_z), positions) in chunks {
+            self.chunk_updates.push(ChunkUpdateEvent {
+                chunk_x,
+                chunk_z,
+                positions,
+            });
+        }
+        undo
+    }
+
+    /// Applies an edit that can be undone.
+    fn edit(&mut self, changes: Vec<BlockChange>) -> CommandResult {
+        let undo = self.apply(&changes);
+        let count = undo.len();
+        self.session.record(undo);
+        Ok(vec![format!("{count} blocks changed.")])
+    }
+
+    /// Pastes the clipboard at the position of the player.
+    ///
+    /// Undoing the paste restores the blocks, but not tile entities that were replaced.
+    fn paste(&mut self) -> CommandResult {
+        let clipboard = self
+            .session
+            .clipboard
+            .as_ref()
+            .ok_or("The clipboard is empty, //copy something first.")?;
+        check_volume("clipboard", clipboard.size().iter().product())?;
+        let changes = clipboard.paste(self.position);
+        let tile_entities = clipboard.paste_tile_entities(self.position);
+        let result = self.edit(changes);
+        for tile_entity in tile_entities {
+            self.world.set_tile_entity(tile_entity);
+        }
+        result
+    }
+}
+
+fn give_wand(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    arguments.end()?;
+    let packet = to_client_packets::AddToInventoryPacket {
+        item_type: WAND,
+        count: 1,
+        life: 0,
+    };
+    context
+        .packets
+        .push(SendPacketEvent::new(context.entity, packet).unwrap());
+    Ok(vec![
+        "Left click a block for the first corner, right click for the second.".to_string(),
+    ])
+}
+
+fn pos1(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    arguments.end()?;
+    context.session.first = Some(context.position);
+    Ok(vec![selection_message(
+        "First",
+        context.position,
+        context.session,
+    )])
+}
+
+fn pos2(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    arguments.end()?;
+    context.session.second = Some(context.position);
+    Ok(vec![selection_message(
+        "Second",
+        context.position,
+        context.session,
+    )])
+}
+
+fn size(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    arguments.end()?;
+    let selection = context.session.selection()?;
+    let [x, y, z] = selection.size();
+    Ok(vec![format!("{x}x{y}x{z}, {} blocks.", selection.volume())])
+}
+
+fn set(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    let (id, data) = arguments.block()?;
+    arguments.end()?;
+    let selection = limited_selection(context.session)?;
+    context.edit(edit::set(&selection, id, data.unwrap_or(0)))
+}
+
+fn walls(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    let (id, data) = arguments.block()?;
+    arguments.end()?;
+    let selection = limited_selection(context.session)?;
+    context.edit(edit::walls(&selection, id, data.unwrap_or(0)))
+}
+
+fn replace(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    let (from, (id, data)) = (arguments.block()?, arguments.block()?);
+    arguments.end()?;
+    let selection = limited_selection(context.session)?;
+    let changes = edit::replace(context.world, &selection, from, (id, data.unwrap_or(0)));
+    context.edit(changes)
+}
+
+fn copy(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    arguments.end()?;
+    let selection = limited_selection(context.session)?;
+    let clipboard = Clipboard::copy(context.world, &selection, context.position);
+    context.session.clipboard = Some(clipboard);
+    Ok(vec![format!("{} blocks copied.", selection.volume())])
+}
+
+fn paste(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    arguments.end()?;
+    context.paste()
+}
+
+fn rotate(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    let degrees = arguments.word()?;
+    arguments.end()?;
+    let clipboard = context
+        .session
+        .clipboard
+        .as_mut()
+        .ok_or("The clipboard is empty, //copy something first.")?;
+    match degrees {
+        "90" | "-270" => clipboard.rotate(1),
+        "180" | "-180" => clipboard.rotate(2),
+        "270" | "-90" => clipboard.rotate(3),
+        _ => return Err("The clipboard can only be rotated by 90, 180 or 270.".into()),
+    }
+    Ok(vec![format!("Clipboard rotated by {degrees} degrees.")])
+}
+
+fn undo(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    arguments.end()?;
+    let undo = context
+        .session
+        .undo
+        .pop_back()
+        .ok_or("Nothing left to undo.")?;
+    let redo = context.apply(&undo);
+    context.session.redo.push(redo);
+    Ok(vec![format!("Undid {} block changes.", undo.len())])
+}
+
+fn redo(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    arguments.end()?;
+    let redo = context.session.redo.pop().ok_or("Nothing left to redo.")?;
+    let undo = context.apply(&redo);
+    context.session.undo.push_back(undo);
+    Ok(vec![format!("Redid {} block changes.", redo.len())])
+}
+
+fn schematic_file(context: &mut Context, arguments: &mut Arguments) -> CommandResult {
+    let (action, name) = (arguments.word()?, arguments.word()?);
+    arguments.end()?;
+    let path = schematic_path(name)?;
+    match action {
+        "load" => {
+            let clipboard = load_schematic(name, path)?;
+            let [x, y, z] = clipboard.size();
+            context.session.clipboard = Some(clipboard);
+            Ok(vec![format!(
+                "Loaded {name} ({x}x{y}x{z}) into the clipboard."
+            )])
+        }
+        "save" => {
+            let clipboard = context
+                .session
+                .clipboard
+                .as_ref()
+                .ok_or("The clipboard is empty, //copy something first.")?;
+            std::fs::create_dir_all(SCHEMATIC_DIRECTORY)
+                .and_then(|_| schematic::save(clipboard, path))
+                .map_err(|err| format!("Could not save {name}: {err}"))?;
+            Ok(vec![format!("Saved the clipboard as {name}.")])
+        }
+        "paste" => {
+            let clipboard = load_schematic(name, path)?;
+            context.session.clipboard = Some(clipboard);
+            context.paste()
+        }
+        _ => Err(CommandError::Usage),
+    }
+}
+
+/// Loads a schematic, refusing ones too large to paste before they replace the clipboard.
+fn load_schematic(name: &str, path: std::path::PathBuf) -> Result<Clipboard, String> {
+    let clipboard = schematic::load(path).map_err(|err| format!("Could not load {name}: {err}"))?;
+    check_volume("schematic", clipboard.size().iter().product())?;
+    Ok(clipboard)
+}
+
+/// Resolves the name of a schematic, refusing names that could escape the schematic directory.
+fn schematic_path(name: &str) -> Result<std::path::PathBuf, String> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if name.is_empty() || !name.chars().all(valid) {
+        return Err(format!("Invalid schematic name: {name}"));
+    }
+    Ok(std::path::Path::new(SCHEMATIC_DIRECTORY).join(format!("{name}.schematic")))
+}
+
+fn limited_selection(session: &EditSession) -> Result<Cuboid, String> {
+    let selection = session.selection()?;
+    check_volume("selection", selection.volume())?;
+    Ok(selection)
+}
+
+/// Refuses to edit more than `MAX_VOLUME` blocks at once, `what` names where they come from.
+fn check_volume(what: &str, volume: usize) -> Result<(), String> {
+    if volume > MAX_VOLUME {
+        return Err(format!(
+            "The {what} has {volume} blocks, at most {MAX_VOLUME} can be edited at once."
+        ));
+    }
+    Ok(())
+}
+
+fn selection_message(corner: &str, position: [i32; 3], session: &EditSession) -> String {
+    let [x, y, z] = position;
+    match session.selection() {
+        Ok(selection) => format!(
+            "{corner} corner set to ({x}, {y}, {z}), {} blocks selected.",
+            selection.volume()
+        ),
+        Err(_) => format!("{corner} corner set to ({x}, {y}, {z})."),
+    }
+}
+
+fn reply(packet_event_emitter: &mut EventWriter<SendPacketEvent>, entity: Entity, message: String) {
+    packet_event_emitter.send(
+        SendPacketEvent::new(entity, to_client_packets::ChatMessagePacket { message }).unwrap(),
+    );
+}
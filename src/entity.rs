@@ -1,12 +1,13 @@
+use crate::interest::SentPosition;
 use crate::packet::to_client_packets::PlayerInventoryPacket;
 use crate::packet::PacketError;
 use crate::world::Chunk;
-use crate::{packet, BUFFER_SIZE};
-use bevy::prelude::{Bundle, Component};
+use crate::{packet, registry, BUFFER_SIZE};
+use bevy::prelude::{Bundle, Component, Entity, Resource};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::marker::PhantomData;
-use std::net::TcpStream;
+use std::net::{SocketAddr, TcpStream};
 use std::sync::{Arc, Mutex, RwLock};
 
 #[derive(Component, Default)]
@@ -18,29 +19,6 @@ pub struct Position {
     pub on_ground: bool,
 }
 
-#[derive(Component, Default)]
-pub struct PreviousPosition {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
-    pub stance: f64,
-    pub on_ground: bool,
-}
-
-impl PreviousPosition {
-    pub fn distance_moved(&self, pos: &Position) -> f64 {
-        let (x, y, z) = (pos.x - self.x, pos.y - self.y, pos.z - self.z);
-        (x * x + y * y + z * z).sqrt()
-    }
-
-    pub fn relative_movement(&self, pos: &Position) -> (i8, i8, i8) {
-        let x = ((pos.x - self.x) * 32.0).round() as i8;
-        let y = ((pos.y - self.y) * 32.0).round() as i8;
-        let z = ((pos.z - self.z) * 32.0).round() as i8;
-        (x, y, z)
-    }
-}
-
 #[derive(Component, Default)]
 pub struct Velocity {
     pub x: f64,
@@ -54,6 +32,70 @@ pub struct Look {
     pub pitch: f32,
 }
 
+/// The item a player holds in their hand.
+#[derive(Component, Default)]
+pub struct Holding {
+    pub item_id: u16,
+}
+
+/// Type of an entity in the world, decides how far away players see it and how it is spawned for them.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+// Nothing spawns mobs, pickups or objects yet.
+#[allow(dead_code)]
+pub enum EntityKind {
+    /// Spawned with the name of its `Named` component.
+    Player,
+    Mob {
+        mob_type: u8,
+    },
+    Pickup {
+        item_id: u16,
+        count: u8,
+    },
+    /// Objects and vehicles, like arrows, boats and minecarts.
+    Object {
+        object_type: u8,
+    },
+}
+
+/// Id of an entity in the protocol, unlike the index of a bevy `Entity` it is never reused.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NetworkId(pub u32);
+
+/// Allocates network ids and maps the ids clients send back to entities.
+#[derive(Resource)]
+pub struct NetworkIds {
+    next: u32,
+    entities: HashMap<u32, Entity>,
+}
+
+impl Default for NetworkIds {
+    fn default() -> Self {
+        Self {
+            next: 1,
+            entities: HashMap::new(),
+        }
+    }
+}
+
+impl NetworkIds {
+    pub fn allocate(&mut self, entity: Entity) -> NetworkId {
+        let id = self.next;
+        self.next += 1;
+        self.entities.insert(id, entity);
+        NetworkId(id)
+    }
+
+    /// The entity with the id, `None` for ids that were never allocated or whose entity is gone.
+    pub fn get(&self, id: u32) -> Option<Entity> {
+        self.entities.get(&id).copied()
+    }
+
+    pub fn release(&mut self, id: NetworkId) {
+        self.entities.remove(&id.0);
+    }
+}
+
 #[derive(Component, Default)]
 pub struct Named {
     pub name: String,
@@ -61,6 +103,16 @@ pub struct Named {
 
 pub mod connection_state {
     use bevy::prelude::Component;
+    use std::net::SocketAddr;
+    use std::time::Instant;
+
+    /// A connection from a trusted proxy that did not send its PROXY header completely yet.
+    #[derive(Component)]
+    pub struct ProxyHeader {
+        pub peer: SocketAddr,
+        pub listener: SocketAddr,
+        pub since: Instant,
+    }
 
     #[derive(Component)]
     pub struct Login;
@@ -76,6 +128,14 @@ pub mod connection_state {
     pub struct Invalid;
 }
 
+/// Address of the client, the one a trusted proxy reported for it or the one of the socket.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct RemoteAddress(pub SocketAddr);
+
+/// Address of the listener the connection came in on.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Listener(pub SocketAddr);
+
 #[derive(Component)]
 pub struct ClientStream {
     pub stream: Arc<RwLock<TcpStream>>,
@@ -103,9 +163,29 @@ pub struct PlayerChunkDB {
     pub chunks: HashMap<(i32, i32), Arc<RwLock<Chunk>>>,
 }
 
+/// Chunks that still have to be sent to a player, nearest first.
+#[derive(Component, Default)]
+pub struct ChunkQueue {
+    pub pending: VecDeque<(i32, i32)>,
+    /// Chunk the player was in when the queue was ordered.
+    pub center: Option<(i32, i32)>,
+    /// View distance the queue was ordered for.
+    pub radius: i32,
+}
+
+/// Radius of chunks around a player that are sent to them.
 #[derive(Component)]
-pub struct PlayerEntityDB {
-    pub visible_entities: Arc<RwLock<Vec<u32>>>,
+pub struct ViewDistance {
+    /// Radius the player should get, the server default or set by an admin.
+    pub requested: i32,
+    /// Radius in use, smaller than `requested` while the server is overloaded.
+    pub current: i32,
+}
+
+/// Entities a player sees, with their position as the player last got it.
+#[derive(Component, Default)]
+pub struct EntityTracker {
+    pub visible: HashMap<NetworkId, SentPosition>,
 }
 
 #[derive(Component)]
@@ -134,10 +214,10 @@ impl<const N: usize> InventoryArea<N> {
 
     pub fn create_with_data() -> Self {
         let mut s = Self::new();
-        let ids: &[u16] = &[1, 2, 3, 4, 5, 6, 7, 12, 13, 14, 15, 16, 17];
-        for (index, id) in ids.iter().enumerate() {
+        let blocks = registry::block::BLOCKS.iter().filter(|b| b.placeable);
+        for (index, block) in blocks.take(N).enumerate() {
             s.items[index] = Some(Item {
-                id: *id,
+                id: block.id as u16,
                 count: 64,
                 uses_left: 0,
             });
@@ -215,3 +295,19 @@ impl Inventory {
 
     */
 }
+
+#[test]
+fn test_network_ids() {
+    let mut ids = NetworkIds::default();
+    let first = ids.allocate(Entity::from_raw(7));
+    let second = ids.allocate(Entity::from_raw(8));
+    assert_ne!(first, second);
+    assert_eq!(ids.get(first.0), Some(Entity::from_raw(7)));
+
+    // Ids of entities that are gone do not resolve and are not handed out again.
+    ids.release(first);
+    assert_eq!(ids.get(first.0), None);
+    let third = ids.allocate(Entity::from_raw(7));
+    assert!(third != first && third != second);
+    assert_eq!(ids.get(12345), None);
+}
@@ -117,6 +117,22 @@ pub struct BlockChangeEvent {
     pub metadata: u8,
 }
 
+/// Blocks of a chunk that were already changed in the world, but not yet sent to the players.
+#[derive(Event)]
+pub struct ChunkUpdateEvent {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    /// Chunk local coordinates of the changed blocks.
+    pub positions: Vec<(u8, u8, u8)>,
+}
+
+/// A chat message starting with `/`, the slash is stripped from `command`.
+#[derive(Event)]
+pub struct CommandEvent {
+    pub entity: Entity,
+    pub command: String,
+}
+
 #[derive(Event, PartialEq, Eq)]
 pub struct SendPacketEvent {
     pub entity: Entity,
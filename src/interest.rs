@@ -0,0 +1,484 @@
+//! Tracking of entities, spawning, moving and destroying them for the players close enough to see them.
+use crate::entity::{
+    connection_state, EntityKind, EntityTracker, Look, Named, NetworkId, Position, ViewDistance,
+};
+use crate::event::SendPacketEvent;
+use crate::packet::{to_client_packets, PacketError};
+use bevy::prelude::{Changed, Entity, EventWriter, Query, Res, ResMut, Resource, With};
+use log::debug;
+use std::collections::HashMap;
+
+/// Distance in blocks up to which players are sent an entity, per type of entity.
+///
+/// Players never see entities outside of their view distance, whatever the range.
+#[derive(Resource)]
+pub struct TrackingRanges {
+    pub players: f64,
+    pub mobs: f64,
+    pub pickups: f64,
+    pub objects: f64,
+}
+
+impl Default for TrackingRanges {
+    fn default() -> Self {
+        Self {
+            players: 128.0,
+            mobs: 80.0,
+            pickups: 64.0,
+            objects: 80.0,
+        }
+    }
+}
+
+impl TrackingRanges {
+    pub fn range(&self, kind: EntityKind) -> f64 {
+        match kind {
+            EntityKind::Player => self.players,
+            EntityKind::Mob { .. } => self.mobs,
+            EntityKind::Pickup { .. } => self.pickups,
+            EntityKind::Object { .. } => self.objects,
+        }
+    }
+
+    pub fn max(&self) -> f64 {
+        self.players
+            .max(self.mobs)
+            .max(self.pickups)
+            .max(self.objects)
+    }
+}
+
+/// Entities bucketed by the chunk they are in.
+#[derive(Resource, Default)]
+pub struct EntityGrid {
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+    entities: HashMap<Entity, (i32, i32)>,
+}
+
+impl EntityGrid {
+    fn cell(x: f64, z: f64) -> (i32, i32) {
+        ((x.floor() as i32) >> 4, (z.floor() as i32) >> 4)
+    }
+
+    /// Inserts an entity or moves it to the bucket of its new position.
+    pub fn update(&mut self, entity: Entity, x: f64, z: f64) {
+        let cell = Self::cell(x, z);
+        match self.entities.insert(entity, cell) {
+            Some(previous) if previous == cell => return,
+            Some(previous) => self.remove_from_cell(entity, previous),
+            None => {}
+        }
+        self.cells.entry(cell).or_default().push(entity);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(cell) = self.entities.remove(&entity) {
+            self.remove_from_cell(entity, cell);
+        }
+    }
+
+    /// Removes every entity `keep` returns false for.
+    pub fn retain(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        let removed = self
+            .entities
+            .keys()
+            .copied()
+            .filter(|entity| !keep(*entity))
+            .collect::<Vec<_>>();
+        for entity in removed {
+            self.remove(entity);
+        }
+    }
+
+    fn remove_from_cell(&mut self, entity: Entity, cell: (i32, i32)) {
+        if let Some(entities) = self.cells.get_mut(&cell) {
+            entities.retain(|e| *e != entity);
+            if entities.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    /// Entities in the chunks that are at most `range` blocks away from the position along both axes.
+    ///
+    /// Entities near the edge of those chunks may be further away than `range`.
+    pub fn near(&self, x: f64, z: f64, range: f64) -> impl Iterator<Item = Entity> + '_ {
+        let (min_x, min_z) = Self::cell(x - range, z - range);
+        let (max_x, max_z) = Self::cell(x + range, z + range);
+        (min_x..=max_x)
+            .flat_map(move |x| (min_z..=max_z).map(move |z| (x, z)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+/// Whether an entity at `other` is within `range` blocks of `position` along both horizontal axes.
+pub fn in_range(position: &Position, other: &Position, range: f64) -> bool {
+    (position.x - other.x).abs() <= range && (position.z - other.z).abs() <= range
+}
+
+/// Ticks after which a viewer gets a teleport instead of a relative move, in case it got out of sync.
+const RESYNC_TICKS: u32 = 400;
+
+/// Position and look of an entity in the units of the protocol, 1/32 of a block and 1/256 of a turn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PackedPosition {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub yaw: i8,
+    pub pitch: i8,
+}
+
+impl PackedPosition {
+    pub fn new(position: &Position, look: &Look) -> Self {
+        let (yaw, pitch) = crate::util::pack_float_pair(look.yaw, look.pitch);
+        Self {
+            x: (position.x * 32.0).round() as i32,
+            y: (position.y * 32.0).round() as i32,
+            z: (position.z * 32.0).round() as i32,
+            yaw,
+            pitch,
+        }
+    }
+}
+
+/// Smallest update that brings a viewer to the current position of an entity.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Movement {
+    None,
+    Move(i8, i8, i8),
+    Look(i8, i8),
+    MoveLook(i8, i8, i8, i8, i8),
+    Teleport(PackedPosition),
+}
+
+/// Position of an entity as a viewer last got it.
+pub struct SentPosition {
+    position: PackedPosition,
+    ticks: u32,
+}
+
+impl SentPosition {
+    pub fn new(position: PackedPosition) -> Self {
+        Self { position, ticks: 0 }
+    }
+
+    /// Picks the update for the position of this tick and remembers it as sent.
+    ///
+    /// Relative moves are computed from what was sent before, so rounding errors do not add up.
+    pub fn update(&mut self, position: PackedPosition) -> Movement {
+        self.ticks += 1;
+        let delta = [
+            position.x - self.position.x,
+            position.y - self.position.y,
+            position.z - self.position.z,
+        ]
+        .map(i8::try_from);
+        let movement = match delta {
+            _ if self.ticks >= RESYNC_TICKS => Movement::Teleport(position),
+            [Ok(x), Ok(y), Ok(z)] => {
+                let moved = [x, y, z] != [0, 0, 0];
+                let looked =
+                    (position.yaw, position.pitch) != (self.position.yaw, self.position.pitch);
+                match (moved, looked) {
+                    (false, false) => Movement::None,
+                    (true, false) => Movement::Move(x, y, z),
+                    (false, true) => Movement::Look(position.yaw, position.pitch),
+                    (true, true) => Movement::MoveLook(x, y, z, position.yaw, position.pitch),
+                }
+            }
+            _ => Movement::Teleport(position),
+        };
+        if let Movement::Teleport(_) = movement {
+            self.ticks = 0;
+        }
+        self.position = position;
+        movement
+    }
+}
+
+fn spawn_packet(
+    viewer: Entity,
+    entity_id: u32,
+    kind: EntityKind,
+    position: PackedPosition,
+    name: Option<&Named>,
+) -> Result<SendPacketEvent, PacketError> {
+    let PackedPosition {
+        x,
+        y,
+        z,
+        yaw,
+        pitch,
+    } = position;
+    match kind {
+        EntityKind::Player => SendPacketEvent::new(
+            viewer,
+            to_client_packets::NamedEntitySpawnPacket {
+                entity_id,
+                name: name.map(|named| named.name.clone()).unwrap_or_default(),
+                x,
+                y,
+                z,
+                rotation: yaw,
+                pitch,
+                current_item: 0,
+            },
+        ),
+        EntityKind::Mob { mob_type } => SendPacketEvent::new(
+            viewer,
+            to_client_packets::MobSpawnPacket {
+                entity_id,
+                mob_type,
+                x,
+                y,
+                z,
+                yaw,
+                pitch,
+            },
+        ),
+        EntityKind::Pickup { item_id, count } => SendPacketEvent::new(
+            viewer,
+            to_client_packets::PickupSpawnPacket {
+                entity_id,
+                item_id,
+                count,
+                x,
+                y,
+                z,
+                rotation: yaw,
+                pitch,
+                roll: 0,
+            },
+        ),
+        EntityKind::Object { object_type } => SendPacketEvent::new(
+            viewer,
+            to_client_packets::AddObjectOrVehiclePacket {
+                entity_id,
+                object_type,
+                x,
+                y,
+                z,
+            },
+        ),
+    }
+}
+
+/// Keeps the grid in sync with the entities in the world and their positions.
+#[allow(clippy::type_complexity)]
+pub fn update_grid(
+    mut grid: ResMut<EntityGrid>,
+    moved: Query<(Entity, &Position), (With<EntityKind>, Changed<Position>)>,
+    tracked: Query<(), With<EntityKind>>,
+) {
+    grid.retain(|entity| tracked.contains(entity));
+    for (entity, position) in &moved {
+        grid.update(entity, position.x, position.z);
+    }
+}
+
+/// Spawns entities for the players that got into their tracking range and destroys them for the players
+/// that left it, or once they are gone from the world.
+#[allow(clippy::type_complexity)]
+pub fn track_entities(
+    mut packet_event_emitter: EventWriter<SendPacketEvent>,
+    grid: Res<EntityGrid>,
+    ranges: Res<TrackingRanges>,
+    mut viewers: Query<
+        (Entity, &Position, &ViewDistance, &mut EntityTracker),
+        With<connection_state::Playing>,
+    >,
+    tracked: Query<(
+        &NetworkId,
+        &Position,
+        Option<&Look>,
+        &EntityKind,
+        Option<&Named>,
+    )>,
+) {
+    let no_look = Look::default();
+    for (viewer, position, view_distance, mut tracker) in &mut viewers {
+        // Entities outside of the loaded chunks could not be shown by the client anyway.
+        let view_range = (view_distance.current * 16) as f64;
+        let visible = grid
+            .near(position.x, position.z, ranges.max().min(view_range))
+            .filter(|entity| *entity != viewer)
+            .filter_map(|entity| {
+                let (id, other, _, kind, _) = tracked.get(entity).ok()?;
+                in_range(position, other, ranges.range(*kind).min(view_range))
+                    .then_some((*id, entity))
+            })
+            .collect::<HashMap<_, _>>();
+
+        tracker.visible.retain(|id, _| {
+            if visible.contains_key(id) {
+                return true;
+            }
+            packet_event_emitter.send(
+                SendPacketEvent::new(
+                    viewer,
+                    to_client_packets::DestroyEntityPacket { entity_id: id.0 },
+                )
+                .unwrap(),
+            );
+            debug!("Sent delete entity: {} to entity: {}", id.0, viewer.index());
+            false
+        });
+
+        for (id, entity) in visible {
+            if tracker.visible.contains_key(&id) {
+                continue;
+            }
+            let (_, other, look, kind, name) = tracked.get(entity).unwrap();
+            let packed = PackedPosition::new(other, look.unwrap_or(&no_look));
+            packet_event_emitter.send(
+                SendPacketEvent::new(viewer, to_client_packets::EntityPacket { entity_id: id.0 })
+                    .unwrap(),
+            );
+            packet_event_emitter.send(spawn_packet(viewer, id.0, *kind, packed, name).unwrap());
+            tracker.visible.insert(id, SentPosition::new(packed));
+            debug!("Sent spawn entity: {} to entity: {}", id.0, viewer.index());
+        }
+    }
+}
+
+/// Sends every player that sees an entity the smallest packet that moves it to where it is now.
+pub fn move_entities(
+    mut packet_event_emitter: EventWriter<SendPacketEvent>,
+    grid: Res<EntityGrid>,
+    ranges: Res<TrackingRanges>,
+    mut viewers: Query<&mut EntityTracker, With<connection_state::Playing>>,
+    tracked: Query<(&NetworkId, &Position, Option<&Look>, &EntityKind)>,
+) {
+    let no_look = Look::default();
+    for (id, position, look, kind) in &tracked {
+        let packed = PackedPosition::new(position, look.unwrap_or(&no_look));
+        let entity_id = id.0;
+        // Viewers can only be in the cells within tracking range of the entity.
+        for entity in grid.near(position.x, position.z, ranges.range(*kind)) {
+            let Ok(mut tracker) = viewers.get_mut(entity) else {
+                continue;
+            };
+            let Some(sent) = tracker.visible.get_mut(id) else {
+                continue;
+            };
+            let packet = match sent.update(packed) {
+                Movement::None => continue,
+                Movement::Move(x, y, z) => SendPacketEvent::new(
+                    entity,
+                    to_client_packets::EntityRelativeMovePacket { entity_id, x, y, z },
+                ),
+                Movement::Look(yaw, pitch) => SendPacketEvent::new(
+                    entity,
+                    to_client_packets::EntityLookPacket {
+                        entity_id,
+                        yaw,
+                        pitch,
+                    },
+                ),
+                Movement::MoveLook(x, y, z, yaw, pitch) => SendPacketEvent::new(
+                    entity,
+                    to_client_packets::EntityLookRelativeMovePacket {
+                        entity_id,
+                        x,
+                        y,
+                        z,
+                        yaw,
+                        pitch,
+                    },
+                ),
+                Movement::Teleport(PackedPosition {
+                    x,
+                    y,
+                    z,
+                    yaw,
+                    pitch,
+                }) => SendPacketEvent::new(
+                    entity,
+                    to_client_packets::EntityTeleportPacket {
+                        entity_id,
+                        x,
+                        y,
+                        z,
+                        yaw,
+                        pitch,
+                    },
+                ),
+            };
+            packet_event_emitter.send(packet.unwrap());
+        }
+    }
+}
+
+#[test]
+fn test_entity_grid() {
+    let mut grid = EntityGrid::default();
+    let a = Entity::from_raw(1);
+    let b = Entity::from_raw(2);
+    grid.update(a, 0.5, 0.5);
+    grid.update(b, -40.0, 100.0);
+    let near = |grid: &EntityGrid, x, z, range| {
+        let mut near = grid.near(x, z, range).collect::<Vec<_>>();
+        near.sort();
+        near
+    };
+    assert_eq!(near(&grid, 10.0, 10.0, 16.0), vec![a]);
+    assert_eq!(near(&grid, 0.0, 50.0, 64.0), vec![a, b]);
+
+    // Moving across a chunk border changes the bucket, moving within it does not.
+    grid.update(a, -1.0, 0.5);
+    assert_eq!(near(&grid, 8.0, 8.0, 4.0), vec![]);
+    assert_eq!(near(&grid, -8.0, 8.0, 4.0), vec![a]);
+    grid.update(a, -15.0, 15.0);
+    assert_eq!(grid.cells.len(), 2);
+
+    grid.remove(a);
+    assert_eq!(near(&grid, 0.0, 50.0, 64.0), vec![b]);
+    assert_eq!(grid.cells.len(), 1);
+    grid.retain(|entity| entity != b);
+    assert_eq!(near(&grid, 0.0, 50.0, 64.0), vec![]);
+    assert!(grid.cells.is_empty());
+}
+
+#[test]
+fn test_sent_position() {
+    let position = |x, y, z, yaw| PackedPosition {
+        x,
+        y,
+        z,
+        yaw,
+        pitch: 0,
+    };
+    let mut sent = SentPosition::new(position(0, 2048, 0, 0));
+    assert_eq!(sent.update(position(0, 2048, 0, 0)), Movement::None);
+    assert_eq!(
+        sent.update(position(5, 2048, -3, 0)),
+        Movement::Move(5, 0, -3)
+    );
+    assert_eq!(
+        sent.update(position(5, 2048, -3, 64)),
+        Movement::Look(64, 0)
+    );
+    assert_eq!(
+        sent.update(position(6, 2047, -3, 0)),
+        Movement::MoveLook(1, -1, 0, 0, 0)
+    );
+    assert_eq!(
+        sent.update(position(200, 2047, -3, 0)),
+        Movement::Teleport(position(200, 2047, -3, 0))
+    );
+
+    // Standing still still gets a teleport every now and then.
+    let updates = (0..RESYNC_TICKS)
+        .map(|_| sent.update(position(200, 2047, -3, 0)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        updates.last(),
+        Some(&Movement::Teleport(position(200, 2047, -3, 0)))
+    );
+    assert!(updates[..updates.len() - 1]
+        .iter()
+        .all(|movement| *movement == Movement::None));
+}
@@ -0,0 +1,4 @@
+pub mod registry;
+pub mod render;
+pub mod util;
+pub mod world;
@@ -3,26 +3,72 @@ use crate::entity::ClientStream;
 use crate::packet::to_server_packets;
 use crate::world::World;
 use bevy::ecs::schedule::ExecutorKind;
-use bevy::prelude::{App, Resource, Schedule, Update};
-use log::{debug, info, Level};
-use std::net::TcpListener;
+use bevy::prelude::{App, IntoSystemConfigs, Resource, Schedule, Update};
+use clap::Parser;
+use log::{debug, info, warn, Level, LevelFilter};
+use std::net::{SocketAddr, TcpListener};
 use std::time::Instant;
 
+mod access;
 mod byte_man;
+mod command;
+mod config;
+mod edit;
 mod entity;
 mod event;
+mod interest;
 mod packet;
+mod permission;
+mod proxy;
+mod rate_limit;
+mod rcon;
 mod system;
-mod util;
-mod world;
+mod view_distance;
+mod web_map;
+
+use betalpha_mc::{registry, util, world};
 
 pub(crate) const BUFFER_SIZE: usize = 1024 * 8;
-pub(crate) const RENDER_DISTANCE_RADIUS: i32 = 4; // Diameter of chunks to send to player in `Initializing` state.
 
 fn main() -> std::io::Result<()> {
-    simple_logger::init_with_level(Level::Info).expect("Failed to initialize logging!");
-    let listener = TcpListener::bind("0.0.0.0:25565")?;
-    listener.set_nonblocking(true)?;
+    // The level is lowered to the configured one as soon as the settings are read.
+    simple_logger::init_with_level(Level::Trace).expect("Failed to initialize logging!");
+    log::set_max_level(LevelFilter::Info);
+    let config = config::Config::load(&config::Args::parse())?;
+    let settings = &config.settings;
+    if settings.online_mode {
+        warn!("online-mode is not supported, players are not authenticated.");
+    }
+    let mut listeners = Vec::new();
+    for bind in &settings.bind.0 {
+        let listener = TcpListener::bind(bind).map_err(|err| {
+            std::io::Error::new(err.kind(), format!("Could not listen on {bind}: {err}"))
+        })?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        info!("Listening on {addr}.");
+        listeners.push((addr, listener));
+    }
+    let mut world = World::open(&settings.world)?;
+    world.set_generator(settings.generator);
+    let web_map = web_map::WebMap::start(settings.web_map.0, &world)?;
+    let rcon = rcon::Rcon::start(settings)?;
+    let mut commands = command::CommandRegistry::default();
+    command::register(&mut commands);
+    edit::register(&mut commands);
+    view_distance::register(&mut commands);
+    permission::register(&mut commands);
+    access::register(&mut commands);
+    let permissions = permission::Permissions::load(permission::OPS_FILE, permission::GROUPS_FILE)?;
+    let mut access_lists = access::AccessLists::load()?;
+    let mut view_distance_settings = view_distance::ViewDistanceSettings::default();
+    let mut chunk_budget = system::ChunkBudget::default();
+    config::apply(
+        settings,
+        &mut view_distance_settings,
+        &mut chunk_budget,
+        &mut access_lists,
+    );
     App::new()
         .add_schedule(Schedule::new(schedule::CoreLabel()))
         .add_schedule(Schedule::new(schedule::ServerTickLabel()))
@@ -38,36 +84,41 @@ fn main() -> std::io::Result<()> {
         .add_event::<event::AnimationEvent>()
         .add_event::<event::PlayerUseEvent>()
         .add_event::<event::PlayerBlockPlacementEvent>()
+        .add_event::<event::ChunkUpdateEvent>()
+        .add_event::<event::CommandEvent>()
         .add_systems(
             schedule::CoreLabel(),
             (
                 core::accept_system,
+                core::proxy_header_system,
                 core::login_system,
                 core::initializing_system,
                 core::event_emitter_system,
+                rcon::run_commands,
             ),
         )
         // TODO: Chunks need to be loaded more async, because loading and unloading them causes lag.
-        .add_systems(
-            schedule::ImmediateLabel(),
-            (system::load_chunks, system::unload_chunks),
-        )
+        .add_systems(schedule::ImmediateLabel(), system::unload_chunks)
         .add_systems(
             schedule::ServerTickLabel(),
             (
                 system::keep_alive,
+                system::load_chunks,
                 system::chat_message,
                 system::system_message,
                 system::disconnecting,
                 system::digging,
                 system::placing,
-                system::block_change,
-                system::calculate_visible_players,
-                system::correct_player_position,
+                // Changes are applied and sent within the same tick.
+                (system::block_change, system::chunk_update).chain(),
+                (interest::update_grid, interest::track_entities).chain(),
                 system::player_movement,
-                system::move_player,
+                interest::move_entities,
                 system::animation,
                 system::player_use,
+                web_map::collect_changed_chunks,
+                edit::wand,
+                command::run_commands,
             ),
         )
         .add_systems(
@@ -75,6 +126,17 @@ fn main() -> std::io::Result<()> {
             (core::send_packets_system, core::remove_invalid_players),
         )
         //.add_systems(schedule::SecondTickLabel(), (system::increment_time,))
+        .add_systems(
+            schedule::SecondTickLabel(),
+            (
+                web_map::render_changed_chunks,
+                web_map::update_players,
+                view_distance::adjust_view_distance,
+                permission::reload_changed,
+                config::reload_changed,
+                system::autosave,
+            ),
+        )
         .edit_schedule(schedule::CoreLabel(), |s| {
             s.set_executor_kind(ExecutorKind::MultiThreaded);
         })
@@ -84,8 +146,21 @@ fn main() -> std::io::Result<()> {
         .edit_schedule(schedule::ImmediateLabel(), |s| {
             s.set_executor_kind(ExecutorKind::MultiThreaded);
         })
-        .insert_resource(World::open("./ExampleWorld")?)
-        .insert_resource(TcpWrapper { listener })
+        .insert_resource(world)
+        .insert_resource(commands)
+        .insert_resource(config)
+        .insert_resource(permissions)
+        .insert_resource(access_lists)
+        .insert_resource(rate_limit::ConnectionThrottle::default())
+        .insert_resource(chunk_budget)
+        .insert_resource(entity::NetworkIds::default())
+        .insert_resource(interest::EntityGrid::default())
+        .insert_resource(interest::TrackingRanges::default())
+        .insert_resource(view_distance_settings)
+        .insert_resource(view_distance::TickTime::default())
+        .insert_resource(web_map)
+        .insert_resource(rcon)
+        .insert_resource(TcpWrapper { listeners })
         .set_runner(|mut app: App| {
             let mut instant = Instant::now();
             let mut second_instant = Instant::now();
@@ -93,8 +168,11 @@ fn main() -> std::io::Result<()> {
                 app.world.run_schedule(schedule::CoreLabel());
                 app.world.run_schedule(schedule::ImmediateLabel());
                 if instant.elapsed().as_millis() >= 50 {
-                    app.world.run_schedule(schedule::ServerTickLabel());
                     instant = Instant::now();
+                    app.world.run_schedule(schedule::ServerTickLabel());
+                    app.world
+                        .resource_mut::<view_distance::TickTime>()
+                        .record(instant.elapsed());
                 }
                 if second_instant.elapsed().as_millis() >= 1000 {
                     app.world.run_schedule(schedule::SecondTickLabel());
@@ -109,63 +187,252 @@ fn main() -> std::io::Result<()> {
 
 #[derive(Resource)]
 struct TcpWrapper {
-    pub listener: TcpListener,
+    /// Every listener with the address it is bound to, players from all of them share the world.
+    pub listeners: Vec<(SocketAddr, TcpListener)>,
 }
 
 mod core {
+    use crate::access::{AccessLists, Admission};
     use crate::byte_man::{get_string, get_u8};
+    use crate::config::Config;
+    use crate::edit::EditSession;
     use crate::entity::{connection_state, Inventory, Position};
     use crate::entity::{
-        ClientStream, Look, Named, PlayerChunkDB, PlayerEntityDB, PreviousPosition, Velocity,
+        ChunkQueue, ClientStream, EntityKind, EntityTracker, Holding, Listener, Look, Named,
+        NetworkId, NetworkIds, PlayerChunkDB, RemoteAddress, Velocity,
     };
     use crate::event::Face;
     use crate::packet::{ids, to_client_packets, to_server_packets, PacketError};
     use crate::packet::{Deserialize, Serialize};
-    use crate::world::{Chunk, World};
-    use crate::{event, packet, util, TcpWrapper, BUFFER_SIZE};
+    use crate::permission::Permissions;
+    use crate::rate_limit::{
+        ChatVerdict, ConnectionThrottle, FloodProtection, PacketClass, PacketVerdict,
+    };
+    use crate::world::{Chunk, PlayerData, World};
+    use crate::{event, packet, proxy, system, util, view_distance, TcpWrapper, BUFFER_SIZE};
     use bevy::prelude::{
-        Commands, Entity, EventReader, EventWriter, Mut, Query, Res, ResMut, With,
+        Commands, Entity, EventReader, EventWriter, Local, Mut, Or, Query, Res, ResMut, With,
     };
     use bytes::{Buf, BufMut, BytesMut};
     use log::{debug, error, info, warn};
     use std::collections::HashMap;
     use std::io::{BufReader, Cursor, ErrorKind, Read, Write};
-    use std::net::TcpStream;
+    use std::net::{SocketAddr, TcpStream};
     use std::sync::{Arc, RwLock, RwLockWriteGuard};
+    use std::time::{Duration, Instant};
 
-    pub fn accept_system(wrapper: Res<TcpWrapper>, mut commands: Commands) {
-        if let Ok((mut stream, addr)) = wrapper.listener.accept() {
-            info!("Got new connection {}", stream.peer_addr().unwrap());
+    const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(2);
+
+    pub fn accept_system(
+        wrapper: Res<TcpWrapper>,
+        access_lists: Res<AccessLists>,
+        config: Res<Config>,
+        mut throttle: ResMut<ConnectionThrottle>,
+        mut commands: Commands,
+    ) {
+        for (listener_addr, listener) in &wrapper.listeners {
+            let Ok((mut stream, peer)) = listener.accept() else {
+                continue;
+            };
             stream.set_nonblocking(true).unwrap();
-            // Create the player entity
-            commands.spawn((ClientStream::new(stream), connection_state::Login));
+            // IPv4 clients of a dual-stack listener have mapped IPv6 addresses.
+            let peer = canonical(peer);
+            if config.settings.trusted_proxies.contains(peer.ip()) {
+                // The header is read by `proxy_header_system` as it arrives.
+                commands.spawn((
+                    ClientStream::new(stream),
+                    connection_state::ProxyHeader {
+                        peer,
+                        listener: *listener_addr,
+                        since: Instant::now(),
+                    },
+                ));
+                continue;
+            }
+            if check_connection(
+                &mut stream,
+                peer,
+                *listener_addr,
+                &access_lists,
+                &config,
+                &mut throttle,
+            ) {
+                // Create the player entity
+                commands.spawn((
+                    ClientStream::new(stream),
+                    RemoteAddress(peer),
+                    Listener(*listener_addr),
+                    connection_state::Login,
+                ));
+            }
         }
     }
 
+    /// Reads the PROXY headers of connections from trusted proxies without waiting for them.
+    pub fn proxy_header_system(
+        access_lists: Res<AccessLists>,
+        config: Res<Config>,
+        mut throttle: ResMut<ConnectionThrottle>,
+        mut buf: Local<Vec<u8>>,
+        query: Query<(Entity, &ClientStream, &connection_state::ProxyHeader)>,
+        mut commands: Commands,
+    ) {
+        buf.resize(proxy::MAX_LENGTH, 0);
+        for (entity, stream, pending) in &query {
+            let mut stream = stream.stream.write().unwrap();
+            let peer = pending.peer;
+            // The bytes stay in the socket until the whole header arrived, the packets after it are
+            // left for the login.
+            let header = match stream.peek(&mut buf) {
+                Ok(0) => Err("Connection closed".to_string()),
+                Ok(n) => match proxy::parse_header(&buf[..n]) {
+                    Ok(None) if n == buf.len() => Err("PROXY header is too long".to_string()),
+                    Ok(header) => Ok(header),
+                    Err(err) => Err(err.to_string()),
+                },
+                Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(None),
+                Err(err) => Err(err.to_string()),
+            };
+            let addr = match header {
+                Ok(Some((length, addr))) => {
+                    stream.read_exact(&mut buf[..length]).unwrap();
+                    match addr {
+                        Some(addr) => {
+                            debug!("{peer} is proxying a connection from {addr}.");
+                            canonical(addr)
+                        }
+                        None => peer,
+                    }
+                }
+                Ok(None) if pending.since.elapsed() < PROXY_HEADER_TIMEOUT => continue,
+                Ok(None) => {
+                    warn!("Refused connection from proxy {peer}: No PROXY header in time");
+                    commands
+                        .entity(entity)
+                        .remove::<connection_state::ProxyHeader>()
+                        .insert(connection_state::Invalid);
+                    continue;
+                }
+                Err(err) => {
+                    warn!("Refused connection from proxy {peer}: {err}");
+                    commands
+                        .entity(entity)
+                        .remove::<connection_state::ProxyHeader>()
+                        .insert(connection_state::Invalid);
+                    continue;
+                }
+            };
+            let mut entity = commands.entity(entity);
+            entity.remove::<connection_state::ProxyHeader>();
+            match check_connection(
+                &mut stream,
+                addr,
+                pending.listener,
+                &access_lists,
+                &config,
+                &mut throttle,
+            ) {
+                true => entity.insert((
+                    RemoteAddress(addr),
+                    Listener(pending.listener),
+                    connection_state::Login,
+                )),
+                false => entity.insert(connection_state::Invalid),
+            };
+        }
+    }
+
+    fn canonical(addr: SocketAddr) -> SocketAddr {
+        SocketAddr::new(addr.ip().to_canonical(), addr.port())
+    }
+
+    /// Applies the throttle and the address bans to a new connection, refused ones get the reason.
+    fn check_connection(
+        stream: &mut TcpStream,
+        addr: SocketAddr,
+        listener: SocketAddr,
+        access_lists: &AccessLists,
+        config: &Config,
+        throttle: &mut ConnectionThrottle,
+    ) -> bool {
+        let per_minute = config.settings.connection_throttle;
+        let refusal = if !throttle.allow(addr.ip(), per_minute, Instant::now()) {
+            warn!(
+                "Throttled connection {addr}, it connects more than {per_minute} times a minute."
+            );
+            "Connection throttled! Please wait before reconnecting.".to_string()
+        } else {
+            info!("Got new connection {addr} on {listener}");
+            match access_lists.check_address(addr.ip()) {
+                Ok(()) => return true,
+                Err(reason) => {
+                    info!("Refused connection {addr}: {reason}");
+                    reason
+                }
+            }
+        };
+        let packet = to_client_packets::KickPacket { reason: refusal };
+        // The connection is dropped right away, whether the client reads the reason or not.
+        let _ = stream.write_all(&packet.serialize().unwrap());
+        false
+    }
+
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
     pub fn login_system(
         world: Res<World>,
-        mut query: Query<(Entity, &ClientStream), With<connection_state::Login>>,
+        access_lists: Res<AccessLists>,
+        permissions: Res<Permissions>,
+        config: Res<Config>,
+        mut network_ids: ResMut<NetworkIds>,
+        mut query: Query<
+            (Entity, &ClientStream, &RemoteAddress, &Listener),
+            With<connection_state::Login>,
+        >,
+        online: Query<
+            (Entity, &Named, Option<(&Position, &Look)>),
+            Or<(
+                With<connection_state::Initializing>,
+                With<connection_state::Playing>,
+            )>,
+        >,
         mut commands: Commands,
     ) {
         #[derive(PartialEq)]
         enum InternalState {
             LoggingIn,
             LoggedIn,
+            Refused,
         }
-        for (entity, stream) in &mut query {
+        let mut admission = Admission {
+            access_lists: &access_lists,
+            permissions: &permissions,
+            settings: &config.settings,
+            online: online
+                .iter()
+                .map(|(entity, named, _)| (entity, named.name.clone()))
+                .collect(),
+        };
+        'players: for (entity, stream, address, listener) in &mut query {
             {
                 let mut stream: RwLockWriteGuard<'_, TcpStream> = stream.stream.write().unwrap();
                 let mut buf = [0u8; BUFFER_SIZE];
                 let (mut buf_start, mut buf_end) = (0usize, 0usize);
                 let mut state = InternalState::LoggingIn;
+                let mut replaced = Vec::new();
                 loop {
+                    #[allow(clippy::too_many_arguments)]
                     fn handle_packets<'w, 's>(
                         stream: &mut TcpStream,
                         buf: &[u8],
                         entity: Entity,
+                        address: SocketAddr,
+                        listener: SocketAddr,
                         world: &World,
+                        admission: &mut Admission,
+                        network_ids: &mut NetworkIds,
                         commands: &mut Commands<'w, 's>,
                         state: &mut InternalState,
+                        replaced: &mut Vec<Entity>,
                     ) -> Result<usize, PacketError> {
                         let mut cursor = Cursor::new(buf);
                         while let Ok(packet_id) = get_u8(&mut cursor) {
@@ -193,19 +460,54 @@ mod core {
                                     };
                                     stream.write_all(&packet.serialize().unwrap()).unwrap();
                                     stream.flush().unwrap();
-                                    debug!("Handshake accepted from address {:?} using username {name:?}", stream.peer_addr().unwrap())
+                                    debug!("Handshake accepted from address {address} using username {name:?}")
                                 }
                                 ids::LOGIN => {
                                     let request =
                                         to_server_packets::LoginRequestPacket::nested_deserialize(
                                             &mut cursor,
                                         )?;
-                                    commands.entity(entity).insert(Named {
-                                        name: request.username.clone(),
-                                    });
-                                    debug!("Received login request from address {:?} containing {request:?}", stream.peer_addr().unwrap());
+                                    match admission.admit(entity, &request.username) {
+                                        Ok(None) => {}
+                                        Ok(Some(old)) => {
+                                            info!(
+                                                "{} logged in again, kicking the old session.",
+                                                request.username
+                                            );
+                                            replaced.push(old);
+                                            commands
+                                                .entity(old)
+                                                .remove::<(
+                                                    connection_state::Initializing,
+                                                    connection_state::Playing,
+                                                )>()
+                                                .insert(connection_state::Disconnecting {
+                                                    reason: "You logged in from another location!"
+                                                        .to_string(),
+                                                });
+                                        }
+                                        Err(reason) => {
+                                            info!(
+                                                "Refused login of {} ({address}): {reason}",
+                                                request.username
+                                            );
+                                            let packet = to_client_packets::KickPacket { reason };
+                                            stream.write_all(&packet.serialize()?).unwrap();
+                                            stream.flush().unwrap();
+                                            *state = InternalState::Refused;
+                                            break;
+                                        }
+                                    }
+                                    let network_id = network_ids.allocate(entity);
+                                    commands.entity(entity).insert((
+                                        Named {
+                                            name: request.username.clone(),
+                                        },
+                                        network_id,
+                                    ));
+                                    debug!("Received login request from address {address} containing {request:?}");
                                     let response = to_client_packets::LoginResponsePacket {
-                                        entity_id: entity.index(),
+                                        entity_id: network_id.0,
                                         _unused1: "".to_string(),
                                         _unused2: "".to_string(),
                                         map_seed: world.get_seed(),
@@ -213,7 +515,10 @@ mod core {
                                     };
                                     stream.write_all(&response.serialize()?).unwrap();
                                     stream.flush().unwrap();
-                                    info!("Player \"{}\" joined the server!", request.username);
+                                    info!(
+                                        "Player \"{}\" joined the server on {listener}!",
+                                        request.username
+                                    );
                                     *state = InternalState::LoggedIn;
                                 }
                                 _ => {
@@ -229,12 +534,23 @@ mod core {
                         &mut stream,
                         &buf[buf_start..buf_end],
                         entity,
+                        address.0,
+                        listener.0,
                         &world,
+                        &mut admission,
+                        &mut network_ids,
                         &mut commands,
                         &mut state,
+                        &mut replaced,
                     ) {
                         buf_start += n;
                     }
+                    // Saved right away, the new session loads the player before the old one is disconnected.
+                    for old in replaced.drain(..) {
+                        if let Ok((_, name, Some((position, look)))) = online.get(old) {
+                            system::save_player(&world, name, position, look);
+                        }
+                    }
 
                     match stream.read(&mut buf[buf_end..]) {
                         Ok(0) => {
@@ -250,6 +566,13 @@ mod core {
                     if state == InternalState::LoggedIn {
                         break;
                     }
+                    if state == InternalState::Refused {
+                        commands
+                            .entity(entity)
+                            .remove::<connection_state::Login>()
+                            .insert(connection_state::Invalid);
+                        continue 'players;
+                    }
                 }
             }
             // Transition state from `Login` to `Initializing`
@@ -263,67 +586,91 @@ mod core {
     // TODO: Parse spawn position as absolute integer.
     pub fn initializing_system(
         mut world: ResMut<World>,
+        view_distance_settings: Res<view_distance::ViewDistanceSettings>,
+        config: Res<Config>,
         mut query: Query<(Entity, &ClientStream, &Named), With<connection_state::Initializing>>,
         mut commands: Commands,
     ) {
         for (entity, stream, name_component) in &mut query {
             {
                 let mut stream: RwLockWriteGuard<'_, TcpStream> = stream.stream.write().unwrap();
+                let spawn = world.get_spawn();
+                let player = world
+                    .load_player(&name_component.name)
+                    .unwrap_or(PlayerData {
+                        position: [spawn[0] as f64, spawn[1] as f64, spawn[2] as f64],
+                        rotation: [0.0, 0.0],
+                        on_ground: false,
+                    });
+                let [x, y, z] = player.position;
+                let [yaw, pitch] = player.rotation;
                 // Send chunk data
-                let (player_chunk_x, player_chunk_z) = (
-                    (world.get_spawn()[0] - world.get_spawn()[0] % 16) / 16,
-                    (world.get_spawn()[2] - world.get_spawn()[2] % 16) / 16,
-                );
+                let (player_chunk_x, player_chunk_z) =
+                    ((x.floor() as i32) >> 4, (z.floor() as i32) >> 4);
                 debug!(
                     "Player {} spawned in chunk: [{player_chunk_x}, {player_chunk_z}].",
                     name_component.name
                 );
-                let mut local_db = HashMap::with_capacity(8 * 8);
-                let chunk_r = crate::RENDER_DISTANCE_RADIUS / 2;
-                for x in (player_chunk_x - chunk_r)..=(player_chunk_x + chunk_r) {
-                    for z in (player_chunk_z - chunk_r)..=(player_chunk_z + chunk_r) {
-                        match world.get_chunk(x, z) {
-                            Ok(chunk) => {
-                                debug!("Loaded chunk at (x: {x}, z: {z}).");
-                                stream
-                                    .write_all(
-                                        &to_client_packets::PreChunkPacket { x, z, mode: true }
-                                            .serialize()
-                                            .unwrap(),
-                                    )
-                                    .unwrap();
-
-                                let (len, chunk_data) = chunk.read().unwrap().get_compressed_data();
-
-                                stream
-                                    .write_all(
-                                        &to_client_packets::MapChunkPacket {
-                                            x: x * 16,
-                                            y: 0,
-                                            z: z * 16,
-                                            size_x: 15,
-                                            size_y: 127,
-                                            size_z: 15,
-                                            compressed_size: len,
-                                            compressed_data: chunk_data[..len as usize].to_vec(),
-                                        }
+                let mut local_db = HashMap::new();
+                let view_distance = view_distance_settings.view_distance();
+                // Only the chunk the player spawns in is sent right away, `load_chunks` queues the
+                // rest nearest first within the chunk budget.
+                {
+                    let (x, z) = (player_chunk_x, player_chunk_z);
+                    match world.get_chunk(x, z) {
+                        Ok(chunk) => {
+                            debug!("Loaded chunk at (x: {x}, z: {z}).");
+                            stream
+                                .write_all(
+                                    &to_client_packets::PreChunkPacket { x, z, mode: true }
                                         .serialize()
                                         .unwrap(),
-                                    )
-                                    .unwrap();
-                                local_db.insert((x, z), chunk);
-                            }
-                            Err(err) => {
-                                warn!("Failed to load chunk at (x: {x}, z: {z}): {err}!")
+                                )
+                                .unwrap();
+
+                            let (len, chunk_data) = chunk.read().unwrap().get_compressed_data();
+
+                            stream
+                                .write_all(
+                                    &to_client_packets::MapChunkPacket {
+                                        x: x * 16,
+                                        y: 0,
+                                        z: z * 16,
+                                        size_x: 15,
+                                        size_y: 127,
+                                        size_z: 15,
+                                        compressed_size: len,
+                                        compressed_data: chunk_data[..len as usize].to_vec(),
+                                    }
+                                    .serialize()
+                                    .unwrap(),
+                                )
+                                .unwrap();
+                            let tile_entities = {
+                                let chunk = chunk.read().unwrap();
+                                chunk
+                                    .tile_entities()
+                                    .iter()
+                                    .filter_map(system::tile_entity_packet)
+                                    .collect::<Vec<_>>()
+                            };
+                            for packet in tile_entities {
+                                stream.write_all(&packet.serialize().unwrap()).unwrap();
                             }
+                            local_db.insert((x, z), chunk);
+                        }
+                        Err(err) => {
+                            warn!("Failed to load chunk at (x: {x}, z: {z}): {err}!")
                         }
                     }
                 }
                 stream.flush().unwrap();
                 info!("Sent chunk data to {}.", name_component.name);
-                commands
-                    .entity(entity)
-                    .insert(PlayerChunkDB { chunks: local_db });
+                commands.entity(entity).insert((
+                    PlayerChunkDB { chunks: local_db },
+                    ChunkQueue::default(),
+                    view_distance,
+                ));
                 // Send spawn information
                 let spawn_packet = to_client_packets::SpawnPositionPacket {
                     x: world.get_spawn()[0],
@@ -346,15 +693,14 @@ mod core {
                 }
                 // TODO: Add spawn component to player.
                 // Send position and look information
-                // TODO: Load position and look information from player file.
                 let position_and_look_packet = to_client_packets::ServerPositionLookPacket {
-                    x: world.get_spawn()[0] as f64,
-                    stance: world.get_spawn()[1] as f64 + 1.75,
-                    y: world.get_spawn()[1] as f64,
-                    z: world.get_spawn()[2] as f64,
-                    yaw: 0.0,
-                    pitch: 0.0,
-                    on_ground: false,
+                    x,
+                    stance: y + 1.75,
+                    y,
+                    z,
+                    yaw,
+                    pitch,
+                    on_ground: player.on_ground,
                 };
                 stream
                     .write_all(&position_and_look_packet.serialize().unwrap())
@@ -366,22 +712,21 @@ mod core {
                 stream
                     .write_all(&inv.to_raw_packet(-1).unwrap().serialize().unwrap())
                     .unwrap();
+                if !config.settings.motd.is_empty() {
+                    let motd = to_client_packets::ChatMessagePacket {
+                        message: config.settings.motd.clone(),
+                    };
+                    stream.write_all(&motd.serialize().unwrap()).unwrap();
+                }
                 stream.flush().unwrap();
 
                 commands.entity(entity).insert((
                     Position {
-                        x: world.get_spawn()[0] as f64,
-                        y: world.get_spawn()[1] as f64,
-                        z: world.get_spawn()[2] as f64,
-                        stance: world.get_spawn()[1] as f64 + 1.65,
-                        on_ground: false,
-                    },
-                    PreviousPosition {
-                        x: world.get_spawn()[0] as f64,
-                        y: world.get_spawn()[1] as f64,
-                        z: world.get_spawn()[2] as f64,
-                        stance: world.get_spawn()[1] as f64 + 1.65,
-                        on_ground: false,
+                        x,
+                        y,
+                        z,
+                        stance: y + 1.65,
+                        on_ground: player.on_ground,
                     },
                     // Velocity {
                     //     x: 0.0,
@@ -389,13 +734,12 @@ mod core {
                     //     z: 0.0,
                     // },
                     inv,
-                    Look {
-                        yaw: 0.0,
-                        pitch: 0.0,
-                    },
-                    PlayerEntityDB {
-                        visible_entities: Arc::new(RwLock::new(Vec::new())),
-                    },
+                    Look { yaw, pitch },
+                    EntityTracker::default(),
+                    Holding::default(),
+                    EditSession::default(),
+                    EntityKind::Player,
+                    FloodProtection::new(Instant::now()),
                 ));
             }
             // Transition state from `Initializing` to `Playing`
@@ -428,10 +772,14 @@ mod core {
     }
 
     pub fn remove_invalid_players(
-        mut query: Query<Entity, With<connection_state::Invalid>>,
+        mut network_ids: ResMut<NetworkIds>,
+        mut query: Query<(Entity, Option<&NetworkId>), With<connection_state::Invalid>>,
         mut commands: Commands,
     ) {
-        for entity in &mut query {
+        for (entity, network_id) in &mut query {
+            if let Some(network_id) = network_id {
+                network_ids.release(*network_id);
+            }
             commands.entity(entity).despawn();
         }
     }
@@ -441,15 +789,27 @@ mod core {
     pub fn event_emitter_system(
         mut system_message_event_emitter: EventWriter<event::SystemMessageEvent>,
         mut chat_message_event_emitter: EventWriter<event::ChatMessageEvent>,
+        mut command_event_emitter: EventWriter<event::CommandEvent>,
         mut position_and_look_event_emitter: EventWriter<event::PlayerPositionAndLookEvent>,
         mut player_digging_event_emitter: EventWriter<event::PlayerDiggingEvent>,
         mut player_block_placement_event_emitter: EventWriter<event::PlayerBlockPlacementEvent>,
         mut animation_event_emitter: EventWriter<event::AnimationEvent>,
         mut player_use_event_emitter: EventWriter<event::PlayerUseEvent>,
-        mut query: Query<(Entity, &ClientStream, &Named), (With<connection_state::Playing>)>,
+        network_ids: Res<NetworkIds>,
+        mut query: Query<
+            (
+                Entity,
+                &ClientStream,
+                &Named,
+                &RemoteAddress,
+                &mut FloodProtection,
+            ),
+            With<connection_state::Playing>,
+        >,
         mut commands: Commands,
     ) {
-        for (entity, stream_component, name_component) in &mut query {
+        let now = Instant::now();
+        for (entity, stream_component, name_component, address, mut flood) in &mut query {
             let mut stream: RwLockWriteGuard<'_, TcpStream> =
                 stream_component.stream.write().unwrap();
             // This buffer has to be persistent between read cycles, because we cannot read the exact number of bytes we need.
@@ -499,10 +859,47 @@ mod core {
                 },
             }
 
+            // Bytes of the packets that were handled, a packet that did not arrive completely is kept from here.
+            let mut handled = 0;
             let res: Result<usize, PacketError> = (|| -> Result<usize, PacketError> {
                 let mut cursor = Cursor::new(&buf[buf_start..buf_end]);
                 // Handle all packets...
-                while let Ok(packet_id) = get_u8(&mut cursor) {
+                loop {
+                    handled = cursor.position() as usize;
+                    let Ok(packet_id) = get_u8(&mut cursor) else {
+                        break;
+                    };
+                    // Packets are only charged once they arrived completely, the rest waits for more bytes.
+                    skip_packet(packet_id, &mut cursor.clone())?;
+                    let class = PacketClass::of(packet_id);
+                    match flood.packet(class, now) {
+                        PacketVerdict::Allow => {}
+                        PacketVerdict::Drop { first } => {
+                            if first {
+                                warn!(
+                                    "{} ({}) exceeds the {class:?} packet budget, dropping packets starting with {packet_id:#04x}.",
+                                    name_component.name,
+                                    address.0
+                                );
+                            }
+                            skip_packet(packet_id, &mut cursor)?;
+                            continue;
+                        }
+                        PacketVerdict::Kick => {
+                            warn!(
+                                "{} ({}) kept exceeding the {class:?} packet budget, last packet {packet_id:#04x}, kicking.",
+                                name_component.name,
+                                address.0
+                            );
+                            commands
+                                .entity(entity)
+                                .remove::<connection_state::Playing>()
+                                .insert(connection_state::Disconnecting {
+                                    reason: "Kicked for flooding the server.".to_string(),
+                                });
+                            break;
+                        }
+                    }
                     match packet_id {
                         ids::KEEP_ALIVE => {
                             to_server_packets::HandshakePacket::nested_deserialize(&mut cursor)?;
@@ -523,10 +920,56 @@ mod core {
                             let packet = to_server_packets::ChatMessagePacket::nested_deserialize(
                                 &mut cursor,
                             )?;
-                            chat_message_event_emitter.send(event::ChatMessageEvent {
-                                from: name_component.name.clone(),
-                                message: packet.message,
-                            });
+                            // Commands share the mute with chat but have a budget of their own.
+                            let verdict = if packet.message.starts_with('/') {
+                                flood.command(now)
+                            } else {
+                                flood.chat(&packet.message, now)
+                            };
+                            let reply = match verdict {
+                                ChatVerdict::Allow => {
+                                    if let Some(command) = packet.message.strip_prefix('/') {
+                                        command_event_emitter.send(event::CommandEvent {
+                                            entity,
+                                            command: command.to_string(),
+                                        });
+                                    } else {
+                                        chat_message_event_emitter.send(event::ChatMessageEvent {
+                                            from: name_component.name.clone(),
+                                            message: packet.message,
+                                        });
+                                    }
+                                    continue;
+                                }
+                                ChatVerdict::Muted(left) => {
+                                    format!("§cYou are muted for another {}s.", left.as_secs() + 1)
+                                }
+                                ChatVerdict::Mute(duration) => {
+                                    warn!(
+                                        "{} ({}) is muted for {duration:?} for spamming: {:?}",
+                                        name_component.name, address.0, packet.message
+                                    );
+                                    format!(
+                                        "§cYou are muted for {}s for spamming.",
+                                        duration.as_secs()
+                                    )
+                                }
+                                ChatVerdict::Kick => {
+                                    warn!(
+                                        "{} ({}) kept spamming, kicking: {:?}",
+                                        name_component.name, address.0, packet.message
+                                    );
+                                    commands
+                                        .entity(entity)
+                                        .remove::<connection_state::Playing>()
+                                        .insert(connection_state::Disconnecting {
+                                            reason: "Kicked for spamming.".to_string(),
+                                        });
+                                    break;
+                                }
+                            };
+                            let reply = to_client_packets::ChatMessagePacket { message: reply };
+                            stream.write_all(&reply.serialize()?).unwrap();
                         }
                         ids::PLAYER_POSITION_AND_LOOK => {
                             let to_server_packets::PlayerPositionLookPacket {
@@ -647,11 +1090,19 @@ mod core {
                             let packet = to_server_packets::UseEntityPacket::nested_deserialize(
                                 &mut cursor,
                             )?;
-                            player_use_event_emitter.send(event::PlayerUseEvent {
-                                entity,
-                                target: Entity::from_raw(packet.target_id),
-                                left_click: packet.is_left_click,
-                            })
+                            match network_ids.get(packet.target_id) {
+                                Some(target) => {
+                                    player_use_event_emitter.send(event::PlayerUseEvent {
+                                        entity,
+                                        target,
+                                        left_click: packet.is_left_click,
+                                    })
+                                }
+                                None => warn!(
+                                    "{} used entity {} which does not exist.",
+                                    name_component.name, packet.target_id
+                                ),
+                            }
                         }
                         ids::PLAYER_INVENTORY => {
                             let packet =
@@ -664,6 +1115,9 @@ mod core {
                                 to_server_packets::HoldingChangePacket::nested_deserialize(
                                     &mut cursor,
                                 )?;
+                            commands.entity(entity).insert(Holding {
+                                item_id: packet.item_id,
+                            });
                         }
                         ids::KICK_OR_DISCONNECT => {
                             let packet = to_server_packets::DisconnectPacket::nested_deserialize(
@@ -711,11 +1165,65 @@ mod core {
                         });
                 }
                 Err(..) => {
+                    buf_start += handled;
                     left_over.append(&mut buf[buf_start..buf_end].to_vec());
                 }
             }
         }
     }
+
+    /// Reads over a packet the client sent without handling it.
+    fn skip_packet(packet_id: u8, cursor: &mut Cursor<&[u8]>) -> Result<(), PacketError> {
+        match packet_id {
+            ids::KEEP_ALIVE => {
+                to_server_packets::HandshakePacket::nested_deserialize(cursor)?;
+            }
+            ids::HANDSHAKE => {
+                to_server_packets::HandshakePacket::nested_deserialize(cursor)?;
+            }
+            ids::LOGIN => {
+                to_server_packets::LoginRequestPacket::nested_deserialize(cursor)?;
+            }
+            ids::CHAT_MESSAGE => {
+                to_server_packets::ChatMessagePacket::nested_deserialize(cursor)?;
+            }
+            ids::PLAYER_POSITION_AND_LOOK => {
+                to_server_packets::PlayerPositionLookPacket::nested_deserialize(cursor)?;
+            }
+            ids::PLAYER => {
+                to_server_packets::PlayerPacket::nested_deserialize(cursor)?;
+            }
+            ids::PLAYER_POSITION => {
+                to_server_packets::PlayerPositionPacket::nested_deserialize(cursor)?;
+            }
+            ids::PLAYER_LOOK => {
+                to_server_packets::PlayerLookPacket::nested_deserialize(cursor)?;
+            }
+            ids::ANIMATION => {
+                to_server_packets::AnimationPacket::nested_deserialize(cursor)?;
+            }
+            ids::PLAYER_DIGGING => {
+                to_server_packets::PlayerDiggingPacket::nested_deserialize(cursor)?;
+            }
+            ids::PLAYER_BLOCK_PLACEMENT => {
+                to_server_packets::PlayerBlockPlacementPacket::nested_deserialize(cursor)?;
+            }
+            ids::USE_ENTITY => {
+                to_server_packets::UseEntityPacket::nested_deserialize(cursor)?;
+            }
+            ids::PLAYER_INVENTORY => {
+                to_client_packets::PlayerInventoryPacket::nested_deserialize(cursor)?;
+            }
+            ids::HOLDING_CHANGE => {
+                to_server_packets::HoldingChangePacket::nested_deserialize(cursor)?;
+            }
+            ids::KICK_OR_DISCONNECT => {
+                to_server_packets::DisconnectPacket::nested_deserialize(cursor)?;
+            }
+            _ => return Err(PacketError::InvalidPacketID(packet_id)),
+        }
+        Ok(())
+    }
 }
 
 mod schedule {
@@ -0,0 +1,393 @@
+//! Permission nodes, dot separated names like `betalpha.command.tp` that allow players to do something.
+//!
+//! Operators are listed one name per line in `ops.txt` like the vanilla server does it, groups and
+//! per player overrides live in `permissions.json`:
+//!
+//! ```json
+//! {
+//!   "groups": {
+//!     "default": { "permissions": ["betalpha.build"] },
+//!     "builder": { "inherits": ["default"], "permissions": ["betalpha.edit.*"] },
+//!     "op": { "permissions": ["*"] }
+//!   },
+//!   "players": {
+//!     "Notch": { "groups": ["builder"], "permissions": ["-betalpha.edit.schematic"] }
+//!   }
+//! }
+//! ```
+//!
+//! A node prefixed with `-` denies instead of grants. The overrides of a player decide first, then
+//! the groups of the player in order, then the `op` group for operators and last the `default` group.
+//! Both files are read again when they change or with `/permissions reload`.
+use crate::command::{
+    Arguments, Command, CommandContext, CommandError, CommandRegistry, CommandResult,
+};
+use bevy::prelude::{ResMut, Resource};
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+pub const OPS_FILE: &str = "./ops.txt";
+pub const GROUPS_FILE: &str = "./permissions.json";
+/// Node that allows digging and placing blocks.
+pub const BUILD: &str = "betalpha.build";
+/// Node that allows building within the spawn protection.
+pub const BUILD_SPAWN: &str = "betalpha.build.spawn";
+/// Node that allows joining a full server.
+pub const JOIN_FULL: &str = "betalpha.join.full";
+/// Group every player is in.
+const DEFAULT_GROUP: &str = "default";
+/// Group operators are in.
+const OP_GROUP: &str = "op";
+
+/// Whether the granted node `granted` covers `node`.
+///
+/// `*` covers every node and a node ending in `.*` covers every node below it.
+pub fn covers(granted: &str, node: &str) -> bool {
+    match granted.strip_suffix('*') {
+        Some("") => true,
+        Some(prefix) if prefix.ends_with('.') => node.starts_with(prefix),
+        _ => granted == node,
+    }
+}
+
+/// Whether a list of nodes grants or denies `node`, `None` if no entry covers it. Denying wins.
+fn decide(nodes: &[String], node: &str) -> Option<bool> {
+    let mut decision = None;
+    for entry in nodes {
+        match entry.strip_prefix('-') {
+            Some(denied) if covers(denied, node) => return Some(false),
+            None if covers(entry, node) => decision = Some(true),
+            _ => {}
+        }
+    }
+    decision
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    inherits: Vec<String>,
+    permissions: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct PlayerPermissions {
+    groups: Vec<String>,
+    permissions: Vec<String>,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Permissions {
+    ops_path: PathBuf,
+    groups_path: PathBuf,
+    /// Modification times of both files when they were last read or written.
+    modified: [Option<SystemTime>; 2],
+    /// Lowercase names of the operators in the order of `ops.txt`.
+    operators: Vec<String>,
+    groups: HashMap<String, Group>,
+    /// Overrides by lowercase player name.
+    players: HashMap<String, PlayerPermissions>,
+}
+
+impl Permissions {
+    /// Reads both files, a missing `ops.txt` means no operators and a missing groups file is created with defaults.
+    pub fn load(ops_path: impl Into<PathBuf>, groups_path: impl Into<PathBuf>) -> io::Result<Self> {
+        let mut permissions = Self {
+            ops_path: ops_path.into(),
+            groups_path: groups_path.into(),
+            ..Self::default()
+        };
+        permissions.reload()?;
+        Ok(permissions)
+    }
+
+    /// Reads both files again, on an error the permissions stay as they were.
+    pub fn reload(&mut self) -> io::Result<()> {
+        self.modified = self.files_modified();
+        let operators = match fs::read_to_string(&self.ops_path) {
+            Ok(ops) => parse_operators(&ops),
+            Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let groups = match fs::read_to_string(&self.groups_path) {
+            Ok(groups) => serde_json::from_str(&groups)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let groups = default_groups();
+                fs::write(&self.groups_path, serde_json::to_string_pretty(&groups)?)?;
+                self.modified = self.files_modified();
+                groups
+            }
+            Err(err) => return Err(err),
+        };
+        let (groups, players) = parse_groups(&groups)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Malformed permissions file"))?;
+        self.operators = operators;
+        self.groups = groups;
+        self.players = players;
+        Ok(())
+    }
+
+    pub fn is_operator(&self, player: &str) -> bool {
+        self.operators.contains(&player.to_lowercase())
+    }
+
+    /// Adds or removes an operator and writes `ops.txt`, returns whether anything changed.
+    pub fn set_operator(&mut self, player: &str, operator: bool) -> io::Result<bool> {
+        if self.is_operator(player) == operator {
+            return Ok(false);
+        }
+        let player = player.to_lowercase();
+        if operator {
+            self.operators.push(player);
+        } else {
+            self.operators.retain(|op| op != &player);
+        }
+        let mut ops = self.operators.join("\n");
+        ops.push('\n');
+        fs::write(&self.ops_path, ops)?;
+        self.modified = self.files_modified();
+        Ok(true)
+    }
+
+    fn files_modified(&self) -> [Option<SystemTime>; 2] {
+        [&self.ops_path, &self.groups_path].map(|path| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+    }
+
+    pub fn has(&self, player: &str, node: &str) -> bool {
+        let player = player.to_lowercase();
+        let overrides = self.players.get(&player);
+        if let Some(decision) = overrides.and_then(|overrides| decide(&overrides.permissions, node))
+        {
+            return decision;
+        }
+        let mut groups: Vec<&str> = overrides
+            .map(|overrides| overrides.groups.iter().map(String::as_str).collect())
+            .unwrap_or_default();
+        if self.operators.contains(&player) {
+            groups.push(OP_GROUP);
+        }
+        groups.push(DEFAULT_GROUP);
+        let mut visited = HashSet::new();
+        groups
+            .into_iter()
+            .find_map(|group| self.decide_group(group, node, &mut visited))
+            .unwrap_or(false)
+    }
+
+    /// Decides with the nodes of a group before the groups it inherits from, each group is only looked at once.
+    fn decide_group<'a>(
+        &'a self,
+        name: &'a str,
+        node: &str,
+        visited: &mut HashSet<&'a str>,
+    ) -> Option<bool> {
+        if !visited.insert(name) {
+            return None;
+        }
+        let group = self.groups.get(name)?;
+        decide(&group.permissions, node).or_else(|| {
+            group
+                .inherits
+                .iter()
+                .find_map(|parent| self.decide_group(parent, node, visited))
+        })
+    }
+}
+
+/// Reloads the permissions when `ops.txt` or the groups file changed on disk.
+pub fn reload_changed(mut permissions: ResMut<Permissions>) {
+    if permissions.modified == permissions.files_modified() {
+        return;
+    }
+    match permissions.reload() {
+        Ok(()) => info!("Permissions reloaded after their files changed."),
+        Err(err) => warn!("Could not reload the permissions, keeping the old ones: {err}"),
+    }
+}
+
+fn parse_operators(ops: &str) -> Vec<String> {
+    ops.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn default_groups() -> Value {
+    json!({
+        "groups": {
+            DEFAULT_GROUP: {
+                "permissions": [
+                    BUILD,
+                    "betalpha.command.help",
+                    "betalpha.command.list",
+                    "betalpha.command.spawn",
+                ]
+            },
+            OP_GROUP: { "permissions": ["*"] },
+        },
+        "players": {},
+    })
+}
+
+fn strings(value: Option<&Value>) -> Option<Vec<String>> {
+    match value {
+        None => Some(Vec::new()),
+        Some(value) => value
+            .as_array()?
+            .iter()
+            .map(|node| node.as_str().map(str::to_string))
+            .collect(),
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn parse_groups(
+    value: &Value,
+) -> Option<(HashMap<String, Group>, HashMap<String, PlayerPermissions>)> {
+    let mut groups = HashMap::new();
+    for (name, group) in value.get("groups").and_then(Value::as_object)? {
+        groups.insert(
+            name.clone(),
+            Group {
+                inherits: strings(group.get("inherits"))?,
+                permissions: strings(group.get("permissions"))?,
+            },
+        );
+    }
+    let mut players = HashMap::new();
+    if let Some(overrides) = value.get("players") {
+        for (name, player) in overrides.as_object()? {
+            players.insert(
+                name.to_lowercase(),
+                PlayerPermissions {
+                    groups: strings(player.get("groups"))?,
+                    permissions: strings(player.get("permissions"))?,
+                },
+            );
+        }
+    }
+    Some((groups, players))
+}
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(
+        "op",
+        Command::new(
+            "betalpha.command.op",
+            "/op <player>",
+            "Makes a player an operator.",
+            |context, arguments| set_operator(context, arguments, true),
+        ),
+    );
+    registry.register(
+        "deop",
+        Command::new(
+            "betalpha.command.deop",
+            "/deop <player>",
+            "Takes operator away from a player.",
+            |context, arguments| set_operator(context, arguments, false),
+        ),
+    );
+    registry.register(
+        "permissions",
+        Command::new(
+            "betalpha.command.permissions",
+            "/permissions reload",
+            "Reads ops.txt and the permissions file again.",
+            reload,
+        ),
+    );
+}
+
+fn set_operator(
+    context: &mut CommandContext,
+    arguments: &mut Arguments,
+    operator: bool,
+) -> CommandResult {
+    let name = arguments.word()?.to_string();
+    arguments.end()?;
+    let changed = context
+        .ecs
+        .resource_mut::<Permissions>()
+        .set_operator(&name, operator)
+        .map_err(|err| format!("Could not write the operators: {err}"))?;
+    match (changed, operator) {
+        (false, true) => Err(format!("{name} is already an operator.").into()),
+        (false, false) => Err(format!("{name} is not an operator.").into()),
+        (true, true) => {
+            info!("{} made {name} an operator.", context.name(context.sender));
+            Ok(vec![format!("{name} is now an operator.")])
+        }
+        (true, false) => {
+            info!(
+                "{} took operator from {name}.",
+                context.name(context.sender)
+            );
+            Ok(vec![format!("{name} is no longer an operator.")])
+        }
+    }
+}
+
+fn reload(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    if arguments.word()? != "reload" {
+        return Err(CommandError::Usage);
+    }
+    arguments.end()?;
+    context
+        .ecs
+        .resource_mut::<Permissions>()
+        .reload()
+        .map_err(|err| format!("Could not reload the permissions: {err}"))?;
+    info!("Permissions reloaded.");
+    Ok(vec!["Permissions reloaded.".to_string()])
+}
+
+#[test]
+fn test_covers() {
+    assert!(covers("*", "betalpha.command.tp"));
+    assert!(covers("betalpha.command.*", "betalpha.command.tp"));
+    assert!(covers("betalpha.command.tp", "betalpha.command.tp"));
+    assert!(!covers("betalpha.command.tp", "betalpha.command.time"));
+    assert!(!covers("betalpha.command.*", "betalpha.edit.set"));
+    assert!(!covers("betalpha.comm*", "betalpha.command.tp"));
+}
+
+#[test]
+fn test_permissions() {
+    let (groups, players) = parse_groups(&json!({
+        "groups": {
+            "default": { "permissions": ["betalpha.build", "betalpha.command.spawn"] },
+            "builder": { "inherits": ["default", "builder"], "permissions": ["betalpha.edit.*", "-betalpha.edit.schematic"] },
+            "op": { "permissions": ["*"] },
+        },
+        "players": {
+            "Notch": { "groups": ["builder"], "permissions": ["betalpha.command.tp", "-betalpha.build"] },
+        },
+    }))
+    .unwrap();
+    let permissions = Permissions {
+        operators: parse_operators("# operators\nJeb_\n\n"),
+        groups,
+        players,
+        ..Permissions::default()
+    };
+    assert!(permissions.has("Steve", "betalpha.build"));
+    assert!(!permissions.has("Steve", "betalpha.edit.set"));
+    assert!(permissions.has("notch", "betalpha.edit.set"));
+    assert!(permissions.has("Notch", "betalpha.command.spawn"));
+    assert!(permissions.has("Notch", "betalpha.command.tp"));
+    assert!(!permissions.has("Notch", "betalpha.edit.schematic"));
+    assert!(!permissions.has("Notch", "betalpha.build"));
+    assert!(permissions.is_operator("jeb_"));
+    assert!(permissions.has("Jeb_", "betalpha.command.kick"));
+    assert!(!permissions.has("Steve", "betalpha.command.kick"));
+}
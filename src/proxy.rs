@@ -0,0 +1,229 @@
+//! PROXY protocol (v1 and v2) headers sent by load balancers in front of the server.
+//!
+//! Connections from a trusted proxy have to start with a header that contains the address of the
+//! client, connections from anywhere else are taken as they are.
+use std::io::{self, ErrorKind, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+/// Longest v1 header including the line break.
+const V1_MAX_LENGTH: usize = 107;
+/// Longest v2 header, the fixed part and as many addresses and extensions as its length allows.
+pub const MAX_LENGTH: usize = 16 + u16::MAX as usize;
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// A network like `10.0.0.0/8` or `::1/128`, a plain address is a network of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.network, address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(cidr: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid network: {cidr}");
+        let (network, prefix) = cidr.split_once('/').unwrap_or((cidr, ""));
+        let network = IpAddr::from_str(network)
+            .map_err(|_| invalid())?
+            .to_canonical();
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            "" => max,
+            prefix => prefix.parse().map_err(|_| invalid())?,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Self { network, prefix })
+    }
+}
+
+/// The networks of the proxies that may send a header, separated by commas in the settings.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TrustedProxies(Vec<Cidr>);
+
+impl TrustedProxies {
+    pub fn contains(&self, address: IpAddr) -> bool {
+        self.0.iter().any(|cidr| cidr.contains(address))
+    }
+}
+
+impl FromStr for TrustedProxies {
+    type Err = String;
+
+    fn from_str(proxies: &str) -> Result<Self, Self::Err> {
+        proxies
+            .split(',')
+            .map(str::trim)
+            .filter(|cidr| !cidr.is_empty())
+            .map(Cidr::from_str)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+/// Reads a header without reading past it, returns the address of the client it contains or
+/// `None` if the proxy did not send one, like for health checks.
+pub fn read_header(stream: &mut impl Read) -> io::Result<Option<SocketAddr>> {
+    let mut first = [0u8];
+    stream.read_exact(&mut first)?;
+    match first[0] {
+        b'P' => read_v1(stream),
+        b'\r' => read_v2(stream),
+        _ => Err(invalid("Missing PROXY header")),
+    }
+}
+
+/// Parses a header at the start of `buf`, returns its length with the address or `None` if more
+/// bytes are needed.
+pub fn parse_header(buf: &[u8]) -> io::Result<Option<(usize, Option<SocketAddr>)>> {
+    let mut rest = buf;
+    match read_header(&mut rest) {
+        Ok(address) => Ok(Some((buf.len() - rest.len(), address))),
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Reads the rest of a line like `PROXY TCP4 203.0.113.7 10.0.0.1 51234 25565`.
+fn read_v1(stream: &mut impl Read) -> io::Result<Option<SocketAddr>> {
+    let mut line = vec![b'P'];
+    while !line.ends_with(b"\r\n") {
+        if line.len() == V1_MAX_LENGTH {
+            return Err(invalid("PROXY header is too long"));
+        }
+        let mut byte = [0u8];
+        stream.read_exact(&mut byte)?;
+        line.push(byte[0]);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("PROXY header is not text"))?;
+    let parts = line.split(' ').collect::<Vec<_>>();
+    match parts[..] {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", "TCP4" | "TCP6", source, _, port, _] => {
+            let source = source
+                .parse()
+                .map_err(|_| invalid("Invalid source address in PROXY header"))?;
+            let port = port
+                .parse()
+                .map_err(|_| invalid("Invalid source port in PROXY header"))?;
+            Ok(Some(SocketAddr::new(source, port)))
+        }
+        _ => Err(invalid("Invalid PROXY header")),
+    }
+}
+
+/// Reads the rest of a binary header, the first byte of the signature was already read.
+fn read_v2(stream: &mut impl Read) -> io::Result<Option<SocketAddr>> {
+    let mut header = [0u8; 15];
+    stream.read_exact(&mut header)?;
+    if header[..11] != V2_SIGNATURE[1..] {
+        return Err(invalid("Invalid PROXY header signature"));
+    }
+    let (command, family) = (header[11], header[12]);
+    if command >> 4 != 2 {
+        return Err(invalid("Unsupported PROXY protocol version"));
+    }
+    let mut addresses = vec![0u8; u16::from_be_bytes([header[13], header[14]]) as usize];
+    stream.read_exact(&mut addresses)?;
+    // LOCAL connections come from the proxy itself.
+    if command & 0x0F == 0 {
+        return Ok(None);
+    }
+    let address = match family {
+        0x11 if addresses.len() >= 12 => {
+            let ip = <[u8; 4]>::try_from(&addresses[..4]).unwrap();
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::from(ip)), port)
+        }
+        0x21 if addresses.len() >= 36 => {
+            let ip = <[u8; 16]>::try_from(&addresses[..16]).unwrap();
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::from(ip)), port)
+        }
+        0x11 | 0x21 => return Err(invalid("PROXY header is too short")),
+        // Other protocols and unix sockets have no address a ban could apply to.
+        _ => return Ok(None),
+    };
+    Ok(Some(address))
+}
+
+#[test]
+fn test_trusted_proxies() {
+    let proxies: TrustedProxies = "10.0.0.0/8, 192.168.1.5,::1".parse().unwrap();
+    assert!(proxies.contains("10.20.30.40".parse().unwrap()));
+    assert!(proxies.contains("192.168.1.5".parse().unwrap()));
+    assert!(!proxies.contains("192.168.1.6".parse().unwrap()));
+    assert!(proxies.contains("::1".parse().unwrap()));
+    // Dual-stack sockets report IPv4 clients as mapped IPv6 addresses.
+    assert!(proxies.contains("::ffff:10.1.2.3".parse().unwrap()));
+    assert!("0.0.0.0/0"
+        .parse::<Cidr>()
+        .unwrap()
+        .contains("8.8.8.8".parse().unwrap()));
+    assert_eq!("".parse(), Ok(TrustedProxies::default()));
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert!("proxy".parse::<TrustedProxies>().is_err());
+}
+
+#[test]
+fn test_read_header() {
+    let mut stream: &[u8] = b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 25565\r\n\x02";
+    assert_eq!(
+        read_header(&mut stream).unwrap(),
+        Some("203.0.113.7:51234".parse().unwrap())
+    );
+    // The packets after the header are left to be read.
+    assert_eq!(stream, b"\x02");
+    let mut stream: &[u8] = b"PROXY UNKNOWN\r\n";
+    assert_eq!(read_header(&mut stream).unwrap(), None);
+    let mut stream: &[u8] = b"PROXY TCP4 nonsense\r\n";
+    assert!(read_header(&mut stream).is_err());
+    let mut stream: &[u8] = b"\x02\x00\x05";
+    assert!(read_header(&mut stream).is_err());
+
+    let mut v2 = V2_SIGNATURE.to_vec();
+    v2.extend([0x21, 0x21, 0, 36]);
+    v2.extend(Ipv6Addr::from([0x2001, 0xdb8, 0, 0, 0, 0, 0, 7]).octets());
+    v2.extend(Ipv6Addr::LOCALHOST.octets());
+    v2.extend([0xC8, 0x22, 0x63, 0xDD, 0x02]);
+    let mut stream = &v2[..];
+    assert_eq!(
+        read_header(&mut stream).unwrap(),
+        Some("[2001:db8::7]:51234".parse().unwrap())
+    );
+    assert_eq!(stream, b"\x02");
+    // Headers that did not arrive completely are read again once there is more.
+    assert_eq!(parse_header(&v2[..30]).unwrap(), None);
+    assert_eq!(parse_header(b"PROXY TCP4 203.0").unwrap(), None);
+    let (length, address) = parse_header(&v2).unwrap().unwrap();
+    assert_eq!(length, v2.len() - 1);
+    assert_eq!(address, Some("[2001:db8::7]:51234".parse().unwrap()));
+    assert!(parse_header(b"\x02\x00").is_err());
+    let mut local = V2_SIGNATURE.to_vec();
+    local.extend([0x20, 0x00, 0, 0]);
+    assert_eq!(read_header(&mut &local[..]).unwrap(), None);
+}
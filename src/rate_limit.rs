@@ -0,0 +1,353 @@
+//! Flood protection: how often an address may connect, how many packets a connection may send
+//! and how fast a player may chat.
+use crate::packet::ids;
+use bevy::prelude::{Component, Resource};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Packets a connection may send per second and in a burst, by packet class.
+const PACKET_BUDGETS: [(f64, f64); 4] = [(40.0, 100.0), (30.0, 60.0), (5.0, 10.0), (20.0, 40.0)];
+/// Packets of a class dropped in a row before the connection is kicked.
+const MAX_DROPPED: u32 = 200;
+/// Chat messages allowed within `CHAT_WINDOW`.
+const CHAT_MESSAGES: usize = 5;
+const CHAT_WINDOW: Duration = Duration::from_secs(5);
+/// Times the same message may be sent in a row.
+const CHAT_REPEATS: usize = 3;
+/// Commands allowed within `CHAT_WINDOW`, they may be repeated as often as they like.
+const COMMANDS: usize = 10;
+/// Mutes for the first and second offence, the third one kicks.
+const MUTES: [Duration; 2] = [Duration::from_secs(10), Duration::from_secs(60)];
+/// Time without spam after which offences are forgotten.
+const OFFENCE_DECAY: Duration = Duration::from_secs(300);
+
+/// Refills `rate` tokens a second up to `burst`, every allowed action takes one.
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(burst: f64, now: Instant) -> Self {
+        Self {
+            tokens: burst,
+            updated: now,
+        }
+    }
+
+    fn take(&mut self, rate: f64, burst: f64, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.updated = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    fn is_full(&self, rate: f64, burst: f64, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * rate >= burst
+    }
+}
+
+/// Limits how often each address may connect, `connection-throttle` times a minute.
+#[derive(Resource, Default)]
+pub struct ConnectionThrottle {
+    buckets: HashMap<IpAddr, Bucket>,
+}
+
+impl ConnectionThrottle {
+    /// Whether `address` may connect now, a limit of 0 allows everything.
+    pub fn allow(&mut self, address: IpAddr, per_minute: u32, now: Instant) -> bool {
+        if per_minute == 0 {
+            return true;
+        }
+        let (rate, burst) = (per_minute as f64 / 60.0, per_minute as f64);
+        // Addresses that did not connect for a while do not need to be remembered.
+        self.buckets
+            .retain(|_, bucket| !bucket.is_full(rate, burst, now));
+        self.buckets
+            .entry(address)
+            .or_insert_with(|| Bucket::new(burst, now))
+            .take(rate, burst, now)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketClass {
+    Movement,
+    /// Digging, placing, using entities and swinging the arm.
+    Interaction,
+    /// Chat messages and commands.
+    Chat,
+    Other,
+}
+
+impl PacketClass {
+    pub fn of(packet_id: u8) -> Self {
+        match packet_id {
+            ids::PLAYER
+            | ids::PLAYER_POSITION
+            | ids::PLAYER_LOOK
+            | ids::PLAYER_POSITION_AND_LOOK => Self::Movement,
+            ids::PLAYER_DIGGING
+            | ids::PLAYER_BLOCK_PLACEMENT
+            | ids::USE_ENTITY
+            | ids::ANIMATION => Self::Interaction,
+            ids::CHAT_MESSAGE => Self::Chat,
+            _ => Self::Other,
+        }
+    }
+
+    fn budget(self) -> (f64, f64) {
+        PACKET_BUDGETS[self as usize]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PacketVerdict {
+    Allow,
+    /// The packet is dropped, `first` is set for the first one of a row so it is logged once.
+    Drop {
+        first: bool,
+    },
+    Kick,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChatVerdict {
+    Allow,
+    /// The player was muted before and still is for the given time.
+    Muted(Duration),
+    /// The message was spam, the player is muted for the given time.
+    Mute(Duration),
+    Kick,
+}
+
+/// What a connection sent recently, kept to stop it from flooding the server.
+#[derive(Component, Debug)]
+pub struct FloodProtection {
+    buckets: [Bucket; 4],
+    dropped: [u32; 4],
+    chat: VecDeque<(Instant, String)>,
+    commands: VecDeque<Instant>,
+    muted_until: Option<Instant>,
+    offences: usize,
+    last_offence: Option<Instant>,
+}
+
+impl FloodProtection {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            buckets: PACKET_BUDGETS.map(|(_, burst)| Bucket::new(burst, now)),
+            dropped: [0; 4],
+            chat: VecDeque::new(),
+            commands: VecDeque::new(),
+            muted_until: None,
+            offences: 0,
+            last_offence: None,
+        }
+    }
+
+    /// Takes a packet from the budget of its class.
+    pub fn packet(&mut self, class: PacketClass, now: Instant) -> PacketVerdict {
+        let (rate, burst) = class.budget();
+        let index = class as usize;
+        if self.buckets[index].take(rate, burst, now) {
+            self.dropped[index] = 0;
+            return PacketVerdict::Allow;
+        }
+        self.dropped[index] += 1;
+        match self.dropped[index] {
+            1 => PacketVerdict::Drop { first: true },
+            MAX_DROPPED.. => PacketVerdict::Kick,
+            _ => PacketVerdict::Drop { first: false },
+        }
+    }
+
+    /// Checks a chat message for spam, a message that is not allowed is not sent to anyone.
+    pub fn chat(&mut self, message: &str, now: Instant) -> ChatVerdict {
+        if let Some(verdict) = self.muted(now) {
+            return verdict;
+        }
+        while self
+            .chat
+            .front()
+            .is_some_and(|(sent, _)| now.saturating_duration_since(*sent) > CHAT_WINDOW)
+        {
+            self.chat.pop_front();
+        }
+        self.chat.push_back((now, message.to_string()));
+        let repeats = self
+            .chat
+            .iter()
+            .rev()
+            .take_while(|(_, sent)| sent.eq_ignore_ascii_case(message))
+            .count();
+        if self.chat.len() <= CHAT_MESSAGES && repeats <= CHAT_REPEATS {
+            return ChatVerdict::Allow;
+        }
+        self.chat.clear();
+        self.offend(now)
+    }
+
+    /// Checks a command for spam, muted players can not run commands either.
+    pub fn command(&mut self, now: Instant) -> ChatVerdict {
+        if let Some(verdict) = self.muted(now) {
+            return verdict;
+        }
+        while self
+            .commands
+            .front()
+            .is_some_and(|sent| now.saturating_duration_since(*sent) > CHAT_WINDOW)
+        {
+            self.commands.pop_front();
+        }
+        self.commands.push_back(now);
+        if self.commands.len() <= COMMANDS {
+            return ChatVerdict::Allow;
+        }
+        self.commands.clear();
+        self.offend(now)
+    }
+
+    /// Returns the verdict for a muted player, forgets old offences otherwise.
+    fn muted(&mut self, now: Instant) -> Option<ChatVerdict> {
+        if let Some(until) = self.muted_until.filter(|until| *until > now) {
+            return Some(ChatVerdict::Muted(until - now));
+        }
+        if self
+            .last_offence
+            .is_some_and(|last| now.saturating_duration_since(last) > OFFENCE_DECAY)
+        {
+            self.offences = 0;
+        }
+        None
+    }
+
+    /// Mutes the player for longer with every offence, until they are kicked.
+    fn offend(&mut self, now: Instant) -> ChatVerdict {
+        self.last_offence = Some(now);
+        self.offences += 1;
+        match MUTES.get(self.offences - 1) {
+            Some(duration) => {
+                self.muted_until = Some(now + *duration);
+                ChatVerdict::Mute(*duration)
+            }
+            None => ChatVerdict::Kick,
+        }
+    }
+}
+
+#[test]
+fn test_connection_throttle() {
+    let mut throttle = ConnectionThrottle::default();
+    let address = IpAddr::from([10, 0, 0, 1]);
+    let now = Instant::now();
+    for _ in 0..3 {
+        assert!(throttle.allow(address, 3, now));
+    }
+    assert!(!throttle.allow(address, 3, now));
+    assert!(throttle.allow(IpAddr::from([10, 0, 0, 2]), 3, now));
+    assert!(throttle.allow(address, 3, now + Duration::from_secs(20)));
+    assert!(throttle.allow(address, 0, now));
+    // Full buckets are forgotten.
+    throttle.allow(address, 3, now + Duration::from_secs(600));
+    assert_eq!(throttle.buckets.len(), 1);
+}
+
+#[test]
+fn test_packet_budget() {
+    let now = Instant::now();
+    let mut flood = FloodProtection::new(now);
+    assert_eq!(
+        PacketClass::of(ids::PLAYER_DIGGING),
+        PacketClass::Interaction
+    );
+    for _ in 0..10 {
+        assert_eq!(flood.packet(PacketClass::Chat, now), PacketVerdict::Allow);
+    }
+    assert_eq!(
+        flood.packet(PacketClass::Chat, now),
+        PacketVerdict::Drop { first: true }
+    );
+    assert_eq!(
+        flood.packet(PacketClass::Chat, now),
+        PacketVerdict::Drop { first: false }
+    );
+    // Other classes have their own budget.
+    assert_eq!(
+        flood.packet(PacketClass::Movement, now),
+        PacketVerdict::Allow
+    );
+    let later = now + Duration::from_secs(1);
+    assert_eq!(flood.packet(PacketClass::Chat, later), PacketVerdict::Allow);
+    for _ in 0..4 {
+        flood.packet(PacketClass::Chat, later);
+    }
+    for _ in 1..MAX_DROPPED {
+        assert_ne!(flood.packet(PacketClass::Chat, later), PacketVerdict::Kick);
+    }
+    assert_eq!(flood.packet(PacketClass::Chat, later), PacketVerdict::Kick);
+}
+
+#[test]
+fn test_chat_spam() {
+    let now = Instant::now();
+    let second = Duration::from_secs(1);
+    let mut flood = FloodProtection::new(now);
+    for i in 0..CHAT_REPEATS {
+        assert_eq!(
+            flood.chat("hi", now + second * i as u32),
+            ChatVerdict::Allow
+        );
+    }
+    assert_eq!(
+        flood.chat("HI", now + second * 3),
+        ChatVerdict::Mute(MUTES[0])
+    );
+    assert_eq!(
+        flood.chat("hello", now + second * 4),
+        ChatVerdict::Muted(MUTES[0] - second)
+    );
+    let now = now + Duration::from_secs(20);
+    for i in 0..CHAT_MESSAGES {
+        assert_eq!(flood.chat(&i.to_string(), now), ChatVerdict::Allow);
+    }
+    assert_eq!(flood.chat("6", now), ChatVerdict::Mute(MUTES[1]));
+    let now = now + Duration::from_secs(120);
+    for i in 0..CHAT_MESSAGES {
+        assert_eq!(flood.chat(&i.to_string(), now), ChatVerdict::Allow);
+    }
+    assert_eq!(flood.chat("6", now), ChatVerdict::Kick);
+    // Offences are forgotten after a while without spam.
+    let now = now + OFFENCE_DECAY * 2;
+    for i in 0..CHAT_MESSAGES {
+        flood.chat(&i.to_string(), now);
+    }
+    assert_eq!(flood.chat("6", now), ChatVerdict::Mute(MUTES[0]));
+}
+
+#[test]
+fn test_command_spam() {
+    let now = Instant::now();
+    let mut flood = FloodProtection::new(now);
+    for _ in 0..COMMANDS {
+        assert_eq!(flood.command(now), ChatVerdict::Allow);
+    }
+    assert_eq!(flood.command(now), ChatVerdict::Mute(MUTES[0]));
+    // Muted players can neither chat nor run commands.
+    assert_eq!(flood.chat("hi", now), ChatVerdict::Muted(MUTES[0]));
+    assert_eq!(flood.command(now), ChatVerdict::Muted(MUTES[0]));
+    let now = now + MUTES[0];
+    assert_eq!(flood.command(now), ChatVerdict::Allow);
+    // Chat and commands count offences together.
+    for _ in 0..=CHAT_REPEATS {
+        flood.chat("hi", now);
+    }
+    assert_eq!(flood.command(now), ChatVerdict::Muted(MUTES[1]));
+}
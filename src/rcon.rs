@@ -0,0 +1,293 @@
+//! Remote console compatible with the Source RCON protocol that Minecraft servers use.
+//!
+//! Connections are handled on their own threads, a client has to log in with `rcon-password` before
+//! its commands are handed to the game and run like the ones players send.
+use crate::command::{self, CommandRegistry, Console};
+use crate::config::Settings;
+use bevy::prelude::{Entity, Local, Mut, Resource};
+use log::{debug, info, warn};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const RESPONSE_VALUE: i32 = 0;
+const EXEC_COMMAND: i32 = 2;
+const AUTH_RESPONSE: i32 = 2;
+const AUTH: i32 = 3;
+/// Longest body a client may send, like the vanilla server.
+const MAX_REQUEST: usize = 1446;
+/// Longest body of one response packet, longer output is split over several.
+const MAX_RESPONSE: usize = 4096;
+/// Time a command may wait for the game before the client gets an error.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time a client may stay silent or take to read a response before it is disconnected.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Consoles connected at once, more are disconnected right away.
+const MAX_CONNECTIONS: usize = 4;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: String,
+}
+
+impl Packet {
+    /// Reads a packet, the length, id and type are little endian.
+    fn read(stream: &mut impl Read) -> io::Result<Self> {
+        let mut int = [0u8; 4];
+        stream.read_exact(&mut int)?;
+        let length = i32::from_le_bytes(int);
+        if !(10..=(MAX_REQUEST + 10) as i32).contains(&length) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid packet length {length}"),
+            ));
+        }
+        let mut data = vec![0u8; length as usize];
+        stream.read_exact(&mut data)?;
+        let id = i32::from_le_bytes(data[0..4].try_into().unwrap());
+        let kind = i32::from_le_bytes(data[4..8].try_into().unwrap());
+        // The body ends with a null byte, followed by an empty string.
+        let body = data[8..].split(|b| *b == 0).next().unwrap_or_default();
+        let body = String::from_utf8_lossy(body);
+        Ok(Self {
+            id,
+            kind,
+            body: body.into_owned(),
+        })
+    }
+
+    fn write(&self, stream: &mut impl Write) -> io::Result<()> {
+        let mut data = Vec::with_capacity(self.body.len() + 14);
+        data.extend((self.body.len() as i32 + 10).to_le_bytes());
+        data.extend(self.id.to_le_bytes());
+        data.extend(self.kind.to_le_bytes());
+        data.extend(self.body.as_bytes());
+        data.extend([0, 0]);
+        stream.write_all(&data)
+    }
+}
+
+/// A command from a remote console and where to send its output.
+struct Request {
+    command: String,
+    reply: Sender<String>,
+}
+
+/// Hands commands from the connection threads to the game.
+#[derive(Resource)]
+pub struct Rcon {
+    requests: Mutex<Receiver<Request>>,
+}
+
+impl Rcon {
+    /// Listens on `rcon-bind` if the remote console is enabled.
+    pub fn start(settings: &Settings) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        if settings.enable_rcon && settings.rcon_password.is_empty() {
+            warn!("The remote console is not started, rcon-password is not set.");
+        } else if settings.enable_rcon {
+            let listener = TcpListener::bind(settings.rcon_bind)?;
+            let password = Arc::new(settings.rcon_password.clone());
+            std::thread::Builder::new()
+                .name("rcon".to_string())
+                .spawn(move || accept(listener, password, sender))?;
+            info!("Remote console listening on {}.", settings.rcon_bind);
+        }
+        Ok(Self {
+            requests: Mutex::new(receiver),
+        })
+    }
+}
+
+fn accept(listener: TcpListener, password: Arc<String>, requests: Sender<Request>) {
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let Ok(address) = stream.peer_addr() else {
+            continue;
+        };
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            warn!("Refused remote console {address}, {MAX_CONNECTIONS} are connected already.");
+            continue;
+        }
+        let (password, requests) = (password.clone(), requests.clone());
+        let thread_connections = connections.clone();
+        let spawned = std::thread::Builder::new()
+            .name(format!("rcon {address}"))
+            .spawn(move || {
+                debug!("Remote console connected from {address}.");
+                if let Err(err) = serve(stream, address, &password, &requests) {
+                    debug!("Remote console {address} disconnected: {err}");
+                }
+                thread_connections.fetch_sub(1, Ordering::SeqCst);
+            });
+        if let Err(err) = spawned {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            warn!("Could not handle the remote console {address}: {err}");
+        }
+    }
+}
+
+fn serve(
+    mut stream: TcpStream,
+    address: SocketAddr,
+    password: &str,
+    requests: &Sender<Request>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut authenticated = false;
+    loop {
+        let packet = Packet::read(&mut stream)?;
+        match packet.kind {
+            AUTH => {
+                authenticated = packet.body == password;
+                let id = if authenticated { packet.id } else { -1 };
+                Packet {
+                    id,
+                    kind: AUTH_RESPONSE,
+                    body: String::new(),
+                }
+                .write(&mut stream)?;
+                if !authenticated {
+                    // Like vanilla the client has to connect again for every guess.
+                    warn!("Remote console {address} used a wrong password, disconnecting.");
+                    return Ok(());
+                }
+                info!("Remote console {address} logged in.");
+            }
+            EXEC_COMMAND if authenticated => {
+                let (reply, output) = mpsc::channel();
+                let command = packet.body.trim_start_matches('/').to_string();
+                requests
+                    .send(Request { command, reply })
+                    .map_err(|_| io::Error::new(ErrorKind::BrokenPipe, "The server stopped"))?;
+                let output = output
+                    .recv_timeout(COMMAND_TIMEOUT)
+                    .unwrap_or_else(|_| "The command timed out.".to_string());
+                for body in split(&output) {
+                    Packet {
+                        id: packet.id,
+                        kind: RESPONSE_VALUE,
+                        body: body.to_string(),
+                    }
+                    .write(&mut stream)?;
+                }
+            }
+            EXEC_COMMAND => {
+                Packet {
+                    id: -1,
+                    kind: AUTH_RESPONSE,
+                    body: String::new(),
+                }
+                .write(&mut stream)?;
+                return Ok(());
+            }
+            kind => {
+                Packet {
+                    id: packet.id,
+                    kind: RESPONSE_VALUE,
+                    body: format!("Unknown request {kind:#x}"),
+                }
+                .write(&mut stream)?;
+            }
+        }
+    }
+}
+
+/// Splits the output of a command into response bodies, always at least one even if it is empty.
+fn split(output: &str) -> Vec<&str> {
+    let mut bodies = Vec::new();
+    let mut rest = output;
+    while rest.len() > MAX_RESPONSE {
+        let mut end = MAX_RESPONSE;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        bodies.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    bodies.push(rest);
+    bodies
+}
+
+/// Removes the `§` color codes meant for the chat of the client.
+fn strip_colors(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '§' => {
+                chars.next();
+            }
+            c => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+/// Runs the commands remote consoles sent, every loop instead of every tick so they do not wait on it.
+pub fn run_commands(ecs: &mut bevy::prelude::World, mut console: Local<Option<Entity>>) {
+    let requests = ecs
+        .resource::<Rcon>()
+        .requests
+        .lock()
+        .unwrap()
+        .try_iter()
+        .collect::<Vec<_>>();
+    if requests.is_empty() {
+        return;
+    }
+    let console = *console.get_or_insert_with(|| {
+        let name = "Rcon".to_string();
+        ecs.spawn(Console { name }).id()
+    });
+    ecs.resource_scope(|ecs, registry: Mut<CommandRegistry>| {
+        for request in requests {
+            let lines = command::execute(ecs, &registry, console, &request.command);
+            let output = lines
+                .iter()
+                .map(|line| strip_colors(line))
+                .collect::<Vec<_>>();
+            // The connection may have closed in the meantime.
+            let _ = request.reply.send(output.join("\n"));
+        }
+    });
+}
+
+#[test]
+fn test_rcon_packets() {
+    let mut data = Vec::new();
+    let packet = Packet {
+        id: 7,
+        kind: EXEC_COMMAND,
+        body: "list".to_string(),
+    };
+    packet.write(&mut data).unwrap();
+    assert_eq!(&data[..12], [14, 0, 0, 0, 7, 0, 0, 0, 2, 0, 0, 0]);
+    assert_eq!(&data[12..], b"list\0\0");
+    assert_eq!(Packet::read(&mut &data[..]).unwrap(), packet);
+    // Lengths that can not be a packet are refused before reading the rest.
+    let mut data: &[u8] = &[5, 0, 0, 0, 0, 0, 0, 0, 0];
+    assert!(Packet::read(&mut data).is_err());
+    let mut data: &[u8] = &[0xFF, 0xFF, 0, 0];
+    assert!(Packet::read(&mut data).is_err());
+
+    assert_eq!(split(""), vec![""]);
+    let long = "§".repeat(MAX_RESPONSE);
+    let bodies = split(&long);
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[0].len(), MAX_RESPONSE);
+    assert_eq!(
+        strip_colors("§cUsage: /kick <player> §7x"),
+        "Usage: /kick <player> x"
+    );
+}
@@ -0,0 +1,53 @@
+pub mod block;
+pub mod item;
+
+/// The class of tool that breaks a block the fastest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tool {
+    None,
+    Pickaxe,
+    Shovel,
+    Axe,
+    Sword,
+    Hoe,
+}
+
+/// What a block leaves behind when it is broken.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Drop {
+    Nothing,
+    Itself,
+    Item { id: u16, count: u8 },
+}
+
+/// Resolves the block that gets placed when a player uses the item with id `item_id` on a block.
+///
+/// Returns `None` if the item can not be placed at all.
+pub fn placed_block(item_id: u16) -> Option<u8> {
+    if let Ok(block_id) = u8::try_from(item_id) {
+        block::get(block_id).filter(|b| b.placeable).map(|b| b.id)
+    } else {
+        item::get(item_id).and_then(|i| i.places)
+    }
+}
+
+/// Resolves what a broken block drops as `(item_id, count)`.
+pub fn dropped_item(block_id: u8) -> Option<(u16, u8)> {
+    match block::get(block_id)?.drop {
+        Drop::Nothing => None,
+        Drop::Itself => Some((block_id as u16, 1)),
+        Drop::Item { id, count } => Some((id, count)),
+    }
+}
+
+#[test]
+fn test_registry_lookup() {
+    assert!(block::BLOCKS.windows(2).all(|w| w[0].id < w[1].id));
+    assert!(item::ITEMS.windows(2).all(|w| w[0].id < w[1].id));
+    assert_eq!(placed_block(4), Some(4));
+    assert_eq!(placed_block(323), Some(63));
+    assert_eq!(placed_block(8), None);
+    assert_eq!(placed_block(263), None);
+    assert_eq!(dropped_item(1), Some((4, 1)));
+    assert_eq!(dropped_item(7), None);
+}
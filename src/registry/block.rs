@@ -0,0 +1,176 @@
+use super::{Drop, Tool};
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub id: u8,
+    pub name: &'static str,
+    /// Time factor it takes to break the block, negative values mean the block is unbreakable.
+    pub hardness: f32,
+    pub tool: Tool,
+    pub drop: Drop,
+    /// How much light is absorbed by the block, ranging from 0 (transparent) to 15 (opaque).
+    pub opacity: u8,
+    /// Light level emitted by the block, ranging from 0 to 15.
+    pub light: u8,
+    /// Whether entities collide with the block.
+    pub solid: bool,
+    /// Whether players are allowed to place the block from their inventory.
+    pub placeable: bool,
+}
+
+impl Block {
+    const fn new(id: u8, name: &'static str) -> Self {
+        Self {
+            id,
+            name,
+            hardness: 0.0,
+            tool: Tool::None,
+            drop: Drop::Itself,
+            opacity: 15,
+            light: 0,
+            solid: true,
+            placeable: true,
+        }
+    }
+
+    const fn hardness(self, hardness: f32) -> Self {
+        Self { hardness, ..self }
+    }
+
+    const fn tool(self, tool: Tool) -> Self {
+        Self { tool, ..self }
+    }
+
+    const fn drops(self, drop: Drop) -> Self {
+        Self { drop, ..self }
+    }
+
+    const fn opacity(self, opacity: u8) -> Self {
+        Self { opacity, ..self }
+    }
+
+    const fn light(self, light: u8) -> Self {
+        Self { light, ..self }
+    }
+
+    /// Marks the block as fully transparent and without collision.
+    const fn passable(self) -> Self {
+        Self {
+            opacity: 0,
+            solid: false,
+            ..self
+        }
+    }
+
+    const fn not_placeable(self) -> Self {
+        Self {
+            placeable: false,
+            ..self
+        }
+    }
+
+    pub fn is_unbreakable(&self) -> bool {
+        self.hardness < 0.0
+    }
+}
+
+const fn item(id: u16, count: u8) -> Drop {
+    Drop::Item { id, count }
+}
+
+pub const AIR: u8 = 0;
+
+/// All blocks known to Alpha 1.2.6, sorted by id.
+#[rustfmt::skip]
+pub static BLOCKS: &[Block] = &[
+    Block::new(0, "air").passable().drops(Drop::Nothing).not_placeable(),
+    Block::new(1, "stone").hardness(1.5).tool(Tool::Pickaxe).drops(item(4, 1)),
+    Block::new(2, "grass").hardness(0.6).tool(Tool::Shovel).drops(item(3, 1)),
+    Block::new(3, "dirt").hardness(0.5).tool(Tool::Shovel),
+    Block::new(4, "cobblestone").hardness(2.0).tool(Tool::Pickaxe),
+    Block::new(5, "planks").hardness(2.0).tool(Tool::Axe),
+    Block::new(6, "sapling").passable(),
+    Block::new(7, "bedrock").hardness(-1.0).drops(Drop::Nothing).not_placeable(),
+    Block::new(8, "flowing_water").hardness(100.0).passable().opacity(3).drops(Drop::Nothing).not_placeable(),
+    Block::new(9, "water").hardness(100.0).passable().opacity(3).drops(Drop::Nothing).not_placeable(),
+    Block::new(10, "flowing_lava").hardness(100.0).passable().light(15).drops(Drop::Nothing).not_placeable(),
+    Block::new(11, "lava").hardness(100.0).passable().light(15).drops(Drop::Nothing).not_placeable(),
+    Block::new(12, "sand").hardness(0.5).tool(Tool::Shovel),
+    Block::new(13, "gravel").hardness(0.6).tool(Tool::Shovel),
+    Block::new(14, "gold_ore").hardness(3.0).tool(Tool::Pickaxe),
+    Block::new(15, "iron_ore").hardness(3.0).tool(Tool::Pickaxe),
+    Block::new(16, "coal_ore").hardness(3.0).tool(Tool::Pickaxe).drops(item(263, 1)),
+    Block::new(17, "log").hardness(2.0).tool(Tool::Axe),
+    Block::new(18, "leaves").hardness(0.2).opacity(1).drops(Drop::Nothing),
+    Block::new(19, "sponge").hardness(0.6),
+    Block::new(20, "glass").hardness(0.3).opacity(0).drops(Drop::Nothing),
+    Block::new(35, "wool").hardness(0.8),
+    Block::new(37, "dandelion").passable(),
+    Block::new(38, "rose").passable(),
+    Block::new(39, "brown_mushroom").passable().light(1),
+    Block::new(40, "red_mushroom").passable(),
+    Block::new(41, "gold_block").hardness(3.0).tool(Tool::Pickaxe),
+    Block::new(42, "iron_block").hardness(5.0).tool(Tool::Pickaxe),
+    Block::new(43, "double_slab").hardness(2.0).tool(Tool::Pickaxe).drops(item(44, 2)).not_placeable(),
+    Block::new(44, "slab").hardness(2.0).tool(Tool::Pickaxe).opacity(0),
+    Block::new(45, "bricks").hardness(2.0).tool(Tool::Pickaxe),
+    Block::new(46, "tnt"),
+    Block::new(47, "bookshelf").hardness(1.5).tool(Tool::Axe).drops(Drop::Nothing),
+    Block::new(48, "mossy_cobblestone").hardness(2.0).tool(Tool::Pickaxe),
+    Block::new(49, "obsidian").hardness(10.0).tool(Tool::Pickaxe),
+    Block::new(50, "torch").passable().light(14),
+    Block::new(51, "fire").passable().light(15).drops(Drop::Nothing).not_placeable(),
+    Block::new(52, "mob_spawner").hardness(5.0).tool(Tool::Pickaxe).opacity(0).drops(Drop::Nothing),
+    Block::new(53, "wooden_stairs").hardness(2.0).tool(Tool::Axe).opacity(0),
+    Block::new(54, "chest").hardness(2.5).tool(Tool::Axe),
+    Block::new(55, "redstone_wire").passable().drops(item(331, 1)).not_placeable(),
+    Block::new(56, "diamond_ore").hardness(3.0).tool(Tool::Pickaxe).drops(item(264, 1)),
+    Block::new(57, "diamond_block").hardness(5.0).tool(Tool::Pickaxe),
+    Block::new(58, "workbench").hardness(2.5).tool(Tool::Axe),
+    Block::new(59, "crops").passable().drops(item(295, 1)).not_placeable(),
+    Block::new(60, "farmland").hardness(0.6).tool(Tool::Shovel).opacity(0).drops(item(3, 1)).not_placeable(),
+    Block::new(61, "furnace").hardness(3.5).tool(Tool::Pickaxe),
+    Block::new(62, "lit_furnace").hardness(3.5).tool(Tool::Pickaxe).light(13).drops(item(61, 1)).not_placeable(),
+    Block::new(63, "sign_post").hardness(1.0).tool(Tool::Axe).passable().drops(item(323, 1)).not_placeable(),
+    Block::new(64, "wooden_door").hardness(3.0).tool(Tool::Axe).opacity(0).drops(item(324, 1)).not_placeable(),
+    Block::new(65, "ladder").hardness(0.4).tool(Tool::Axe).passable(),
+    Block::new(66, "rail").hardness(0.7).tool(Tool::Pickaxe).passable(),
+    Block::new(67, "cobblestone_stairs").hardness(2.0).tool(Tool::Pickaxe).opacity(0),
+    Block::new(68, "wall_sign").hardness(1.0).tool(Tool::Axe).passable().drops(item(323, 1)).not_placeable(),
+    Block::new(69, "lever").hardness(0.5).passable(),
+    Block::new(70, "stone_pressure_plate").hardness(0.5).tool(Tool::Pickaxe).passable(),
+    Block::new(71, "iron_door").hardness(5.0).tool(Tool::Pickaxe).opacity(0).drops(item(330, 1)).not_placeable(),
+    Block::new(72, "wooden_pressure_plate").hardness(0.5).tool(Tool::Axe).passable(),
+    Block::new(73, "redstone_ore").hardness(3.0).tool(Tool::Pickaxe).drops(item(331, 4)),
+    Block::new(74, "lit_redstone_ore").hardness(3.0).tool(Tool::Pickaxe).light(9).drops(item(331, 4)).not_placeable(),
+    Block::new(75, "unlit_redstone_torch").passable().drops(item(76, 1)).not_placeable(),
+    Block::new(76, "redstone_torch").passable().light(7),
+    Block::new(77, "stone_button").hardness(0.5).tool(Tool::Pickaxe).passable(),
+    Block::new(78, "snow_layer").hardness(0.1).tool(Tool::Shovel).passable().drops(item(332, 1)),
+    Block::new(79, "ice").hardness(0.5).tool(Tool::Pickaxe).opacity(3).drops(Drop::Nothing),
+    Block::new(80, "snow").hardness(0.2).tool(Tool::Shovel).drops(item(332, 4)),
+    Block::new(81, "cactus").hardness(0.4).opacity(0),
+    Block::new(82, "clay").hardness(0.6).tool(Tool::Shovel).drops(item(337, 4)),
+    Block::new(83, "reeds").passable().drops(item(338, 1)).not_placeable(),
+    Block::new(84, "jukebox").hardness(2.0).tool(Tool::Axe),
+    Block::new(85, "fence").hardness(2.0).tool(Tool::Axe).opacity(0),
+    Block::new(86, "pumpkin").hardness(1.0).tool(Tool::Axe),
+    Block::new(87, "netherrack").hardness(0.4).tool(Tool::Pickaxe),
+    Block::new(88, "soul_sand").hardness(0.5).tool(Tool::Shovel),
+    Block::new(89, "glowstone").hardness(0.3).opacity(0).light(15).drops(item(348, 2)),
+    Block::new(90, "portal").hardness(-1.0).passable().light(11).drops(Drop::Nothing).not_placeable(),
+    Block::new(91, "lit_pumpkin").hardness(1.0).tool(Tool::Axe).light(15),
+];
+
+/// Returns the block registered under `id` or `None` if the id is unknown.
+pub fn get(id: u8) -> Option<&'static Block> {
+    BLOCKS
+        .binary_search_by_key(&id, |b| b.id)
+        .ok()
+        .map(|index| &BLOCKS[index])
+}
+
+/// Looks a block up by its name, e.g. `"cobblestone"`.
+pub fn by_name(name: &str) -> Option<&'static Block> {
+    BLOCKS.iter().find(|b| b.name.eq_ignore_ascii_case(name))
+}
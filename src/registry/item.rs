@@ -0,0 +1,175 @@
+use super::Tool;
+
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub id: u16,
+    pub name: &'static str,
+    pub max_stack: u8,
+    /// Number of uses before the item breaks, `0` for items without durability.
+    pub durability: u16,
+    pub tool: Tool,
+    /// The block this item places when used on a block.
+    pub places: Option<u8>,
+}
+
+impl Item {
+    const fn new(id: u16, name: &'static str) -> Self {
+        Self {
+            id,
+            name,
+            max_stack: 64,
+            durability: 0,
+            tool: Tool::None,
+            places: None,
+        }
+    }
+
+    const fn stack(self, max_stack: u8) -> Self {
+        Self { max_stack, ..self }
+    }
+
+    const fn tool(self, tool: Tool, durability: u16) -> Self {
+        Self {
+            tool,
+            durability,
+            max_stack: 1,
+            ..self
+        }
+    }
+
+    const fn armor(self, durability: u16) -> Self {
+        Self {
+            durability,
+            max_stack: 1,
+            ..self
+        }
+    }
+
+    const fn places(self, block_id: u8) -> Self {
+        Self {
+            places: Some(block_id),
+            ..self
+        }
+    }
+}
+
+const WOOD: u16 = 59;
+const STONE: u16 = 131;
+const IRON: u16 = 250;
+const DIAMOND: u16 = 1561;
+const GOLD: u16 = 32;
+
+/// All items known to Alpha 1.2.6, sorted by id.
+#[rustfmt::skip]
+pub static ITEMS: &[Item] = &[
+    Item::new(256, "iron_shovel").tool(Tool::Shovel, IRON),
+    Item::new(257, "iron_pickaxe").tool(Tool::Pickaxe, IRON),
+    Item::new(258, "iron_axe").tool(Tool::Axe, IRON),
+    Item::new(259, "flint_and_steel").tool(Tool::None, 64),
+    Item::new(260, "apple").stack(1),
+    Item::new(261, "bow").stack(1),
+    Item::new(262, "arrow"),
+    Item::new(263, "coal"),
+    Item::new(264, "diamond"),
+    Item::new(265, "iron_ingot"),
+    Item::new(266, "gold_ingot"),
+    Item::new(267, "iron_sword").tool(Tool::Sword, IRON),
+    Item::new(268, "wooden_sword").tool(Tool::Sword, WOOD),
+    Item::new(269, "wooden_shovel").tool(Tool::Shovel, WOOD),
+    Item::new(270, "wooden_pickaxe").tool(Tool::Pickaxe, WOOD),
+    Item::new(271, "wooden_axe").tool(Tool::Axe, WOOD),
+    Item::new(272, "stone_sword").tool(Tool::Sword, STONE),
+    Item::new(273, "stone_shovel").tool(Tool::Shovel, STONE),
+    Item::new(274, "stone_pickaxe").tool(Tool::Pickaxe, STONE),
+    Item::new(275, "stone_axe").tool(Tool::Axe, STONE),
+    Item::new(276, "diamond_sword").tool(Tool::Sword, DIAMOND),
+    Item::new(277, "diamond_shovel").tool(Tool::Shovel, DIAMOND),
+    Item::new(278, "diamond_pickaxe").tool(Tool::Pickaxe, DIAMOND),
+    Item::new(279, "diamond_axe").tool(Tool::Axe, DIAMOND),
+    Item::new(280, "stick"),
+    Item::new(281, "bowl"),
+    Item::new(282, "mushroom_stew").stack(1),
+    Item::new(283, "golden_sword").tool(Tool::Sword, GOLD),
+    Item::new(284, "golden_shovel").tool(Tool::Shovel, GOLD),
+    Item::new(285, "golden_pickaxe").tool(Tool::Pickaxe, GOLD),
+    Item::new(286, "golden_axe").tool(Tool::Axe, GOLD),
+    Item::new(287, "string"),
+    Item::new(288, "feather"),
+    Item::new(289, "gunpowder"),
+    Item::new(290, "wooden_hoe").tool(Tool::Hoe, WOOD),
+    Item::new(291, "stone_hoe").tool(Tool::Hoe, STONE),
+    Item::new(292, "iron_hoe").tool(Tool::Hoe, IRON),
+    Item::new(293, "diamond_hoe").tool(Tool::Hoe, DIAMOND),
+    Item::new(294, "golden_hoe").tool(Tool::Hoe, GOLD),
+    Item::new(295, "seeds").places(59),
+    Item::new(296, "wheat"),
+    Item::new(297, "bread").stack(1),
+    Item::new(298, "leather_helmet").armor(33),
+    Item::new(299, "leather_chestplate").armor(48),
+    Item::new(300, "leather_leggings").armor(45),
+    Item::new(301, "leather_boots").armor(39),
+    Item::new(302, "chainmail_helmet").armor(66),
+    Item::new(303, "chainmail_chestplate").armor(96),
+    Item::new(304, "chainmail_leggings").armor(90),
+    Item::new(305, "chainmail_boots").armor(78),
+    Item::new(306, "iron_helmet").armor(132),
+    Item::new(307, "iron_chestplate").armor(192),
+    Item::new(308, "iron_leggings").armor(180),
+    Item::new(309, "iron_boots").armor(156),
+    Item::new(310, "diamond_helmet").armor(264),
+    Item::new(311, "diamond_chestplate").armor(384),
+    Item::new(312, "diamond_leggings").armor(360),
+    Item::new(313, "diamond_boots").armor(312),
+    Item::new(314, "golden_helmet").armor(528),
+    Item::new(315, "golden_chestplate").armor(768),
+    Item::new(316, "golden_leggings").armor(720),
+    Item::new(317, "golden_boots").armor(624),
+    Item::new(318, "flint"),
+    Item::new(319, "porkchop").stack(1),
+    Item::new(320, "cooked_porkchop").stack(1),
+    Item::new(321, "painting"),
+    Item::new(322, "golden_apple").stack(1),
+    Item::new(323, "sign").stack(1).places(63),
+    Item::new(324, "wooden_door").stack(1).places(64),
+    Item::new(325, "bucket").stack(1),
+    Item::new(326, "water_bucket").stack(1),
+    Item::new(327, "lava_bucket").stack(1),
+    Item::new(328, "minecart").stack(1),
+    Item::new(329, "saddle").stack(1),
+    Item::new(330, "iron_door").stack(1).places(71),
+    Item::new(331, "redstone").places(55),
+    Item::new(332, "snowball").stack(16),
+    Item::new(333, "boat").stack(1),
+    Item::new(334, "leather"),
+    Item::new(335, "milk_bucket").stack(1),
+    Item::new(336, "brick"),
+    Item::new(337, "clay_ball"),
+    Item::new(338, "reeds").places(83),
+    Item::new(339, "paper"),
+    Item::new(340, "book"),
+    Item::new(341, "slime_ball"),
+    Item::new(342, "chest_minecart").stack(1),
+    Item::new(343, "furnace_minecart").stack(1),
+    Item::new(344, "egg").stack(16),
+    Item::new(345, "compass").stack(1),
+    Item::new(346, "fishing_rod").tool(Tool::None, 64),
+    Item::new(347, "clock").stack(1),
+    Item::new(348, "glowstone_dust"),
+    Item::new(349, "fish").stack(1),
+    Item::new(350, "cooked_fish").stack(1),
+    Item::new(2256, "record_13").stack(1),
+    Item::new(2257, "record_cat").stack(1),
+];
+
+/// Returns the item registered under `id` or `None` if the id is unknown.
+pub fn get(id: u16) -> Option<&'static Item> {
+    ITEMS
+        .binary_search_by_key(&id, |i| i.id)
+        .ok()
+        .map(|index| &ITEMS[index])
+}
+
+/// Looks an item up by its name, e.g. `"iron_pickaxe"`.
+pub fn by_name(name: &str) -> Option<&'static Item> {
+    ITEMS.iter().find(|i| i.name.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,385 @@
+use crate::world::storage::ChunkStorage;
+use crate::world::Chunk;
+use log::warn;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+pub mod palette;
+
+/// Number of chunks along each side of a map tile.
+pub const TILE_CHUNKS: i32 = 32;
+/// Width and height of a map tile in pixels.
+pub const TILE_SIZE: u32 = TILE_CHUNKS as u32 * 16;
+
+const STATE_FILE: &str = "render-state.txt";
+const STITCHED_FILE: &str = "map.png";
+
+/// The top-down view of a single chunk as 16x16 RGBA pixels, indexed by `(x + z * 16) * 4`.
+pub type ChunkImage = [u8; 16 * 16 * 4];
+
+/// Renders the top-down view of `chunk`.
+pub fn render_chunk(chunk: &Chunk) -> ChunkImage {
+    let mut image = [0u8; 16 * 16 * 4];
+    let blocks = chunk.blocks();
+    for z in 0..16usize {
+        for x in 0..16usize {
+            let column = x * 128 * 16 + z * 128;
+            // Blocks that let light through, like glass or torches, can be above the height map.
+            let top = (0..128)
+                .rev()
+                .find_map(|y| palette::color(blocks[column + y]).map(|color| (y, color)));
+            if let Some((y, color)) = top {
+                let pixel = (x + z * 16) * 4;
+                image[pixel..pixel + 3].copy_from_slice(&shade(color, y));
+                image[pixel + 3] = 0xFF;
+            }
+        }
+    }
+    image
+}
+
+/// Darkens blocks below sea level and brightens blocks above it.
+fn shade(color: [u8; 3], y: usize) -> [u8; 3] {
+    let factor = (1.0 + (y as f32 - 64.0) / 160.0).clamp(0.55, 1.25);
+    color.map(|c| (c as f32 * factor).min(255.0) as u8)
+}
+
+/// Returns the coordinates of the tile that contains the chunk at `chunk_x` and `chunk_z`.
+pub fn tile_of(chunk_x: i32, chunk_z: i32) -> (i32, i32) {
+    (
+        chunk_x.div_euclid(TILE_CHUNKS),
+        chunk_z.div_euclid(TILE_CHUNKS),
+    )
+}
+
+/// File name of the tile at `tile_x` and `tile_z`.
+pub fn tile_file_name(tile_x: i32, tile_z: i32) -> String {
+    format!("tile.{tile_x}.{tile_z}.png")
+}
+
+/// An RGBA image, fully transparent where nothing was drawn.
+#[derive(Clone)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Draws `chunk` with its north-west corner at the pixel `x` and `z`, `None` clears the area.
+    pub fn draw_chunk(&mut self, x: u32, z: u32, chunk: Option<&ChunkImage>) {
+        for row in 0..16usize {
+            let start = ((z as usize + row) * self.width as usize + x as usize) * 4;
+            let target = &mut self.pixels[start..start + 16 * 4];
+            match chunk {
+                Some(chunk) => target.copy_from_slice(&chunk[row * 16 * 4..(row + 1) * 16 * 4]),
+                None => target.fill(0),
+            }
+        }
+    }
+
+    pub fn encode_png(&self) -> std::io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(bytes)
+    }
+
+    /// Decodes a PNG written by [`Image::encode_png`], other colour types are refused.
+    pub fn decode_png(bytes: &[u8]) -> std::io::Result<Self> {
+        let mut reader = png::Decoder::new(bytes).read_info()?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels)?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Image is not 8 bit RGBA!",
+            ));
+        }
+        pixels.truncate(info.buffer_size());
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        Self::decode_png(&std::fs::read(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.encode_png()?)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Layout {
+    /// Square tiles of [`TILE_CHUNKS`] chunks, named by [`tile_file_name`].
+    Tiles,
+    /// A single image covering every chunk of the world.
+    Stitched,
+}
+
+/// What a call to [`render_world`] did.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct RenderStats {
+    /// Chunks that were rendered.
+    pub rendered: usize,
+    /// Chunks that were unchanged since the last run.
+    pub unchanged: usize,
+    /// Chunks that could not be read.
+    pub failed: usize,
+    /// Images that were written.
+    pub images: usize,
+}
+
+/// Renders the chunks of `storage` into the directory `output`.
+///
+/// With `incremental` set, only chunks written since the last run into `output` are rendered and
+/// patched into the existing images. The run falls back to a full render if there is no usable previous state.
+pub fn render_world(
+    storage: &dyn ChunkStorage,
+    output: &Path,
+    layout: Layout,
+    incremental: bool,
+) -> std::io::Result<RenderStats> {
+    std::fs::create_dir_all(output)?;
+    let chunks = storage.list_chunks()?;
+    let timestamps = storage.chunk_timestamps()?;
+
+    let header = match layout {
+        Layout::Tiles => "tiles".to_string(),
+        Layout::Stitched => {
+            let (min, max) = bounds(&chunks);
+            format!("stitched {} {} {} {}", min.0, min.1, max.0, max.1)
+        }
+    };
+    let previous = incremental
+        .then(|| RenderState::load(&output.join(STATE_FILE)))
+        .flatten()
+        .filter(|state| state.header == header);
+    let full = previous.is_none();
+    let previous = previous.map(|state| state.timestamps).unwrap_or_default();
+    // Timestamps can be as coarse as a second, so chunks written as late as the newest one of the last
+    // run may have been written again after it without their timestamp changing.
+    let newest = previous.values().max().copied();
+
+    let changed = chunks
+        .iter()
+        .filter(|key| {
+            let timestamp = timestamps.get(*key);
+            full || previous.get(*key) != timestamp || timestamp >= newest.as_ref()
+        })
+        .copied()
+        .collect::<Vec<_>>();
+    let present = chunks.iter().copied().collect::<HashSet<_>>();
+    let removed = previous
+        .keys()
+        .filter(|key| !present.contains(*key))
+        .copied();
+
+    let rendered = changed
+        .par_iter()
+        .map(|(x, z)| match Chunk::load(storage, *x, *z) {
+            Ok(chunk) => ((*x, *z), Ok(render_chunk(&chunk))),
+            Err(err) => {
+                warn!("Could not render chunk at (x: {x}, z: {z}): {err}");
+                ((*x, *z), Err(()))
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut stats = RenderStats {
+        rendered: rendered.iter().filter(|(_, r)| r.is_ok()).count(),
+        unchanged: chunks.len() - changed.len(),
+        failed: rendered.iter().filter(|(_, r)| r.is_err()).count(),
+        images: 0,
+    };
+    // Failed chunks are left out of the state so the next run tries them again.
+    let failed = rendered
+        .iter()
+        .filter(|(_, r)| r.is_err())
+        .map(|(key, _)| *key)
+        .collect::<HashSet<_>>();
+    let updates = rendered
+        .into_iter()
+        .filter_map(|(key, r)| r.ok().map(|image| (key, Some(image))))
+        .chain(removed.map(|key| (key, None)))
+        .collect::<Vec<_>>();
+
+    match layout {
+        Layout::Tiles => {
+            let mut tiles = HashMap::<_, Vec<_>>::new();
+            for update in &updates {
+                tiles
+                    .entry(tile_of(update.0 .0, update.0 .1))
+                    .or_default()
+                    .push(update);
+            }
+            stats.images = tiles.len();
+            tiles
+                .par_iter()
+                .try_for_each(|((tile_x, tile_z), updates)| {
+                    let path = output.join(tile_file_name(*tile_x, *tile_z));
+                    let mut image = open_or_new(&path, full, TILE_SIZE, TILE_SIZE);
+                    for ((x, z), chunk) in updates.iter() {
+                        let (x, z) = (x.rem_euclid(TILE_CHUNKS), z.rem_euclid(TILE_CHUNKS));
+                        image.draw_chunk(x as u32 * 16, z as u32 * 16, chunk.as_ref());
+                    }
+                    image.save(&path)
+                })?;
+        }
+        Layout::Stitched if !updates.is_empty() => {
+            let (min, max) = bounds(&chunks);
+            let (width, height) = (
+                (max.0 - min.0 + 1) as u32 * 16,
+                (max.1 - min.1 + 1) as u32 * 16,
+            );
+            let path = output.join(STITCHED_FILE);
+            let mut image = open_or_new(&path, full, width, height);
+            for ((x, z), chunk) in &updates {
+                // Removed chunks outside of the current bounds are already gone.
+                if (min.0..=max.0).contains(x) && (min.1..=max.1).contains(z) {
+                    let (x, z) = ((x - min.0) as u32 * 16, (z - min.1) as u32 * 16);
+                    image.draw_chunk(x, z, chunk.as_ref());
+                }
+            }
+            image.save(&path)?;
+            stats.images = 1;
+        }
+        Layout::Stitched => {}
+    }
+
+    let state = RenderState {
+        header,
+        timestamps: timestamps
+            .into_iter()
+            .filter(|(key, _)| !failed.contains(key))
+            .collect(),
+    };
+    state.save(&output.join(STATE_FILE))?;
+    Ok(stats)
+}
+
+/// Returns the smallest and the largest chunk coordinates of `chunks`.
+fn bounds(chunks: &[(i32, i32)]) -> ((i32, i32), (i32, i32)) {
+    let min_x = chunks.iter().map(|(x, _)| *x).min().unwrap_or_default();
+    let min_z = chunks.iter().map(|(_, z)| *z).min().unwrap_or_default();
+    let max_x = chunks.iter().map(|(x, _)| *x).max().unwrap_or_default();
+    let max_z = chunks.iter().map(|(_, z)| *z).max().unwrap_or_default();
+    ((min_x, min_z), (max_x, max_z))
+}
+
+/// Opens the image at `path` to patch it, or starts from scratch if it has to be redrawn or does not fit.
+fn open_or_new(path: &Path, full: bool, width: u32, height: u32) -> Image {
+    if !full {
+        match Image::open(path) {
+            Ok(image) if image.width == width && image.height == height => return image,
+            Ok(_) => warn!("{path:?} has the wrong size and is redrawn."),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => warn!("{path:?} can not be read and is redrawn: {err}"),
+        }
+    }
+    Image::new(width, height)
+}
+
+/// Chunk timestamps of the last run, which is what incremental renders compare against.
+struct RenderState {
+    /// Describes the layout of the last run, a different layout needs a full render.
+    header: String,
+    timestamps: HashMap<(i32, i32), u64>,
+}
+
+impl RenderState {
+    fn load(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        let mut lines = content.lines();
+        let header = lines.next()?.to_string();
+        let mut timestamps = HashMap::new();
+        for line in lines {
+            let parts = line.split(' ').collect::<Vec<_>>();
+            let [x, z, timestamp] = parts.as_slice() else {
+                return None;
+            };
+            timestamps.insert((x.parse().ok()?, z.parse().ok()?), timestamp.parse().ok()?);
+        }
+        Some(Self { header, timestamps })
+    }
+
+    fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut content = self.header.clone();
+        for ((x, z), timestamp) in &self.timestamps {
+            content.push_str(&format!("\n{x} {z} {timestamp}"));
+        }
+        std::fs::write(path, content)
+    }
+}
+
+#[test]
+fn test_render_chunk() {
+    use crate::registry;
+
+    let mut chunk = Chunk::empty(0, 0);
+    chunk.set_block(0, 60, 0, 1);
+    chunk.set_block(0, 61, 0, 37);
+    chunk.set_block(5, 64, 3, 2);
+    chunk.set_block(5, 70, 3, registry::block::AIR);
+    let image = render_chunk(&chunk);
+
+    let pixel = |x: usize, z: usize| &image[(x + z * 16) * 4..(x + z * 16) * 4 + 4];
+    let dandelion = palette::color(37).unwrap();
+    assert_eq!(pixel(0, 0)[3], 0xFF);
+    assert_eq!(pixel(0, 0)[..3], shade(dandelion, 61));
+    assert_eq!(pixel(5, 3)[..3], palette::color(2).unwrap());
+    assert_eq!(pixel(1, 0), [0, 0, 0, 0]);
+
+    let mut tile = Image::new(32, 16);
+    tile.draw_chunk(16, 0, Some(&image));
+    let decoded = Image::decode_png(&tile.encode_png().unwrap()).unwrap();
+    assert_eq!(decoded.pixels, tile.pixels);
+    assert_eq!(tile_of(-1, 32), (-1, 1));
+}
+
+#[test]
+fn test_incremental_render() {
+    use crate::world::region::RegionStorage;
+
+    let path = std::env::temp_dir().join(format!("betalpha-render-{}", std::process::id()));
+    let storage = RegionStorage::new(path.join("region"));
+    let output = path.join("map");
+    let mut chunk = Chunk::empty(0, 0);
+    chunk.save(&storage).unwrap();
+    Chunk::empty(1, 0).save(&storage).unwrap();
+    let stats = render_world(&storage, &output, Layout::Tiles, true).unwrap();
+    assert_eq!(stats.rendered, 2);
+
+    // Region timestamps are seconds, so the chunk is most likely written again within the same one.
+    chunk.set_block(0, 64, 0, 1);
+    chunk.save(&storage).unwrap();
+    let stats = render_world(&storage, &output, Layout::Tiles, true).unwrap();
+    assert!(stats.rendered >= 1);
+    let tile = Image::open(&output.join(tile_file_name(0, 0))).unwrap();
+    assert_eq!(tile.pixels[3], 0xFF);
+    std::fs::remove_dir_all(path).unwrap();
+}
@@ -0,0 +1,90 @@
+/// Map colour of every block that shows up from above, sorted by id.
+///
+/// Blocks without an entry, like air, are see-through on the map.
+#[rustfmt::skip]
+static COLORS: &[(u8, [u8; 3])] = &[
+    (1, [125, 125, 125]),  // stone
+    (2, [95, 159, 53]),    // grass
+    (3, [134, 96, 67]),    // dirt
+    (4, [110, 110, 110]),  // cobblestone
+    (5, [157, 128, 79]),   // planks
+    (6, [72, 120, 28]),    // sapling
+    (7, [84, 84, 84]),     // bedrock
+    (8, [47, 67, 244]),    // flowing_water
+    (9, [47, 67, 244]),    // water
+    (10, [217, 98, 20]),   // flowing_lava
+    (11, [217, 98, 20]),   // lava
+    (12, [219, 211, 160]), // sand
+    (13, [136, 126, 126]), // gravel
+    (14, [143, 140, 125]), // gold_ore
+    (15, [136, 130, 127]), // iron_ore
+    (16, [115, 115, 115]), // coal_ore
+    (17, [102, 81, 51]),   // log
+    (18, [56, 95, 31]),    // leaves
+    (19, [195, 195, 78]),  // sponge
+    (20, [218, 240, 244]), // glass
+    (35, [222, 222, 222]), // wool
+    (37, [241, 249, 2]),   // dandelion
+    (38, [242, 7, 11]),    // rose
+    (39, [145, 109, 85]),  // brown_mushroom
+    (40, [226, 18, 18]),   // red_mushroom
+    (41, [249, 236, 78]),  // gold_block
+    (42, [219, 219, 219]), // iron_block
+    (43, [159, 159, 159]), // double_slab
+    (44, [159, 159, 159]), // slab
+    (45, [146, 99, 86]),   // bricks
+    (46, [219, 68, 26]),   // tnt
+    (47, [157, 128, 79]),  // bookshelf
+    (48, [90, 108, 90]),   // mossy_cobblestone
+    (49, [20, 18, 29]),    // obsidian
+    (50, [255, 214, 0]),   // torch
+    (51, [224, 174, 21]),  // fire
+    (52, [27, 42, 53]),    // mob_spawner
+    (53, [157, 128, 79]),  // wooden_stairs
+    (54, [164, 116, 42]),  // chest
+    (55, [150, 0, 0]),     // redstone_wire
+    (56, [129, 140, 143]), // diamond_ore
+    (57, [97, 219, 213]),  // diamond_block
+    (58, [107, 71, 42]),   // workbench
+    (59, [146, 192, 0]),   // crops
+    (60, [95, 58, 30]),    // farmland
+    (61, [96, 96, 96]),    // furnace
+    (62, [96, 96, 96]),    // lit_furnace
+    (63, [157, 128, 79]),  // sign_post
+    (64, [145, 109, 56]),  // wooden_door
+    (65, [142, 115, 60]),  // ladder
+    (66, [164, 164, 164]), // rail
+    (67, [110, 110, 110]), // cobblestone_stairs
+    (68, [157, 128, 79]),  // wall_sign
+    (69, [117, 94, 59]),   // lever
+    (70, [125, 125, 125]), // stone_pressure_plate
+    (71, [194, 194, 194]), // iron_door
+    (72, [157, 128, 79]),  // wooden_pressure_plate
+    (73, [132, 107, 107]), // redstone_ore
+    (74, [132, 107, 107]), // lit_redstone_ore
+    (75, [102, 0, 0]),     // unlit_redstone_torch
+    (76, [253, 0, 0]),     // redstone_torch
+    (77, [125, 125, 125]), // stone_button
+    (78, [240, 251, 251]), // snow_layer
+    (79, [125, 173, 255]), // ice
+    (80, [240, 251, 251]), // snow
+    (81, [13, 120, 23]),   // cactus
+    (82, [159, 164, 177]), // clay
+    (83, [148, 192, 101]), // reeds
+    (84, [107, 73, 55]),   // jukebox
+    (85, [157, 128, 79]),  // fence
+    (86, [227, 144, 29]),  // pumpkin
+    (87, [111, 54, 52]),   // netherrack
+    (88, [85, 67, 54]),    // soul_sand
+    (89, [249, 212, 156]), // glowstone
+    (90, [87, 10, 191]),   // portal
+    (91, [227, 144, 29]),  // lit_pumpkin
+];
+
+/// Returns the map colour of the block with id `block_id` or `None` if it should not be drawn.
+pub fn color(block_id: u8) -> Option<[u8; 3]> {
+    COLORS
+        .binary_search_by_key(&block_id, |(id, _)| *id)
+        .ok()
+        .map(|index| COLORS[index].1)
+}
@@ -1,14 +1,18 @@
-use crate::entity::{connection_state, Digging, PreviousPosition};
-use crate::entity::{Look, Named, PlayerChunkDB, PlayerEntityDB, Position, Velocity};
+use crate::config::Config;
+use crate::entity::{connection_state, ChunkQueue, Digging, Holding, ViewDistance};
+use crate::entity::{EntityKind, Look, Named, NetworkId, PlayerChunkDB, Position, Velocity};
 use crate::event::{
-    AnimationEvent, BlockChangeEvent, PlayerBlockPlacementEvent, PlayerDiggingEvent,
-    PlayerPositionAndLookEvent, PlayerUseEvent, SendPacketEvent,
+    AnimationEvent, BlockChangeEvent, ChunkUpdateEvent, Face, PlayerBlockPlacementEvent,
+    PlayerDiggingEvent, PlayerPositionAndLookEvent, PlayerUseEvent, SendPacketEvent,
 };
 use crate::packet::{ids, to_client_packets, to_server_packets, PacketError};
 use crate::packet::{Deserialize, Serialize};
-use crate::world::{Chunk, World};
-use crate::{event, packet, util, TcpWrapper, BUFFER_SIZE};
-use bevy::prelude::{Commands, Entity, EventReader, EventWriter, Mut, Query, Res, ResMut, With};
+use crate::permission::{self, Permissions};
+use crate::world::{tile_entity_position, ChunkSection, PlayerData, TileEntity, World};
+use crate::{edit, event, packet, registry, TcpWrapper, BUFFER_SIZE};
+use bevy::prelude::{
+    Commands, Entity, EventReader, EventWriter, Local, Mut, Query, Res, ResMut, Resource, With,
+};
 use bevy::utils::tracing::Instrument;
 use bytes::{Buf, BufMut, BytesMut};
 use log::{debug, error, info, warn};
@@ -16,7 +20,7 @@ use std::collections::HashMap;
 use std::io::{BufReader, Cursor, ErrorKind, Read, Write};
 use std::net::TcpStream;
 use std::process::Command;
-use std::sync::{Arc, RwLock, RwLockWriteGuard};
+use std::time::{Duration, Instant};
 
 pub fn keep_alive(
     mut packet_event_emitter: EventWriter<SendPacketEvent>,
@@ -30,13 +34,18 @@ pub fn keep_alive(
 
 pub fn disconnecting(
     mut packet_event_emitter: EventWriter<SendPacketEvent>,
-    (mut query, mut other): (
-        Query<(Entity, &connection_state::Disconnecting)>,
-        Query<(Entity, &PlayerEntityDB), With<connection_state::Playing>>,
-    ),
+    world: Res<World>,
+    mut query: Query<(
+        Entity,
+        &connection_state::Disconnecting,
+        Option<(&Named, &Position, &Look)>,
+    )>,
     mut commands: Commands,
 ) {
-    for (entity, state) in &mut query {
+    for (entity, state, player) in &mut query {
+        if let Some((name, position, look)) = player {
+            save_player(&world, name, position, look);
+        }
         packet_event_emitter.send(
             SendPacketEvent::new(
                 entity,
@@ -48,24 +57,45 @@ pub fn disconnecting(
         );
         commands
             .entity(entity)
-            .remove::<connection_state::Disconnecting>()
+            .remove::<(connection_state::Disconnecting, EntityKind)>()
             .insert(connection_state::Invalid);
-        // Delete player for other players
-        for (other, db) in &mut other {
-            let mut list: RwLockWriteGuard<Vec<u32>> = db.visible_entities.write().unwrap();
-            if let Some(index) = list.iter().position(|p| *p == entity.index()) {
-                list.swap_remove(index);
-            }
-            packet_event_emitter.send(
-                SendPacketEvent::new(
-                    other,
-                    to_client_packets::DestroyEntityPacket {
-                        entity_id: entity.index(),
-                    },
-                )
-                .unwrap(),
-            );
-        }
+    }
+}
+
+pub fn save_player(world: &World, name: &Named, position: &Position, look: &Look) {
+    let data = PlayerData {
+        position: [position.x, position.y, position.z],
+        rotation: [look.yaw, look.pitch],
+        on_ground: position.on_ground,
+    };
+    if let Err(err) = world.save_player(&name.name, &data) {
+        error!("Failed to save player {}: {err}", name.name);
+    }
+}
+
+/// Saves the loaded chunks, the level and the players in the world every `autosave-interval` seconds.
+pub fn autosave(
+    config: Res<Config>,
+    mut world: ResMut<World>,
+    mut last_save: Local<Option<Instant>>,
+    query: Query<(&Named, &Position, &Look), With<connection_state::Playing>>,
+) {
+    let interval = config.settings.autosave_interval;
+    let last_save = last_save.get_or_insert_with(Instant::now);
+    if interval == 0 || last_save.elapsed() < Duration::from_secs(interval) {
+        return;
+    }
+    *last_save = Instant::now();
+    for (name, position, look) in &query {
+        save_player(&world, name, position, look);
+    }
+    match world.save() {
+        Ok(()) => info!(
+            "Saved {} chunks and {} players.",
+            world.loaded_chunks(),
+            query.iter().len()
+        ),
+        Err(err) => error!("Failed to save the world: {err}"),
     }
 }
 
@@ -115,103 +145,12 @@ pub fn system_message(
     }
 }
 
-pub fn calculate_visible_players(
-    mut packet_event_emitter: EventWriter<SendPacketEvent>,
-    (mut query_entities, mut other): (
-        Query<(Entity, &mut PlayerEntityDB, &PlayerChunkDB), With<connection_state::Playing>>,
-        Query<(Entity, &Position, &Look, &Named), With<connection_state::Playing>>,
-    ),
-    mut commands: Commands,
-) {
-    for (entity, player_db, chunk_db) in &mut query_entities {
-        let mut list: RwLockWriteGuard<Vec<u32>> = player_db.visible_entities.write().unwrap();
-        let chunks: &HashMap<(i32, i32), Arc<RwLock<Chunk>>> = &chunk_db.chunks;
-        for (other, other_position, other_look, other_name_component) in &mut other {
-            let other: Entity = other;
-            let Position { x, z, .. } = &other_position;
-            if entity.index() == other.index() {
-                continue;
-            }
-            let is_inside_visible_chunks = chunks
-                .values()
-                .map(|c| {
-                    let c = c.read().unwrap();
-                    if c.is_inside_chunk(x.round() as i32, z.round() as i32) {
-                        1
-                    } else {
-                        0
-                    }
-                })
-                .sum::<usize>()
-                > 0;
-            match (is_inside_visible_chunks, list.contains(&other.index())) {
-                (true, false) => {
-                    list.push(other.index());
-                    packet_event_emitter.send(
-                        SendPacketEvent::new(
-                            entity,
-                            to_client_packets::EntityPacket {
-                                entity_id: other.index(),
-                            },
-                        )
-                        .unwrap(),
-                    );
-                    let (rotation, pitch) = util::pack_float_pair(other_look.yaw, other_look.pitch);
-                    packet_event_emitter.send(
-                        SendPacketEvent::new(
-                            entity,
-                            to_client_packets::NamedEntitySpawnPacket {
-                                entity_id: other.index(),
-                                name: other_name_component.name.clone(),
-                                x: (other_position.x * 32.0).round() as i32,
-                                y: (other_position.y * 32.0).round() as i32,
-                                z: (other_position.z * 32.0).round() as i32,
-                                rotation,
-                                pitch,
-                                current_item: 0,
-                            },
-                        )
-                        .unwrap(),
-                    );
-                    debug!(
-                        "Sent spawn entity: {} to entity: {}",
-                        other.index(),
-                        entity.index()
-                    );
-                }
-                (false, true) => {
-                    let index = list.iter().position(|p| *p == other.index()).unwrap();
-                    list.swap_remove(index);
-                    packet_event_emitter.send(
-                        SendPacketEvent::new(
-                            entity,
-                            to_client_packets::DestroyEntityPacket {
-                                entity_id: other.index(),
-                            },
-                        )
-                        .unwrap(),
-                    );
-                    debug!(
-                        "Sent delete entity: {} to entity: {}",
-                        other.index(),
-                        entity.index()
-                    );
-                }
-                (_, _) => {}
-            }
-        }
-    }
-}
-
 pub fn player_movement(
     mut event_collector: EventReader<PlayerPositionAndLookEvent>,
-    mut query: Query<
-        (Entity, &mut Position, &mut PreviousPosition, &mut Look),
-        With<connection_state::Playing>,
-    >,
+    mut query: Query<(Entity, &mut Position, &mut Look), With<connection_state::Playing>>,
 ) {
     let events = event_collector.read().collect::<Vec<_>>();
-    for (entity, mut position, mut prev, mut look) in &mut query {
+    for (entity, mut position, mut look) in &mut query {
         for event in events.clone() {
             match event {
                 PlayerPositionAndLookEvent::PositionAndLook {
@@ -224,10 +163,6 @@ pub fn player_movement(
                     pitch,
                 } => {
                     if entity.index() == *entity_id {
-                        prev.x = position.x;
-                        prev.y = position.y;
-                        prev.z = position.z;
-                        prev.stance = position.stance;
                         position.x = *x;
                         position.y = *y;
                         position.z = *z;
@@ -244,10 +179,6 @@ pub fn player_movement(
                     stance,
                 } => {
                     if entity.index() == *entity_id {
-                        prev.x = position.x;
-                        prev.y = position.y;
-                        prev.z = position.z;
-                        prev.stance = position.stance;
                         position.x = *x;
                         position.y = *y;
                         position.z = *z;
@@ -269,93 +200,30 @@ pub fn player_movement(
     }
 }
 
-pub fn move_player(
-    mut packet_event_emitter: EventWriter<SendPacketEvent>,
-    (mut query, mut other): (
-        Query<Entity, With<connection_state::Playing>>,
-        Query<(Entity, &Position, &PreviousPosition, &Look), With<connection_state::Playing>>,
-    ),
-) {
-    for entity in &mut query {
-        for (other, position, prev_position, look) in &mut other {
-            if entity.index() == other.index() {
-                continue;
-            }
-
-            let (yaw, pitch) = crate::util::pack_float_pair(look.yaw, look.pitch);
-            if prev_position.distance_moved(&position) < 4.0 {
-                let (x, y, z) = prev_position.relative_movement(&position);
-                packet_event_emitter.send(
-                    SendPacketEvent::new(
-                        entity,
-                        to_client_packets::EntityLookRelativeMovePacket {
-                            entity_id: other.index(),
-                            x,
-                            y,
-                            z,
-                            yaw,
-                            pitch,
-                        },
-                    )
-                    .unwrap(),
-                );
-            } else {
-                packet_event_emitter.send(
-                    SendPacketEvent::new(
-                        entity,
-                        to_client_packets::EntityTeleportPacket {
-                            entity_id: other.index(),
-                            x: (position.x * 32.0).round() as i32,
-                            y: (position.y * 32.0).round() as i32,
-                            z: (position.z * 32.0).round() as i32,
-                            yaw,
-                            pitch,
-                        },
-                    )
-                    .unwrap(),
-                );
-            }
-        }
-    }
-}
-
-pub fn correct_player_position(
-    mut packet_event_emitter: EventWriter<SendPacketEvent>,
-    (mut query, mut other): (
-        Query<Entity, With<connection_state::Playing>>,
-        Query<(Entity, &Position, &Look), With<connection_state::Playing>>,
-    ),
-) {
-    for entity in &mut query {
-        for (other, position, look) in &mut other {
-            if entity.index() == other.index() {
-                continue;
-            }
-
-            let (yaw, pitch) = crate::util::pack_float_pair(look.yaw, look.pitch);
-
-            packet_event_emitter.send(
-                SendPacketEvent::new(
-                    entity,
-                    to_client_packets::EntityTeleportPacket {
-                        entity_id: other.index(),
-                        x: (position.x * 32.0).round() as i32,
-                        y: (position.y * 32.0).round() as i32,
-                        z: (position.z * 32.0).round() as i32,
-                        yaw,
-                        pitch,
-                    },
-                )
-                .unwrap(),
-            );
-        }
-    }
+/// Whether a player may change the block at `x` and `z`, close to the spawn only some players may.
+fn may_build(
+    permissions: &Permissions,
+    config: &Config,
+    world: &World,
+    player: &Named,
+    x: i32,
+    z: i32,
+) -> bool {
+    let [spawn_x, _, spawn_z] = world.get_spawn();
+    let protected = (x - spawn_x).abs().max((z - spawn_z).abs()) < config.settings.spawn_protection;
+    permissions.has(&player.name, permission::BUILD)
+        && (!protected || permissions.has(&player.name, permission::BUILD_SPAWN))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn digging(
+    mut packet_event_emitter: EventWriter<SendPacketEvent>,
     mut event_collector: EventReader<PlayerDiggingEvent>,
     mut event_emitter: EventWriter<BlockChangeEvent>,
-    mut query: Query<(Entity, &Digging), With<Digging>>,
+    mut world: ResMut<World>,
+    permissions: Res<Permissions>,
+    config: Res<Config>,
+    mut query: Query<(Entity, &Digging, &Named, Option<&Holding>), With<Digging>>,
     mut commands: Commands,
 ) {
     for event in event_collector.read() {
@@ -380,17 +248,52 @@ pub fn digging(
                 commands.entity(*entity).remove::<Digging>();
             }
             PlayerDiggingEvent::Completed { entity } => {
-                for (player, digging) in &mut query {
+                for (player, digging, named, holding) in &mut query {
                     if player.index() != entity.index() {
                         continue;
                     }
-                    event_emitter.send(BlockChangeEvent {
-                        x: digging.x,
-                        y: digging.y,
-                        z: digging.z,
-                        ty: 0,
-                        metadata: 0,
-                    });
+                    // The wand selects blocks instead of breaking them.
+                    if holding.is_some_and(|holding| holding.item_id == edit::WAND)
+                        || !may_build(&permissions, &config, &world, named, digging.x, digging.z)
+                    {
+                        revert_block(
+                            &mut packet_event_emitter,
+                            &mut world,
+                            player,
+                            digging.x,
+                            digging.y,
+                            digging.z,
+                        );
+                        continue;
+                    }
+                    let block = world
+                        .get_block(digging.x, digging.y as i32, digging.z)
+                        .and_then(registry::block::get);
+                    match block {
+                        Some(block) if !block.is_unbreakable() => {
+                            event_emitter.send(BlockChangeEvent {
+                                x: digging.x,
+                                y: digging.y,
+                                z: digging.z,
+                                ty: registry::block::AIR,
+                                metadata: 0,
+                            });
+                        }
+                        _ => {
+                            debug!(
+                                "{} can not be broken!",
+                                block.map_or("Unknown block", |b| b.name)
+                            );
+                            revert_block(
+                                &mut packet_event_emitter,
+                                &mut world,
+                                player,
+                                digging.x,
+                                digging.y,
+                                digging.z,
+                            );
+                        }
+                    }
                 }
                 commands.entity(*entity).remove::<Digging>();
             }
@@ -399,63 +302,219 @@ pub fn digging(
 }
 
 pub fn placing(
+    mut packet_event_emitter: EventWriter<SendPacketEvent>,
     mut event_collector: EventReader<PlayerBlockPlacementEvent>,
     mut event_emitter: EventWriter<BlockChangeEvent>,
-    mut query: Query<Entity, With<connection_state::Playing>>,
-    mut commands: Commands,
+    mut world: ResMut<World>,
+    permissions: Res<Permissions>,
+    config: Res<Config>,
+    query: Query<&Named>,
 ) {
     for event in event_collector.read() {
-        for player in &mut query {
-            let (x, y, z) = event.direction.to_offset();
-            event_emitter.send(BlockChangeEvent {
-                x: event.x + x,
-                y: event.y + y,
-                z: event.z + z,
-                ty: event.id as u8,
+        if let Face::UNKNOWN = event.direction {
+            continue;
+        }
+        let (x, y, z) = event.direction.to_offset();
+        let (x, y, z) = (event.x + x, event.y + y, event.z + z);
+        let allowed = query
+            .get(event.entity)
+            .is_ok_and(|named| may_build(&permissions, &config, &world, named, x, z));
+        if !allowed {
+            revert_block(&mut packet_event_emitter, &mut world, event.entity, x, y, z);
+            continue;
+        }
+        match registry::placed_block(event.id) {
+            Some(block_id) => event_emitter.send(BlockChangeEvent {
+                x,
+                y,
+                z,
+                ty: block_id,
                 metadata: 0,
-            });
+            }),
+            None => {
+                debug!("Item {} can not be placed!", event.id);
+                revert_block(&mut packet_event_emitter, &mut world, event.entity, x, y, z);
+            }
         }
     }
 }
 
+/// Describes a tile entity to the client, which needs them for things like the text of signs.
+pub fn tile_entity_packet(
+    tile_entity: &TileEntity,
+) -> Option<to_client_packets::ComplexEntitiesPacket> {
+    let [x, y, z] = tile_entity_position(tile_entity)?;
+    let mut blob = nbt::Blob::new();
+    for (name, value) in tile_entity {
+        blob.insert(name.as_str(), value.clone()).ok()?;
+    }
+    let mut payload = Vec::new();
+    if let Err(err) = blob.to_gzip_writer(&mut payload) {
+        warn!("Failed to encode tile entity at (x: {x}, y: {y}, z: {z}): {err}");
+        return None;
+    }
+    Some(to_client_packets::ComplexEntitiesPacket {
+        x,
+        y: y as i16,
+        z,
+        payload_size: payload.len() as u16,
+        payload,
+    })
+}
+
+/// Resends the block stored in the world to a single player, undoing whatever the client predicted.
+fn revert_block(
+    packet_event_emitter: &mut EventWriter<SendPacketEvent>,
+    world: &mut World,
+    entity: Entity,
+    x: i32,
+    y: i8,
+    z: i32,
+) {
+    if let Some((block_id, data)) = world.get_block_and_data(x, y as i32, z) {
+        packet_event_emitter.send(
+            SendPacketEvent::new(
+                entity,
+                to_client_packets::BlockChangePacket {
+                    x,
+                    y,
+                    z,
+                    block_type: block_id as i8,
+                    block_metadata: data as i8,
+                },
+            )
+            .unwrap(),
+        );
+    }
+}
+
+/// Writes block changes into the world, they are sent to the players by `chunk_update`.
 pub fn block_change(
-    mut packet_event_emitter: EventWriter<SendPacketEvent>,
+    mut chunk_update_emitter: EventWriter<ChunkUpdateEvent>,
     mut world: ResMut<World>,
     mut event_collector: EventReader<BlockChangeEvent>,
-    mut query: Query<Entity, With<connection_state::Playing>>,
 ) {
-    let events = event_collector.read().collect::<Vec<_>>();
-    for entity in &mut query {
-        for event in events.clone() {
-            let (chunk_x, chunk_z) = (event.x >> 4, event.z >> 4);
-            if let Ok(chunk) = world.get_chunk(chunk_x, chunk_z) {
-                if let Ok(mut chunk) = chunk.write() {
-                    let old_block = chunk.set_block(
-                        (event.x & 15) as u8,
-                        event.y as u8,
-                        (event.z & 15) as u8,
-                        event.ty,
-                    );
-                    info!("Removed block from ExampleWorld: {old_block:?}");
-                } else {
-                    warn!("Cloud not obtain chunk!")
+    let mut chunks = HashMap::<_, Vec<_>>::new();
+    for event in event_collector.read() {
+        let (x, y, z) = (event.x, event.y as i32, event.z);
+        if world
+            .set_block_and_data(x, y, z, event.ty, event.metadata)
+            .is_none()
+        {
+            warn!("Could not change the block at (x: {x}, y: {y}, z: {z})!");
+            continue;
+        }
+        chunks
+            .entry((x >> 4, z >> 4))
+            .or_default()
+            .push(((x & 15) as u8, y as u8, (z & 15) as u8));
+    }
+    for ((chunk_x, chunk_z), positions) in chunks {
+        chunk_update_emitter.send(ChunkUpdateEvent {
+            chunk_x,
+            chunk_z,
+            positions,
+        });
+    }
+}
+
+/// Above this many changed blocks the part of the chunk covering them is resent instead of sending a `MultiBlockChangePacket`.
+///
+/// Every change costs four bytes, a compressed chunk is a few kilobytes.
+const MULTI_BLOCK_CHANGE_LIMIT: usize = 256;
+
+/// Sends the blocks that changed this tick to every player that has their chunk loaded, batched per chunk.
+pub fn chunk_update(
+    mut packet_event_emitter: EventWriter<SendPacketEvent>,
+    mut world: ResMut<World>,
+    mut event_collector: EventReader<ChunkUpdateEvent>,
+    query: Query<(Entity, &PlayerChunkDB), With<connection_state::Playing>>,
+) {
+    let mut chunks = HashMap::<_, Vec<_>>::new();
+    for event in event_collector.read() {
+        chunks
+            .entry((event.chunk_x, event.chunk_z))
+            .or_default()
+            .extend_from_slice(&event.positions);
+    }
+    for ((chunk_x, chunk_z), mut positions) in chunks {
+        positions.sort_unstable();
+        positions.dedup();
+        let Ok(chunk) = world.get_chunk(chunk_x, chunk_z) else {
+            continue;
+        };
+        let chunk = chunk.read().unwrap();
+        let section = (positions.len() > MULTI_BLOCK_CHANGE_LIMIT)
+            .then(|| ChunkSection::covering(positions.iter().copied()))
+            .flatten();
+        let bytes = if let Some(section) = section {
+            let (len, chunk_data) = chunk.get_compressed_section(&section);
+            let [min_x, min_y, min_z] = section.min;
+            let [max_x, max_y, max_z] = section.max;
+            to_client_packets::MapChunkPacket {
+                x: chunk_x * 16 + min_x as i32,
+                y: min_y as i16,
+                z: chunk_z * 16 + min_z as i32,
+                size_x: (max_x - min_x) as i8,
+                size_y: (max_y - min_y) as i8,
+                size_z: (max_z - min_z) as i8,
+                compressed_size: len,
+                compressed_data: chunk_data[..len as usize].to_vec(),
+            }
+            .serialize()
+        } else {
+            let (mut type_array, mut metadata_array) = (Vec::new(), Vec::new());
+            for (x, y, z) in &positions {
+                type_array.push(chunk.get_block(*x, *y, *z).unwrap_or_default());
+                metadata_array.push(chunk.get_data(*x, *y, *z).unwrap_or_default());
+            }
+            to_client_packets::MultiBlockChangePacket {
+                chunk_x,
+                chunk_y: chunk_z,
+                array_size: positions.len() as u16,
+                coordinate_array: positions
+                    .iter()
+                    .map(|(x, y, z)| ((*x as i16) << 12) | ((*z as i16) << 8) | *y as i16)
+                    .collect(),
+                type_array,
+                metadata_array,
+            }
+            .serialize()
+        };
+        let bytes = bytes.unwrap();
+        // A resent section arrives without its tile entities, otherwise only the changed ones are sent.
+        let changed = |[x, y, z]: [i32; 3]| {
+            let local = [(x & 15) as u8, y as u8, (z & 15) as u8];
+            match section {
+                Some(ChunkSection { min, max }) => {
+                    (0..3).all(|a| (min[a]..=max[a]).contains(&local[a]))
                 }
-            } else {
-                warn!("Chunk is unable to load!")
+                None => positions
+                    .binary_search(&(local[0], local[1], local[2]))
+                    .is_ok(),
             }
-            packet_event_emitter.send(
-                SendPacketEvent::new(
+        };
+        let tile_entities = chunk
+            .tile_entities()
+            .iter()
+            .filter(|tile_entity| tile_entity_position(tile_entity).is_some_and(changed))
+            .filter_map(|tile_entity| tile_entity_packet(tile_entity)?.serialize().ok())
+            .collect::<Vec<_>>();
+        for (entity, chunk_db) in &query {
+            if chunk_db.chunks.contains_key(&(chunk_x, chunk_z)) {
+                packet_event_emitter.send(SendPacketEvent {
                     entity,
-                    to_client_packets::BlockChangePacket {
-                        x: event.x,
-                        y: event.y,
-                        z: event.z,
-                        block_type: event.ty as i8,
-                        block_metadata: event.metadata as i8,
-                    },
-                )
-                .unwrap(),
-            );
+                    ord: 5,
+                    bytes: bytes.clone(),
+                });
+                for bytes in &tile_entities {
+                    packet_event_emitter.send(SendPacketEvent {
+                        entity,
+                        ord: 6,
+                        bytes: bytes.clone(),
+                    });
+                }
+            }
         }
     }
 }
@@ -464,15 +523,19 @@ pub fn animation(
     mut packet_event_emitter: EventWriter<SendPacketEvent>,
     mut event_collector: EventReader<AnimationEvent>,
     mut query: Query<Entity, With<connection_state::Playing>>,
+    network_ids: Query<&NetworkId>,
 ) {
-    let animations = event_collector.read().collect::<Vec<_>>();
+    let animations = event_collector
+        .read()
+        .filter_map(|e| Some((e.entity, *network_ids.get(e.entity).ok()?, e.animation)))
+        .collect::<Vec<_>>();
     for entity in &mut query {
         animations
             .iter()
-            .filter(|e| e.entity != entity)
-            .map(|e| to_client_packets::AnimationPacket {
-                entity_id: e.entity.index(),
-                animate: e.animation,
+            .filter(|(other, ..)| *other != entity)
+            .map(|(_, id, animate)| to_client_packets::AnimationPacket {
+                entity_id: id.0,
+                animate: *animate,
             })
             .for_each(|p| packet_event_emitter.send(SendPacketEvent::new(entity, p).unwrap()));
     }
@@ -482,15 +545,19 @@ pub fn player_use(
     mut packet_event_emitter: EventWriter<SendPacketEvent>,
     mut event_collector: EventReader<PlayerUseEvent>,
     mut query: Query<Entity, With<connection_state::Playing>>,
+    network_ids: Query<&NetworkId>,
 ) {
-    let events = event_collector.read().collect::<Vec<_>>();
+    let targets = event_collector
+        .read()
+        .filter_map(|event| network_ids.get(event.target).ok())
+        .collect::<Vec<_>>();
     for entity in &mut query {
-        for event in events.clone() {
+        for target in targets.clone() {
             packet_event_emitter.send(
                 SendPacketEvent::new(
                     entity,
                     to_client_packets::EntityVelocityPacket {
-                        entity_id: event.target.index(),
+                        entity_id: target.0,
                         vel_x: 0,
                         vel_y: i16::MAX,
                         vel_z: 0,
@@ -502,83 +569,166 @@ pub fn player_use(
     }
 }
 
+/// How much chunk data is sent to a single player per tick, the first chunk of a tick is always sent.
+#[derive(Resource)]
+pub struct ChunkBudget {
+    pub chunks: usize,
+    /// Compressed bytes of chunk data.
+    pub bytes: usize,
+}
+
+impl Default for ChunkBudget {
+    fn default() -> Self {
+        Self {
+            chunks: 4,
+            bytes: 64 * 1024,
+        }
+    }
+}
+
+/// How strongly chunks in the direction of travel are preferred, 0 ignores the direction and 1 would never send
+/// chunks straight ahead later than the chunk the player is in.
+const DIRECTION_BIAS: f32 = 0.5;
+
+/// Orders the chunks within `radius` around `center` in a spiral, nearest first and ahead of `direction` earlier.
+fn chunk_order(center: (i32, i32), direction: (i32, i32), radius: i32) -> Vec<(i32, i32)> {
+    let length = ((direction.0 * direction.0 + direction.1 * direction.1) as f32).sqrt();
+    let key = |(dx, dz): (i32, i32)| {
+        let distance = ((dx * dx + dz * dz) as f32).sqrt();
+        let ahead = if length > 0.0 && distance > 0.0 {
+            (dx * direction.0 + dz * direction.1) as f32 / (length * distance)
+        } else {
+            0.0
+        };
+        let angle = (dz as f32).atan2(dx as f32);
+        (distance * (1.0 - DIRECTION_BIAS * ahead), angle)
+    };
+    let mut offsets = (-radius..=radius)
+        .flat_map(|dx| (-radius..=radius).map(move |dz| (dx, dz)))
+        .map(|offset| (key(offset), offset))
+        .collect::<Vec<_>>();
+    offsets.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap());
+    offsets
+        .into_iter()
+        .map(|(_, (dx, dz))| (center.0 + dx, center.1 + dz))
+        .collect()
+}
+
+/// Sends the missing chunks around every player within the [`ChunkBudget`].
+///
+/// The queue is reordered whenever a player enters another chunk or their view distance changes,
+/// dropping chunks that are out of range.
 pub fn load_chunks(
     mut packet_event_emitter: EventWriter<SendPacketEvent>,
     mut world: ResMut<World>,
-    mut query: Query<(Entity, &Position, &mut PlayerChunkDB), With<connection_state::Playing>>,
+    budget: Res<ChunkBudget>,
+    mut query: Query<
+        (
+            Entity,
+            &Position,
+            &ViewDistance,
+            &mut PlayerChunkDB,
+            &mut ChunkQueue,
+        ),
+        With<connection_state::Playing>,
+    >,
 ) {
-    for (entity, position, mut db) in &mut query {
-        // Get players chunk
+    for (entity, position, view_distance, mut db, mut queue) in &mut query {
         let x = position.x.floor() as i32;
         let z = position.z.floor() as i32;
-        let (player_chunk_x, player_chunk_z) = (x >> 4, z >> 4);
+        let center = (x >> 4, z >> 4);
+        if queue.center != Some(center) || queue.radius != view_distance.current {
+            let direction = queue
+                .center
+                .map_or((0, 0), |(x, z)| (center.0 - x, center.1 - z));
+            queue.pending = chunk_order(center, direction, view_distance.current)
+                .into_iter()
+                .filter(|key| !db.chunks.contains_key(key))
+                .collect();
+            queue.center = Some(center);
+            queue.radius = view_distance.current;
+        }
 
-        let chunk_r = crate::RENDER_DISTANCE_RADIUS;
-        for x in (player_chunk_x - chunk_r)..=(player_chunk_x + chunk_r) {
-            for z in (player_chunk_z - chunk_r)..=(player_chunk_z + chunk_r) {
-                if db.chunks.get(&(x, z)).is_none() {
-                    match world.get_chunk(x, z) {
-                        Ok(chunk) => {
-                            debug!("Loaded chunk at (x: {x}, z: {z}).");
-                            if db.chunks.insert((x, z), chunk.clone()).is_none() {
-                                let packet = SendPacketEvent::with_ord(
-                                    entity,
-                                    1,
-                                    to_client_packets::PreChunkPacket { x, z, mode: true },
-                                )
-                                .unwrap();
-                                packet_event_emitter.send(packet);
-                                let (len, chunk_data) = chunk.read().unwrap().get_compressed_data();
-                                let packet = SendPacketEvent::with_ord(
-                                    entity,
-                                    2,
-                                    to_client_packets::MapChunkPacket {
-                                        x: x * 16,
-                                        y: 0,
-                                        z: z * 16,
-                                        size_x: 15,
-                                        size_y: 127,
-                                        size_z: 15,
-                                        compressed_size: len,
-                                        compressed_data: chunk_data[..len as usize].to_vec(),
-                                    },
-                                )
-                                .unwrap();
-                                packet_event_emitter.send(packet);
-                            }
-                        }
-                        Err(err) => {
-                            error!("Failed to load chunk at (x: {x}, z: {z}): {err}!")
-                        }
-                    }
+        let (mut chunks, mut bytes) = (0, 0);
+        while chunks < budget.chunks.max(1) && bytes < budget.bytes {
+            let Some((x, z)) = queue.pending.pop_front() else {
+                break;
+            };
+            if db.chunks.contains_key(&(x, z)) {
+                continue;
+            }
+            let chunk = match world.get_chunk(x, z) {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    error!("Failed to load chunk at (x: {x}, z: {z}): {err}!");
+                    continue;
                 }
+            };
+            debug!("Loaded chunk at (x: {x}, z: {z}).");
+            let packet = SendPacketEvent::with_ord(
+                entity,
+                1,
+                to_client_packets::PreChunkPacket { x, z, mode: true },
+            )
+            .unwrap();
+            packet_event_emitter.send(packet);
+            let (len, chunk_data) = chunk.read().unwrap().get_compressed_data();
+            let packet = SendPacketEvent::with_ord(
+                entity,
+                2,
+                to_client_packets::MapChunkPacket {
+                    x: x * 16,
+                    y: 0,
+                    z: z * 16,
+                    size_x: 15,
+                    size_y: 127,
+                    size_z: 15,
+                    compressed_size: len,
+                    compressed_data: chunk_data[..len as usize].to_vec(),
+                },
+            )
+            .unwrap();
+            packet_event_emitter.send(packet);
+            for packet in chunk
+                .read()
+                .unwrap()
+                .tile_entities()
+                .iter()
+                .filter_map(tile_entity_packet)
+            {
+                packet_event_emitter.send(SendPacketEvent::with_ord(entity, 3, packet).unwrap());
             }
+            db.chunks.insert((x, z), chunk);
+            chunks += 1;
+            bytes += len as usize;
         }
     }
 }
 
+/// Chunks this far outside of the view distance of a player are kept, so walking back and forth does not resend them.
+const UNLOAD_MARGIN: i32 = 2;
+
 pub fn unload_chunks(
     mut packet_event_emitter: EventWriter<SendPacketEvent>,
     mut world: ResMut<World>,
-    mut query: Query<(Entity, &Position, &mut PlayerChunkDB), With<connection_state::Playing>>,
+    mut query: Query<
+        (Entity, &Position, &ViewDistance, &mut PlayerChunkDB),
+        With<connection_state::Playing>,
+    >,
 ) {
-    for (entity, position, mut db) in &mut query {
+    for (entity, position, view_distance, mut db) in &mut query {
         // Get players chunk
         let x = position.x.floor() as i32;
         let z = position.z.floor() as i32;
         let (player_chunk_x, player_chunk_z) = (x >> 4, z >> 4);
 
-        let chunk_r = crate::RENDER_DISTANCE_RADIUS * 2; // Buffer zone
-        let mut allowed_chunks = Vec::with_capacity(chunk_r as usize * chunk_r as usize);
-        for x in (player_chunk_x - chunk_r)..=(player_chunk_x + chunk_r) {
-            for z in (player_chunk_z - chunk_r)..=(player_chunk_z + chunk_r) {
-                allowed_chunks.push((x, z));
-            }
-        }
+        let chunk_r = view_distance.current + UNLOAD_MARGIN;
         let to_remove = db
             .chunks
             .keys()
-            .filter(|k| !allowed_chunks.contains(*k))
+            .filter(|(x, z)| {
+                (x - player_chunk_x).abs() > chunk_r || (z - player_chunk_z).abs() > chunk_r
+            })
             .copied()
             .collect::<Vec<_>>();
         for (x, z) in to_remove {
@@ -612,3 +762,19 @@ pub fn increment_time(
         packet_event_emitter.send(SendPacketEvent::new(entity, packet.clone()).unwrap());
     }
 }
+
+#[test]
+fn test_chunk_order() {
+    let order = chunk_order((10, -3), (0, 0), 2);
+    assert_eq!(order.len(), 25);
+    assert_eq!(order[0], (10, -3));
+    let distance = |(x, z): (i32, i32)| (x - 10) * (x - 10) + (z + 3) * (z + 3);
+    assert!(order.windows(2).all(|w| distance(w[0]) <= distance(w[1])));
+
+    // Travelling towards positive x, two chunks ahead come before the chunk behind.
+    let order = chunk_order((0, 0), (1, 0), 2);
+    let index = |key| order.iter().position(|k| *k == key).unwrap();
+    assert_eq!(order[0], (0, 0));
+    assert_eq!(order[1], (1, 0));
+    assert!(index((2, 0)) < index((-1, 0)));
+}
@@ -31,6 +31,19 @@ pub fn base36_from_i32(input: i32) -> String {
     format!("{lead}{out}")
 }
 
+/// Parses a signed base36 number as used in Alpha chunk file names, returns `None` for invalid input.
+pub fn i32_from_base36(input: &str) -> Option<i32> {
+    let (negative, digits) = match input.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, input),
+    };
+    if digits.starts_with(['+', '-']) {
+        return None;
+    }
+    let value = i64::from_str_radix(digits, 36).ok()?;
+    i32::try_from(if negative { -value } else { value }).ok()
+}
+
 pub fn pack_float_pair(yaw: f32, pitch: f32) -> (i8, i8) {
     let yaw_shortened = ((yaw / 360.) * 255.) % 255.;
     let pitch = (((pitch / 360.) * 255.) % 255.) as i8;
@@ -64,3 +77,11 @@ fn test_base36_from_i32() {
     assert_eq!(base36_from_i32(-13), "-d");
     assert_eq!(base36_from_i32(0), "0");
 }
+
+#[test]
+fn test_i32_from_base36() {
+    assert_eq!(i32_from_base36("-d"), Some(-13));
+    assert_eq!(i32_from_base36("ya"), Some(1234));
+    assert_eq!(i32_from_base36("0"), Some(0));
+    assert_eq!(i32_from_base36("c.1"), None);
+}
@@ -0,0 +1,127 @@
+use crate::command::{
+    Arguments, Command, CommandContext, CommandError, CommandRegistry, CommandResult,
+};
+use crate::entity::{connection_state, Named, ViewDistance};
+use crate::world::World;
+use bevy::prelude::{Query, Res, Resource, With};
+use log::{info, warn};
+use std::time::Duration;
+
+/// Server tick time above which view distances shrink, a tick is meant to take 50ms.
+const SLOW_TICK: Duration = Duration::from_millis(40);
+/// Server tick time below which shrunk view distances grow again.
+const FAST_TICK: Duration = Duration::from_millis(25);
+/// Memory loaded chunks may take before view distances shrink.
+const MAX_CHUNK_MEMORY: usize = 256 * 1024 * 1024;
+/// Blocks, metadata and both light arrays of a chunk.
+const CHUNK_MEMORY: usize = 16 * 16 * 128 * 5 / 2;
+/// Smallest radius view distances shrink to.
+const MIN_RADIUS: i32 = 2;
+
+/// View distance new players get and the largest one that can be set.
+#[derive(Resource)]
+pub struct ViewDistanceSettings {
+    pub default: i32,
+    pub max: i32,
+}
+
+impl Default for ViewDistanceSettings {
+    fn default() -> Self {
+        Self {
+            default: 4,
+            max: 10,
+        }
+    }
+}
+
+impl ViewDistanceSettings {
+    pub fn view_distance(&self) -> ViewDistance {
+        ViewDistance {
+            requested: self.default,
+            current: self.default,
+        }
+    }
+}
+
+/// Average duration of the server tick, measured by the runner.
+#[derive(Resource, Default)]
+pub struct TickTime {
+    average: Duration,
+}
+
+impl TickTime {
+    pub fn record(&mut self, duration: Duration) {
+        // Exponential moving average over roughly the last second.
+        self.average = (self.average * 19 + duration) / 20;
+    }
+
+    pub fn average(&self) -> Duration {
+        self.average
+    }
+}
+
+/// Shrinks the view distance of every player by one while the server is overloaded and grows it back afterwards.
+pub fn adjust_view_distance(
+    tick_time: Res<TickTime>,
+    world: Res<World>,
+    mut query: Query<(&Named, &mut ViewDistance), With<connection_state::Playing>>,
+) {
+    let chunk_memory = world.loaded_chunks() * CHUNK_MEMORY;
+    let tick_time = tick_time.average();
+    if tick_time > SLOW_TICK || chunk_memory > MAX_CHUNK_MEMORY {
+        let mut shrunk = false;
+        for (_, mut view_distance) in &mut query {
+            if view_distance.current > MIN_RADIUS {
+                view_distance.current -= 1;
+                shrunk = true;
+            }
+        }
+        if shrunk {
+            warn!(
+                "Shrinking view distances, ticks take {tick_time:?} and loaded chunks {} MiB.",
+                chunk_memory / 1024 / 1024
+            );
+        }
+    } else if tick_time < FAST_TICK && chunk_memory < MAX_CHUNK_MEMORY * 3 / 4 {
+        for (_, mut view_distance) in &mut query {
+            if view_distance.current < view_distance.requested {
+                view_distance.current += 1;
+            }
+        }
+    }
+}
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(
+        "viewdistance",
+        Command::new(
+            "betalpha.command.viewdistance",
+            "/viewdistance [player] <radius>",
+            "Sets the view distance of you or a player.",
+            command,
+        ),
+    );
+}
+
+fn command(context: &mut CommandContext, arguments: &mut Arguments) -> CommandResult {
+    let player = match arguments.remaining() {
+        1 => context.sender,
+        2 => arguments.player(context)?,
+        _ => return Err(CommandError::Usage),
+    };
+    let max = context.ecs.resource::<ViewDistanceSettings>().max;
+    let radius = arguments.integer::<i32>()?;
+    if !(1..=max).contains(&radius) {
+        return Err(format!("The radius has to be between 1 and {max}.").into());
+    }
+    let name = context.name(player);
+    let mut view_distance = context
+        .ecs
+        .get_mut::<ViewDistance>(player)
+        .ok_or("You are not in the world.")?;
+    // Growing starts from the current radius, shrinking applies immediately.
+    view_distance.requested = radius;
+    view_distance.current = view_distance.current.min(radius);
+    info!("View distance of {name} set to {radius}.");
+    Ok(vec![format!("View distance of {name} set to {radius}.")])
+}
@@ -0,0 +1,327 @@
+use crate::entity::{connection_state, Look, Named, Position};
+use crate::event::{BlockChangeEvent, ChunkUpdateEvent};
+use crate::world::storage::WorldStorage;
+use crate::world::{Chunk, World};
+use betalpha_mc::render::{self, ChunkImage, Image, TILE_CHUNKS, TILE_SIZE};
+use bevy::prelude::{EventReader, Query, Res, ResMut, Resource, With};
+use log::{info, warn};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+const INDEX: &str = include_str!("web_map.html");
+/// Rendered chunks kept in memory, about 1 KiB each.
+const MAX_CHUNK_IMAGES: usize = 16 * 1024;
+
+type EncodedTiles = HashMap<(i32, i32), Arc<Vec<u8>>>;
+/// Smallest and largest tile coordinates.
+type TileBounds = Option<((i32, i32), (i32, i32))>;
+
+/// Map data shared between the server and the HTTP thread.
+struct MapState {
+    storage: Arc<dyn WorldStorage>,
+    chunks: Mutex<ChunkCache>,
+    /// Smallest and largest tile that contains a chunk, other tiles are not composed.
+    bounds: RwLock<TileBounds>,
+    /// Encoded tiles, a tile is dropped whenever one of its chunks changes.
+    tiles: RwLock<EncodedTiles>,
+    /// Counts dropped tiles, so tiles that were composed while their chunks changed are not cached.
+    generation: AtomicU64,
+    players: RwLock<String>,
+    spawn: [i32; 3],
+}
+
+/// Rendered chunks, the least recently used ones are dropped once there are too many.
+#[derive(Default)]
+struct ChunkCache {
+    images: HashMap<(i32, i32), (ChunkImage, u64)>,
+    /// Chunks that do not exist.
+    missing: HashSet<(i32, i32)>,
+    /// Counts lookups, to tell which images were used last.
+    clock: u64,
+}
+
+impl ChunkCache {
+    /// Returns the cached chunk, `Some(None)` if it does not exist.
+    fn get(&mut self, key: (i32, i32)) -> Option<Option<ChunkImage>> {
+        if self.missing.contains(&key) {
+            return Some(None);
+        }
+        self.clock += 1;
+        let (image, used) = self.images.get_mut(&key)?;
+        *used = self.clock;
+        Some(Some(*image))
+    }
+
+    fn insert(&mut self, key: (i32, i32), image: Option<ChunkImage>) {
+        let Some(image) = image else {
+            self.images.remove(&key);
+            self.missing.insert(key);
+            return;
+        };
+        self.missing.remove(&key);
+        self.clock += 1;
+        self.images.insert(key, (image, self.clock));
+        if self.images.len() > MAX_CHUNK_IMAGES {
+            // Evicting a quarter at once keeps the scan for the oldest images rare.
+            let mut used = self
+                .images
+                .values()
+                .map(|(_, used)| *used)
+                .collect::<Vec<_>>();
+            let (_, oldest, _) = used.select_nth_unstable(MAX_CHUNK_IMAGES / 4);
+            let oldest = *oldest;
+            self.images.retain(|_, (_, used)| *used > oldest);
+        }
+    }
+}
+
+/// Serves a live map of the world over HTTP.
+#[derive(Resource)]
+pub struct WebMap {
+    /// `None` if the map is turned off.
+    state: Option<Arc<MapState>>,
+    changed: HashSet<(i32, i32)>,
+}
+
+impl WebMap {
+    /// Starts serving the map of `world` on `address` from a separate thread, no address turns it off.
+    pub fn start(address: Option<SocketAddr>, world: &World) -> std::io::Result<Self> {
+        let Some(address) = address else {
+            info!("The web map is turned off.");
+            return Ok(Self {
+                state: None,
+                changed: HashSet::new(),
+            });
+        };
+        let server = tiny_http::Server::http(address)
+            .map_err(|err| std::io::Error::other(err.to_string()))?;
+        let storage = world.shared_storage();
+        let mut bounds = None;
+        for (x, z) in storage.chunks().list_chunks()? {
+            extend(&mut bounds, render::tile_of(x, z));
+        }
+        let state = Arc::new(MapState {
+            storage,
+            chunks: Mutex::new(ChunkCache::default()),
+            bounds: RwLock::new(bounds),
+            tiles: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            players: RwLock::new("[]".to_string()),
+            spawn: world.get_spawn(),
+        });
+        let thread_state = state.clone();
+        std::thread::Builder::new()
+            .name("web-map".to_string())
+            .spawn(move || serve(server, &thread_state))?;
+        info!("Serving the web map on http://{address}/");
+        Ok(Self {
+            state: Some(state),
+            changed: HashSet::new(),
+        })
+    }
+}
+
+/// Remembers which chunks were touched by block changes until they are rendered again.
+pub fn collect_changed_chunks(
+    mut web_map: ResMut<WebMap>,
+    mut event_collector: EventReader<BlockChangeEvent>,
+    mut chunk_update_collector: EventReader<ChunkUpdateEvent>,
+) {
+    if web_map.state.is_none() {
+        return;
+    }
+    for event in event_collector.read() {
+        web_map.changed.insert((event.x >> 4, event.z >> 4));
+    }
+    for event in chunk_update_collector.read() {
+        web_map.changed.insert((event.chunk_x, event.chunk_z));
+    }
+}
+
+pub fn render_changed_chunks(mut web_map: ResMut<WebMap>, mut world: ResMut<World>) {
+    let changed = std::mem::take(&mut web_map.changed);
+    let Some(state) = &web_map.state else {
+        return;
+    };
+    for (x, z) in changed {
+        let image = match world.get_chunk(x, z) {
+            Ok(chunk) => render::render_chunk(&chunk.read().unwrap()),
+            Err(err) => {
+                warn!("Could not render chunk at (x: {x}, z: {z}) for the web map: {err}");
+                continue;
+            }
+        };
+        state.chunks.lock().unwrap().insert((x, z), Some(image));
+        extend(&mut state.bounds.write().unwrap(), render::tile_of(x, z));
+        state.generation.fetch_add(1, Ordering::SeqCst);
+        state.tiles.write().unwrap().remove(&render::tile_of(x, z));
+    }
+}
+
+/// Grows `bounds` to include `tile`.
+fn extend(bounds: &mut TileBounds, (x, z): (i32, i32)) {
+    let ((min_x, min_z), (max_x, max_z)) = bounds.get_or_insert(((x, z), (x, z)));
+    (*min_x, *min_z) = (x.min(*min_x), z.min(*min_z));
+    (*max_x, *max_z) = (x.max(*max_x), z.max(*max_z));
+}
+
+pub fn update_players(
+    web_map: Res<WebMap>,
+    query: Query<(&Named, &Position, &Look), With<connection_state::Playing>>,
+) {
+    let Some(state) = &web_map.state else {
+        return;
+    };
+    let players = query
+        .iter()
+        .map(|(named, position, look)| {
+            json!({
+                "name": named.name,
+                "x": position.x,
+                "y": position.y,
+                "z": position.z,
+                "yaw": look.yaw,
+            })
+        })
+        .collect::<Vec<_>>();
+    *state.players.write().unwrap() = serde_json::Value::from(players).to_string();
+}
+
+fn serve(server: tiny_http::Server, state: &MapState) {
+    for request in server.incoming_requests() {
+        let response = respond(state, request.url());
+        if let Err(err) = request.respond(response) {
+            warn!("Failed to answer web map request: {err}");
+        }
+    }
+}
+
+fn respond(state: &MapState, url: &str) -> tiny_http::Response<Cursor<Vec<u8>>> {
+    let path = url.split('?').next().unwrap_or_default();
+    let parts = path.split('/').skip(1).collect::<Vec<_>>();
+    match parts.as_slice() {
+        [""] => {
+            let [x, _, z] = state.spawn;
+            let index = INDEX
+                .replace("{spawn_x}", &x.to_string())
+                .replace("{spawn_z}", &z.to_string());
+            with_type(index.into_bytes(), "text/html; charset=utf-8")
+        }
+        ["players.json"] => with_type(
+            state.players.read().unwrap().clone().into_bytes(),
+            "application/json",
+        ),
+        ["tiles", x, z] => {
+            let coordinates = z
+                .strip_suffix(".png")
+                .and_then(|z| Some((x.parse().ok()?, z.parse().ok()?)));
+            // Tiles outside of the world are refused, so requests can not fill the cache with nothing.
+            let inside = |(x, z): (i32, i32)| {
+                state.bounds.read().unwrap().is_some_and(|(min, max)| {
+                    (min.0..=max.0).contains(&x) && (min.1..=max.1).contains(&z)
+                })
+            };
+            match coordinates.filter(|tile| inside(*tile)) {
+                Some((x, z)) => with_type(tile(state, x, z).to_vec(), "image/png"),
+                None => not_found(),
+            }
+        }
+        _ => not_found(),
+    }
+}
+
+fn with_type(bytes: Vec<u8>, content_type: &str) -> tiny_http::Response<Cursor<Vec<u8>>> {
+    let header = tiny_http::Header::from_bytes("Content-Type", content_type).unwrap();
+    let no_cache = tiny_http::Header::from_bytes("Cache-Control", "no-cache").unwrap();
+    tiny_http::Response::from_data(bytes)
+        .with_header(header)
+        .with_header(no_cache)
+}
+
+fn not_found() -> tiny_http::Response<Cursor<Vec<u8>>> {
+    tiny_http::Response::from_string("Not found").with_status_code(404)
+}
+
+/// Returns the encoded tile at `tile_x` and `tile_z`, composing it if it is not cached.
+fn tile(state: &MapState, tile_x: i32, tile_z: i32) -> Arc<Vec<u8>> {
+    if let Some(tile) = state.tiles.read().unwrap().get(&(tile_x, tile_z)) {
+        return tile.clone();
+    }
+    let generation = state.generation.load(Ordering::SeqCst);
+    let mut image = Image::new(TILE_SIZE, TILE_SIZE);
+    // Tiles with chunks that could not be read are composed again on the next request.
+    let mut complete = true;
+    for z in 0..TILE_CHUNKS {
+        for x in 0..TILE_CHUNKS {
+            let chunk = chunk_image(state, tile_x * TILE_CHUNKS + x, tile_z * TILE_CHUNKS + z);
+            complete &= chunk.is_ok();
+            image.draw_chunk(x as u32 * 16, z as u32 * 16, chunk.ok().flatten().as_ref());
+        }
+    }
+    let tile = match image.encode_png() {
+        Ok(bytes) => Arc::new(bytes),
+        Err(err) => {
+            warn!("Failed to encode tile at (x: {tile_x}, z: {tile_z}): {err}");
+            return Arc::new(Vec::new());
+        }
+    };
+    let mut tiles = state.tiles.write().unwrap();
+    if complete && state.generation.load(Ordering::SeqCst) == generation {
+        tiles.insert((tile_x, tile_z), tile.clone());
+    }
+    tile
+}
+
+/// Returns the rendered chunk at `x` and `z`, reading chunks the server did not render from the storage.
+///
+/// Only chunks that do not exist are remembered as missing, other errors, like a region file that is
+/// being written, are retried on the next request.
+fn chunk_image(state: &MapState, x: i32, z: i32) -> std::io::Result<Option<ChunkImage>> {
+    if let Some(image) = state.chunks.lock().unwrap().get((x, z)) {
+        return Ok(image);
+    }
+    let image = match Chunk::load(state.storage.chunks(), x, z) {
+        Ok(chunk) => Some(render::render_chunk(&chunk)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => {
+            warn!("Could not render chunk at (x: {x}, z: {z}) for the web map: {err}");
+            return Err(err);
+        }
+    };
+    // The server may have rendered a newer version in the meantime, which must not be replaced.
+    let mut chunks = state.chunks.lock().unwrap();
+    if let Some(image) = chunks.get((x, z)) {
+        return Ok(image);
+    }
+    chunks.insert((x, z), image);
+    Ok(image)
+}
+
+#[test]
+fn test_chunk_cache() {
+    let mut cache = ChunkCache::default();
+    cache.insert((0, 0), None);
+    assert_eq!(cache.get((0, 0)), Some(None));
+    assert_eq!(cache.get((1, 0)), None);
+    for x in 0..MAX_CHUNK_IMAGES as i32 {
+        cache.insert((x, 1), Some([x as u8; 16 * 16 * 4]));
+    }
+    // The chunk that was used last is kept when the oldest ones are dropped.
+    assert!(cache.get((0, 1)).is_some());
+    cache.insert((0, 2), Some([0; 16 * 16 * 4]));
+    assert!(cache.images.len() < MAX_CHUNK_IMAGES);
+    assert!(cache.get((0, 1)).is_some());
+    assert_eq!(cache.get((1, 1)), None);
+    // Chunks that were generated later are no longer missing.
+    cache.insert((0, 0), Some([0; 16 * 16 * 4]));
+    assert!(cache.get((0, 0)).unwrap().is_some());
+
+    let mut bounds = None;
+    extend(&mut bounds, (2, -1));
+    extend(&mut bounds, (-3, 4));
+    assert_eq!(bounds, Some(((-3, -1), (2, 4))));
+}
@@ -1,16 +1,44 @@
-use crate::util::{base36_from_i32, base36_from_u64};
+use crate::registry;
+use crate::world::generator::Generator;
+use crate::world::storage::{ChunkStorage, FsStorage, WorldStorage};
 use crate::world::util::{
     read_nbt_bool, read_nbt_byte_array, read_nbt_i32, read_nbt_i64, read_value_bool,
-    read_value_byte_array, read_value_i32, read_value_i64,
+    read_value_byte_array, read_value_f32, read_value_f64, read_value_i32, read_value_i64,
 };
 use bevy::prelude::Resource;
 use log::debug;
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex, RwLock, TryLockResult};
 
+pub mod edit;
+pub mod generator;
+pub mod memory;
+pub mod region;
+pub mod schematic;
+pub mod storage;
+
+/// NBT compound of a tile entity like a chest or a sign, its `x`, `y` and `z` are world coordinates.
+pub type TileEntity = HashMap<String, nbt::Value>;
+
+/// Reads the position stored in a tile entity.
+pub fn tile_entity_position(tile_entity: &TileEntity) -> Option<[i32; 3]> {
+    let coordinate = |name: &str| match tile_entity.get(name) {
+        Some(nbt::Value::Int(v)) => Some(*v),
+        _ => None,
+    };
+    Some([coordinate("x")?, coordinate("y")?, coordinate("z")?])
+}
+
+/// Overwrites the position stored in a tile entity.
+pub fn set_tile_entity_position(tile_entity: &mut TileEntity, [x, y, z]: [i32; 3]) {
+    tile_entity.insert("x".to_string(), nbt::Value::Int(x));
+    tile_entity.insert("y".to_string(), nbt::Value::Int(y));
+    tile_entity.insert("z".to_string(), nbt::Value::Int(z));
+}
+
 mod util {
     pub fn read_nbt_i64(blob: &nbt::Blob, name: &'static str) -> std::io::Result<i64> {
         if let nbt::Value::Long(v) = blob.get(name).ok_or(std::io::Error::new(
@@ -97,6 +125,28 @@ mod util {
         }
     }
 
+    pub fn read_value_f64(value: &nbt::Value) -> std::io::Result<f64> {
+        if let nbt::Value::Double(v) = value {
+            Ok(*v)
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Field has wrong type!",
+            ))
+        }
+    }
+
+    pub fn read_value_f32(value: &nbt::Value) -> std::io::Result<f32> {
+        if let nbt::Value::Float(v) = value {
+            Ok(*v)
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Field has wrong type!",
+            ))
+        }
+    }
+
     pub fn read_value_byte(value: &nbt::Value) -> std::io::Result<i8> {
         if let nbt::Value::Byte(v) = value {
             return Ok(*v);
@@ -129,22 +179,32 @@ mod util {
 
 #[derive(Resource)]
 pub struct World {
-    path: PathBuf,
+    storage: Arc<dyn WorldStorage>,
     chunks: HashMap<(i32, i32), Arc<RwLock<Chunk>>>,
     seed: i64,
     spawn: [i32; 3],
     time: u64,
     size_on_disk: u64,
     last_played: u64,
+    /// Makes up chunks that are missing from the storage.
+    generator: Generator,
 }
 
 impl World {
+    /// Opens the world saved in the directory `world_path`.
     pub fn open<P: AsRef<Path>>(world_path: P) -> std::io::Result<Self> {
+        Self::load(Box::new(FsStorage::open(world_path)))
+    }
+
+    /// Loads an existing world from `storage`.
+    pub fn load(storage: Box<dyn WorldStorage>) -> std::io::Result<Self> {
         let (seed, spawn, time, size_on_disk, last_played) = {
-            let mut file = std::fs::File::open(world_path.as_ref().join("level.dat"))?;
-            let blob = nbt::Blob::from_gzip_reader(&mut file)?;
+            let blob = storage.read_level()?;
 
-            let data = blob.get("Data").unwrap();
+            let data = blob.get("Data").ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Did not find Data field in level!",
+            ))?;
 
             if let nbt::Value::Compound(v) = data {
                 let seed = read_value_i64(v.get("RandomSeed").unwrap())?;
@@ -160,42 +220,77 @@ impl World {
             } else {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::NotFound,
-                    "Did not find Level field in chunk!",
+                    "Did not find Data field in level!",
                 ));
             }
         };
 
         Ok(Self {
-            path: world_path.as_ref().to_path_buf(),
+            storage: Arc::from(storage),
             chunks: HashMap::with_capacity(u16::MAX as usize),
             seed,
             spawn,
             time,
             size_on_disk,
             last_played,
+            generator: Generator::None,
         })
     }
 
-    pub fn close(self) -> std::io::Result<()> {
-        let mut file = std::fs::File::create(self.path.join("level.dat"))?;
-        let mut compund = HashMap::with_capacity(7);
-        compund.insert("RandomSeed".to_string(), nbt::Value::Long(self.seed as i64));
+    /// Creates a new, empty world in `storage`. Nothing is written until the world is saved.
+    pub fn create(storage: Box<dyn WorldStorage>, seed: i64, spawn: [i32; 3]) -> Self {
+        Self {
+            storage: Arc::from(storage),
+            chunks: HashMap::new(),
+            seed,
+            spawn,
+            time: 0,
+            size_on_disk: 0,
+            last_played: 0,
+            generator: Generator::None,
+        }
+    }
+
+    /// Saves all loaded chunks and the level data.
+    pub fn save(&mut self) -> std::io::Result<()> {
+        for chunk in self.chunks.values() {
+            chunk.read().unwrap().save(self.storage.chunks())?;
+        }
+        self.save_level()
+    }
+
+    pub fn save_level(&mut self) -> std::io::Result<()> {
+        self.size_on_disk = self.storage.size_on_disk()?;
+        self.last_played = std::time::UNIX_EPOCH.elapsed().unwrap().as_secs();
+        // Keys the server does not know about, like the player of a single player world, are kept.
+        let mut compund = match self.storage.read_level() {
+            Ok(blob) => match blob.get("Data") {
+                Some(nbt::Value::Compound(data)) => data.clone(),
+                _ => HashMap::with_capacity(7),
+            },
+            Err(_) => HashMap::with_capacity(7),
+        };
+        compund.insert("RandomSeed".to_string(), nbt::Value::Long(self.seed));
         compund.insert("SpawnX".to_string(), nbt::Value::Int(self.spawn[0]));
         compund.insert("SpawnY".to_string(), nbt::Value::Int(self.spawn[1]));
         compund.insert("SpawnZ".to_string(), nbt::Value::Int(self.spawn[2]));
         compund.insert("Time".to_string(), nbt::Value::Long(self.time as i64));
-        let size = fs_extra::dir::get_size(self.path)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-        compund.insert("SizeOnDisk".to_string(), nbt::Value::Long(size as i64));
+        compund.insert(
+            "SizeOnDisk".to_string(),
+            nbt::Value::Long(self.size_on_disk as i64),
+        );
         compund.insert(
             "LastPlayed".to_string(),
-            nbt::Value::Long(std::time::UNIX_EPOCH.elapsed().unwrap().as_secs() as i64),
+            nbt::Value::Long(self.last_played as i64),
         );
 
         let mut blob = nbt::Blob::new();
         blob.insert("Data", nbt::Value::Compound(compund))?;
-        blob.to_gzip_writer(&mut file)?;
-        Ok(())
+        self.storage.write_level(&blob)
+    }
+
+    pub fn close(mut self) -> std::io::Result<()> {
+        self.save()
     }
 
     /// Gets a chunk from loaded chunks or loads the chunk into memory.
@@ -206,7 +301,12 @@ impl World {
         if let Some(chunk) = self.chunks.get(&key) {
             Ok(chunk.clone())
         } else {
-            let chunk = Chunk::load(&self.path, x, z)?;
+            let chunk = match Chunk::load(self.storage.chunks(), x, z) {
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    self.generator.generate(x, z).ok_or(err)?
+                }
+                chunk => chunk?,
+            };
             self.chunks.insert(key, Arc::new(RwLock::new(chunk)));
             self.chunks.get(&key).cloned().ok_or(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
@@ -225,7 +325,7 @@ impl World {
 
         if let Some(chunk) = self.chunks.remove(&key) {
             match chunk.try_write() {
-                Ok(mut chunk) => chunk.save(&self.path),
+                Ok(chunk) => chunk.save(self.storage.chunks()),
                 Err(e) => {
                     self.chunks.insert(key, chunk.clone());
                     Err(std::io::Error::new(
@@ -246,7 +346,7 @@ impl World {
         let chunk = self.get_chunk(x, z)?;
         let chunk = chunk.try_write();
         match chunk {
-            Ok(chunk) => chunk.save(&self.path),
+            Ok(chunk) => chunk.save(self.storage.chunks()),
             Err(err) => Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
                 err.to_string(),
@@ -254,15 +354,130 @@ impl World {
         }
     }
 
+    /// Returns the BlockID at the world coordinates specified, loading the chunk if necessary.
+    ///
+    /// returns: Option<u8>
+    pub fn get_block(&mut self, x: i32, y: i32, z: i32) -> Option<u8> {
+        let y = u8::try_from(y).ok().filter(|y| *y < 128)?;
+        let chunk = self.get_chunk(x >> 4, z >> 4).ok()?;
+        let chunk = chunk.read().ok()?;
+        chunk.get_block((x & 15) as u8, y, (z & 15) as u8)
+    }
+
+    /// Returns the BlockID and metadata at the world coordinates specified, loading the chunk if necessary.
+    pub fn get_block_and_data(&mut self, x: i32, y: i32, z: i32) -> Option<(u8, u8)> {
+        let y = u8::try_from(y).ok().filter(|y| *y < 128)?;
+        let chunk = self.get_chunk(x >> 4, z >> 4).ok()?;
+        let chunk = chunk.read().ok()?;
+        let (x, z) = ((x & 15) as u8, (z & 15) as u8);
+        Some((chunk.get_block(x, y, z)?, chunk.get_data(x, y, z)?))
+    }
+
+    /// Overwrites the BlockID and metadata at the world coordinates specified, loading the chunk if necessary.
+    ///
+    /// A tile entity at the position is removed if the BlockID changes.
+    /// Returns the old BlockID and metadata or `None` if the position is outside of the world.
+    pub fn set_block_and_data(
+        &mut self,
+        x: i32,
+        y: i32,
+        z: i32,
+        id: u8,
+        data: u8,
+    ) -> Option<(u8, u8)> {
+        let y = u8::try_from(y).ok().filter(|y| *y < 128)?;
+        let chunk = self.get_chunk(x >> 4, z >> 4).ok()?;
+        let mut chunk = chunk.write().ok()?;
+        let (local_x, local_z) = ((x & 15) as u8, (z & 15) as u8);
+        let old_data = chunk.set_data(local_x, y, local_z, data)?;
+        let old_id = chunk.set_block(local_x, y, local_z, id)?;
+        if old_id != id {
+            chunk.remove_tile_entity([x, y as i32, z]);
+        }
+        Some((old_id, old_data))
+    }
+
+    /// Returns a copy of the tile entity at the world coordinates specified, loading the chunk if necessary.
+    pub fn get_tile_entity(&mut self, x: i32, y: i32, z: i32) -> Option<TileEntity> {
+        let chunk = self.get_chunk(x >> 4, z >> 4).ok()?;
+        let chunk = chunk.read().ok()?;
+        chunk.tile_entity([x, y, z]).cloned()
+    }
+
+    /// Stores a tile entity at the position it contains, replacing the previous one.
+    ///
+    /// Returns `false` if the tile entity has no position or its chunk does not exist.
+    pub fn set_tile_entity(&mut self, tile_entity: TileEntity) -> bool {
+        let Some([x, _, z]) = tile_entity_position(&tile_entity) else {
+            return false;
+        };
+        let Ok(chunk) = self.get_chunk(x >> 4, z >> 4) else {
+            return false;
+        };
+        let mut chunk = chunk.write().unwrap();
+        chunk.set_tile_entity(tile_entity);
+        true
+    }
+
+    /// Number of chunks kept in memory.
+    pub fn loaded_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Adds a chunk to the loaded chunks, replacing any chunk at the same position.
+    pub fn insert_chunk(&mut self, chunk: Chunk) -> Arc<RwLock<Chunk>> {
+        let chunk = Arc::new(RwLock::new(chunk));
+        self.chunks
+            .insert(chunk.read().unwrap().get_position(), chunk.clone());
+        chunk
+    }
+
+    /// Loads the saved state of the player `name`.
+    ///
+    /// Errors with `ErrorKind::NotFound` if the player never joined this world.
+    pub fn load_player(&self, name: &str) -> std::io::Result<PlayerData> {
+        PlayerData::from_nbt(&self.storage.read_player(name)?)
+    }
+
+    pub fn save_player(&self, name: &str, player: &PlayerData) -> std::io::Result<()> {
+        // Keep whatever else the file contains, we only know about a subset of the fields.
+        let mut blob = self.storage.read_player(name).unwrap_or_default();
+        player.write_nbt(&mut blob)?;
+        self.storage.write_player(name, &blob)
+    }
+
+    pub fn storage(&self) -> &dyn WorldStorage {
+        self.storage.as_ref()
+    }
+
+    /// Returns a handle to the storage that can be used outside of the server, e.g. from another thread.
+    ///
+    /// Chunks that are loaded may be newer in memory than in the storage.
+    pub fn shared_storage(&self) -> Arc<dyn WorldStorage> {
+        self.storage.clone()
+    }
+
+    pub fn set_generator(&mut self, generator: Generator) {
+        self.generator = generator;
+    }
+
     pub fn get_seed(&self) -> i64 {
         self.seed
     }
 
+    pub fn set_seed(&mut self, seed: i64) {
+        self.seed = seed;
+    }
+
     #[inline]
     pub fn get_spawn(&self) -> [i32; 3] {
         self.spawn
     }
 
+    pub fn set_spawn(&mut self, spawn: [i32; 3]) {
+        self.spawn = spawn;
+    }
+
     pub fn get_time(&self) -> u64 {
         self.time
     }
@@ -275,6 +490,97 @@ impl World {
     }
 }
 
+/// The part of a player file the server keeps track of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerData {
+    pub position: [f64; 3],
+    /// Yaw and pitch in degrees.
+    pub rotation: [f32; 2],
+    pub on_ground: bool,
+}
+
+impl PlayerData {
+    pub fn from_nbt(blob: &nbt::Blob) -> std::io::Result<Self> {
+        let list = |name: &'static str| match blob.get(name) {
+            Some(nbt::Value::List(values)) => Ok(values),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Player is missing the {name} list!"),
+            )),
+        };
+        let (position, rotation) = (list("Pos")?, list("Rotation")?);
+        if position.len() != 3 || rotation.len() != 2 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Player position or rotation has the wrong length!",
+            ));
+        }
+        Ok(Self {
+            position: [
+                read_value_f64(&position[0])?,
+                read_value_f64(&position[1])?,
+                read_value_f64(&position[2])?,
+            ],
+            rotation: [read_value_f32(&rotation[0])?, read_value_f32(&rotation[1])?],
+            on_ground: blob.get("OnGround").map_or(Ok(false), read_value_bool)?,
+        })
+    }
+
+    pub fn write_nbt(&self, blob: &mut nbt::Blob) -> std::io::Result<()> {
+        blob.insert(
+            "Pos",
+            nbt::Value::List(self.position.map(nbt::Value::Double).to_vec()),
+        )?;
+        blob.insert(
+            "Rotation",
+            nbt::Value::List(self.rotation.map(nbt::Value::Float).to_vec()),
+        )?;
+        blob.insert("OnGround", nbt::Value::Byte(self.on_ground as i8))?;
+        Ok(())
+    }
+}
+
+/// A box inside a chunk, both corners are inclusive chunk local coordinates.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ChunkSection {
+    pub min: [u8; 3],
+    pub max: [u8; 3],
+}
+
+impl ChunkSection {
+    pub const FULL: Self = Self {
+        min: [0, 0, 0],
+        max: [15, 127, 15],
+    };
+
+    /// Returns the smallest section containing every chunk local position, or `None` if there are none.
+    ///
+    /// Metadata and light are sent as nibbles, so the y range is widened to start at an even and end at an odd y.
+    pub fn covering(positions: impl IntoIterator<Item = (u8, u8, u8)>) -> Option<Self> {
+        let mut positions = positions.into_iter().map(|(x, y, z)| [x, y, z]);
+        let first = positions.next()?;
+        let (mut min, mut max) = (first, first);
+        for position in positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+        min[1] &= !1;
+        max[1] |= 1;
+        Some(Self {
+            min: [min[0].min(15), min[1].min(126), min[2].min(15)],
+            max: [max[0].min(15), max[1].min(127), max[2].min(15)],
+        })
+    }
+
+    pub fn volume(&self) -> usize {
+        (0..3)
+            .map(|axis| (self.max[axis] - self.min[axis]) as usize + 1)
+            .product()
+    }
+}
+
 pub struct Chunk {
     chunk_x: i32,
     chunk_z: i32,
@@ -285,22 +591,46 @@ pub struct Chunk {
     block_light: Vec<u8>,
     sky_light: Vec<u8>,
     height_map: Vec<u8>,
+    tile_entities: Vec<TileEntity>,
 }
 
 impl Chunk {
-    pub fn load(world_path: &Path, x: i32, z: i32) -> std::io::Result<Self> {
-        let (x_string, z_string) = (base36_from_i32(x), base36_from_i32(z));
-        let (high_level, low_level) = (
-            base36_from_u64((((x as i8) as u8) % 64) as u64),
-            base36_from_u64((((z as i8) as u8) % 64) as u64),
-        );
-        let file_name = format!("c.{x_string}.{z_string}.dat");
-        let file_path = world_path.join(high_level).join(low_level).join(file_name);
+    /// Creates a chunk filled with air and full sky light.
+    pub fn empty(x: i32, z: i32) -> Self {
+        Self {
+            chunk_x: x,
+            chunk_z: z,
+            terrain_populated: true,
+            last_update: 0,
+            blocks: vec![0; 16 * 16 * 128],
+            data: vec![0; 16 * 16 * 64],
+            block_light: vec![0; 16 * 16 * 64],
+            sky_light: vec![0xFF; 16 * 16 * 64],
+            height_map: vec![0; 16 * 16],
+            tile_entities: Vec::new(),
+        }
+    }
 
-        let (terrain_populated, last_update, blocks, data, block_light, sky_light, height_map) = {
-            let mut file = std::fs::File::open(file_path)?;
-            let blob = nbt::Blob::from_gzip_reader(&mut file)?;
-            let data = blob.get("Level").unwrap();
+    pub fn load(storage: &dyn ChunkStorage, x: i32, z: i32) -> std::io::Result<Self> {
+        Self::from_nbt(&storage.read_chunk(x, z)?, x, z)
+    }
+
+    /// Parses a chunk from its NBT representation, which is shared by all storage formats.
+    pub fn from_nbt(blob: &nbt::Blob, x: i32, z: i32) -> std::io::Result<Self> {
+        let (
+            terrain_populated,
+            last_update,
+            blocks,
+            data,
+            block_light,
+            sky_light,
+            height_map,
+            tile_entities,
+        ) = {
+            let data = blob.get("Level").ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Did not find Level field in chunk!",
+            ))?;
 
             if let nbt::Value::Compound(v) = data {
                 let terrain_populated = read_value_bool(v.get("TerrainPopulated").unwrap())?;
@@ -310,6 +640,17 @@ impl Chunk {
                 let block_light = read_value_byte_array(v.get("BlockLight").unwrap())?;
                 let sky_light = read_value_byte_array(v.get("SkyLight").unwrap())?;
                 let height_map = read_value_byte_array(v.get("HeightMap").unwrap())?;
+                // Tile entities that are not compounds can not be placed anywhere and are dropped.
+                let tile_entities = match v.get("TileEntities") {
+                    Some(nbt::Value::List(values)) => values
+                        .iter()
+                        .filter_map(|value| match value {
+                            nbt::Value::Compound(tile_entity) => Some(tile_entity.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
                 (
                     terrain_populated,
                     last_update,
@@ -318,6 +659,7 @@ impl Chunk {
                     block_light,
                     sky_light,
                     height_map,
+                    tile_entities,
                 )
             } else {
                 return Err(std::io::Error::new(
@@ -337,6 +679,7 @@ impl Chunk {
             block_light,
             sky_light,
             height_map,
+            tile_entities,
         })
     }
 
@@ -365,67 +708,164 @@ impl Chunk {
     /// returns: Option<u8>
     pub fn set_block(&mut self, x: u8, y: u8, z: u8, block_id: u8) -> Option<u8> {
         let index = (y as i32 + ((z as i32) * 128 + ((x as i32) * 128 * 16))) as usize;
-        self.blocks.get_mut(index).map(|v| {
+        let old = self.blocks.get_mut(index).map(|v| {
             let tmp = *v;
             *v = block_id;
             tmp
+        })?;
+        self.update_height(x, y, z);
+        Some(old)
+    }
+
+    /// Returns the metadata nibble of the block at the chunk local coordinates or `None` if they are out of bounds.
+    pub fn get_data(&self, x: u8, y: u8, z: u8) -> Option<u8> {
+        let index = y as usize + z as usize * 128 + x as usize * 128 * 16;
+        let byte = self.data.get(index >> 1)?;
+        Some(if index & 1 == 0 {
+            byte & 0x0F
+        } else {
+            byte >> 4
         })
     }
 
-    pub fn save(&self, world_path: &Path) -> std::io::Result<()> {
-        let (x_string, z_string) = (self.chunk_x, self.chunk_z);
-        let (high_level, low_level) = (
-            base36_from_u64(self.chunk_x as u64 % 64),
-            base36_from_u64(self.chunk_z as u64 % 64),
+    /// Overwrites the metadata nibble of the block at the chunk local coordinates and returns the old one.
+    pub fn set_data(&mut self, x: u8, y: u8, z: u8, data: u8) -> Option<u8> {
+        let index = y as usize + z as usize * 128 + x as usize * 128 * 16;
+        let byte = self.data.get_mut(index >> 1)?;
+        let old = if index & 1 == 0 {
+            *byte & 0x0F
+        } else {
+            *byte >> 4
+        };
+        *byte = if index & 1 == 0 {
+            (*byte & 0xF0) | (data & 0x0F)
+        } else {
+            (*byte & 0x0F) | (data << 4)
+        };
+        Some(old)
+    }
+
+    /// Keeps the height map of the column at `x` and `z` correct after the block at `y` changed.
+    fn update_height(&mut self, x: u8, y: u8, z: u8) {
+        let Some(height) = self.height_map.get_mut(x as usize + z as usize * 16) else {
+            return;
+        };
+        let is_opaque = |id: u8| registry::block::get(id).is_none_or(|b| b.opacity > 0);
+        let column = x as usize * 128 * 16 + z as usize * 128;
+        if is_opaque(self.blocks[column + y as usize]) {
+            *height = (*height).max(y + 1);
+        } else if y + 1 == *height {
+            *height = (0..y)
+                .rev()
+                .find(|y| is_opaque(self.blocks[column + *y as usize]))
+                .map_or(0, |y| y + 1);
+        }
+    }
+
+    pub fn save(&self, storage: &dyn ChunkStorage) -> std::io::Result<()> {
+        storage.write_chunk(self.chunk_x, self.chunk_z, &self.to_nbt()?)
+    }
+
+    pub fn to_nbt(&self) -> std::io::Result<nbt::Blob> {
+        let vu8_vi8 = |x: &Vec<u8>| -> Vec<i8> {
+            unsafe {
+                let slice = std::ptr::slice_from_raw_parts(x.as_ptr() as *const i8, x.len());
+                Vec::from(slice.as_ref().unwrap())
+            }
+        };
+
+        let mut compound = HashMap::with_capacity(10);
+        compound.insert("xPos".to_string(), nbt::Value::Int(self.chunk_x));
+        compound.insert("zPos".to_string(), nbt::Value::Int(self.chunk_z));
+        compound.insert(
+            "TerrainPopulated".to_string(),
+            nbt::Value::Byte(self.terrain_populated as i8),
+        );
+        compound.insert(
+            "LastUpdate".to_string(),
+            nbt::Value::Long(self.last_update as i64),
+        );
+        compound.insert(
+            "Blocks".to_string(),
+            nbt::Value::ByteArray(vu8_vi8(&self.blocks)),
+        );
+        compound.insert(
+            "Data".to_string(),
+            nbt::Value::ByteArray(vu8_vi8(&self.data)),
+        );
+        compound.insert(
+            "BlockLight".to_string(),
+            nbt::Value::ByteArray(vu8_vi8(&self.block_light)),
+        );
+        compound.insert(
+            "SkyLight".to_string(),
+            nbt::Value::ByteArray(vu8_vi8(&self.sky_light)),
+        );
+        compound.insert(
+            "HeightMap".to_string(),
+            nbt::Value::ByteArray(vu8_vi8(&self.height_map)),
+        );
+        compound.insert(
+            "TileEntities".to_string(),
+            nbt::Value::List(
+                self.tile_entities
+                    .iter()
+                    .cloned()
+                    .map(nbt::Value::Compound)
+                    .collect(),
+            ),
         );
-        let file_name = format!("c.{x_string}.{z_string}.dat");
-        let file_path = world_path.join(high_level).join(low_level).join(file_name);
-
-        {
-            let vu8_vi8 = |x: &Vec<u8>| -> Vec<i8> {
-                unsafe {
-                    let slice = std::ptr::slice_from_raw_parts(x.as_ptr() as *const i8, x.len());
-                    Vec::from(slice.as_ref().unwrap())
-                }
-            };
 
-            let mut file = std::fs::File::create(file_path)?;
-            let mut compound = HashMap::with_capacity(7);
-            compound.insert(
-                "TerrainPopulated".to_string(),
-                nbt::Value::Byte(self.terrain_populated as i8),
-            );
-            compound.insert(
-                "LastUpdate".to_string(),
-                nbt::Value::Long(self.last_update as i64),
-            );
-            compound.insert(
-                "Blocks".to_string(),
-                nbt::Value::ByteArray(vu8_vi8(&self.blocks)),
-            );
-            compound.insert(
-                "Data".to_string(),
-                nbt::Value::ByteArray(vu8_vi8(&self.data)),
-            );
-            compound.insert(
-                "BlockLight".to_string(),
-                nbt::Value::ByteArray(vu8_vi8(&self.block_light)),
-            );
-            compound.insert(
-                "SkyLight".to_string(),
-                nbt::Value::ByteArray(vu8_vi8(&self.sky_light)),
-            );
-            compound.insert(
-                "HeightMap".to_string(),
-                nbt::Value::ByteArray(vu8_vi8(&self.height_map)),
-            );
+        let mut blob = nbt::Blob::new();
+        blob.insert("Level", nbt::Value::Compound(compound))?;
+        Ok(blob)
+    }
 
-            let mut blob = nbt::Blob::new();
-            blob.insert("Level", nbt::Value::Compound(compound))?;
-            blob.to_gzip_writer(&mut file)?;
-        }
+    pub fn get_position(&self) -> (i32, i32) {
+        (self.chunk_x, self.chunk_z)
+    }
 
-        Ok(())
+    /// Raw block ids, indexed by `y + z * 128 + x * 128 * 16`.
+    pub fn blocks(&self) -> &[u8] {
+        &self.blocks
+    }
+
+    /// Raw block metadata, packed as nibbles in the same order as [`Chunk::blocks`].
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The lowest y of each column that still receives full sky light, indexed by `x + z * 16`.
+    pub fn height_map(&self) -> &[u8] {
+        &self.height_map
+    }
+
+    pub fn tile_entities(&self) -> &[TileEntity] {
+        &self.tile_entities
+    }
+
+    /// Returns the tile entity at the world coordinates `position`.
+    pub fn tile_entity(&self, position: [i32; 3]) -> Option<&TileEntity> {
+        self.tile_entities
+            .iter()
+            .find(|tile_entity| tile_entity_position(tile_entity) == Some(position))
+    }
+
+    /// Adds a tile entity, replacing the one at the same position.
+    pub fn set_tile_entity(&mut self, tile_entity: TileEntity) {
+        let position = tile_entity_position(&tile_entity);
+        self.tile_entities
+            .retain(|other| tile_entity_position(other) != position);
+        self.tile_entities.push(tile_entity);
+    }
+
+    /// Removes the tile entity at the world coordinates `position` and returns it.
+    pub fn remove_tile_entity(&mut self, position: [i32; 3]) -> Option<TileEntity> {
+        let index = self
+            .tile_entities
+            .iter()
+            .position(|tile_entity| tile_entity_position(tile_entity) == Some(position))?;
+        Some(self.tile_entities.swap_remove(index))
     }
 
     pub fn is_inside_chunk(&self, x: i32, z: i32) -> bool {
@@ -434,10 +874,12 @@ impl Chunk {
     }
 
     pub fn get_compressed_data(&self) -> (i32, Vec<u8>) {
-        let mut to_compress = self.blocks.clone();
-        to_compress.extend_from_slice(&self.data);
-        to_compress.extend_from_slice(&self.block_light);
-        to_compress.extend_from_slice(&self.sky_light);
+        self.get_compressed_section(&ChunkSection::FULL)
+    }
+
+    /// Compresses the blocks, metadata and light inside `section`, the payload of a `MapChunkPacket` covering it.
+    pub fn get_compressed_section(&self, section: &ChunkSection) -> (i32, Vec<u8>) {
+        let to_compress = self.section_data(section);
         let mut len = unsafe { libz_sys::compressBound(to_compress.len().try_into().unwrap()) };
         let mut compressed_bytes = vec![0u8; len as usize];
         unsafe {
@@ -450,4 +892,54 @@ impl Chunk {
         }
         (len as i32, compressed_bytes)
     }
+
+    /// Serializes `section` like the client reads it: the blocks of every column, x changing the slowest,
+    /// followed by the metadata, block light and sky light nibbles in the same order.
+    pub fn section_data(&self, section: &ChunkSection) -> Vec<u8> {
+        let [min_x, min_y, min_z] = section.min.map(|c| c as usize);
+        let [max_x, max_y, max_z] = section.max.map(|c| c as usize);
+        let height = max_y - min_y + 1;
+        let columns = (min_x..=max_x)
+            .flat_map(|x| (min_z..=max_z).map(move |z| x * 128 * 16 + z * 128 + min_y))
+            .collect::<Vec<_>>();
+        let mut bytes = Vec::with_capacity(section.volume() * 5 / 2);
+        for column in &columns {
+            bytes.extend_from_slice(&self.blocks[*column..*column + height]);
+        }
+        for nibbles in [&self.data, &self.block_light, &self.sky_light] {
+            for column in &columns {
+                bytes.extend_from_slice(&nibbles[column / 2..(column + height) / 2]);
+            }
+        }
+        bytes
+    }
+}
+
+#[test]
+fn test_chunk_section_data() {
+    let mut chunk = Chunk::empty(0, 0);
+    chunk.set_block(3, 9, 4, 1);
+    chunk.set_data(3, 9, 4, 5);
+    chunk.set_block(5, 12, 4, 4);
+
+    // The full section is the layout of a whole chunk.
+    let mut full = chunk.blocks.clone();
+    for nibbles in [&chunk.data, &chunk.block_light, &chunk.sky_light] {
+        full.extend_from_slice(nibbles);
+    }
+    assert_eq!(chunk.section_data(&ChunkSection::FULL), full);
+
+    let section = ChunkSection::covering([(3, 9, 4), (5, 12, 4)]).unwrap();
+    assert_eq!(section.min, [3, 8, 4]);
+    assert_eq!(section.max, [5, 13, 4]);
+    assert_eq!(section.volume(), 18);
+    let bytes = chunk.section_data(&section);
+    assert_eq!(bytes.len(), 18 + 3 * 9);
+    // Columns x = 3, 4 and 5, each from y = 8 to y = 13.
+    assert_eq!(
+        &bytes[..18],
+        &[0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0]
+    );
+    // y = 9 is the high nibble of the first metadata byte.
+    assert_eq!(bytes[18], 0x50);
 }
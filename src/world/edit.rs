@@ -0,0 +1,329 @@
+use crate::world::{set_tile_entity_position, tile_entity_position, TileEntity, World};
+
+/// A single block that is about to be written, or was written, by a bulk edit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BlockChange {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub id: u8,
+    pub data: u8,
+}
+
+/// An axis aligned box of blocks, both corners are inclusive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cuboid {
+    pub min: [i32; 3],
+    pub max: [i32; 3],
+}
+
+impl Cuboid {
+    /// Creates the cuboid spanned by two opposite corners, clamped to the height of the world.
+    pub fn new(a: [i32; 3], b: [i32; 3]) -> Self {
+        let (min_y, max_y) = (a[1].min(b[1]).clamp(0, 127), a[1].max(b[1]).clamp(0, 127));
+        Self {
+            min: [a[0].min(b[0]), min_y, a[2].min(b[2])],
+            max: [a[0].max(b[0]), max_y, a[2].max(b[2])],
+        }
+    }
+
+    /// Number of blocks along the x, y and z axis.
+    pub fn size(&self) -> [usize; 3] {
+        [0, 1, 2].map(|axis| (self.max[axis] - self.min[axis] + 1) as usize)
+    }
+
+    pub fn volume(&self) -> usize {
+        self.size().iter().product()
+    }
+
+    /// Iterates over every position inside the cuboid, x changing the fastest and y the slowest.
+    pub fn positions(&self) -> impl Iterator<Item = [i32; 3]> {
+        let (min, max) = (self.min, self.max);
+        (min[1]..=max[1]).flat_map(move |y| {
+            (min[2]..=max[2]).flat_map(move |z| (min[0]..=max[0]).map(move |x| [x, y, z]))
+        })
+    }
+
+    /// Whether `position` lies on one of the four vertical sides of the cuboid.
+    pub fn is_wall(&self, position: [i32; 3]) -> bool {
+        let [x, _, z] = position;
+        x == self.min[0] || x == self.max[0] || z == self.min[2] || z == self.max[2]
+    }
+}
+
+/// Fills the whole cuboid with a single block.
+pub fn set(cuboid: &Cuboid, id: u8, data: u8) -> Vec<BlockChange> {
+    cuboid
+        .positions()
+        .map(|[x, y, z]| BlockChange { x, y, z, id, data })
+        .collect()
+}
+
+/// Fills the four vertical sides of the cuboid with a single block.
+pub fn walls(cuboid: &Cuboid, id: u8, data: u8) -> Vec<BlockChange> {
+    cuboid
+        .positions()
+        .filter(|position| cuboid.is_wall(*position))
+        .map(|[x, y, z]| BlockChange { x, y, z, id, data })
+        .collect()
+}
+
+/// Replaces every block with the id `from` inside the cuboid, with a matching metadata if `from_data` is given.
+pub fn replace(
+    world: &mut World,
+    cuboid: &Cuboid,
+    (from, from_data): (u8, Option<u8>),
+    (id, data): (u8, u8),
+) -> Vec<BlockChange> {
+    cuboid
+        .positions()
+        .filter(|[x, y, z]| {
+            world
+                .get_block_and_data(*x, *y, *z)
+                .is_some_and(|(block, block_data)| {
+                    block == from && from_data.is_none_or(|d| d == block_data)
+                })
+        })
+        .map(|[x, y, z]| BlockChange { x, y, z, id, data })
+        .collect()
+}
+
+/// Writes `changes` into the world and returns what they replaced.
+///
+/// Applying the returned changes undoes the edit. Changes outside of the world or in chunks that do not exist are skipped.
+pub fn apply(world: &mut World, changes: &[BlockChange]) -> Vec<BlockChange> {
+    let mut previous = changes
+        .iter()
+        .filter_map(|change| {
+            let BlockChange { x, y, z, id, data } = *change;
+            let (old_id, old_data) = world.set_block_and_data(x, y, z, id, data)?;
+            Some(BlockChange {
+                id: old_id,
+                data: old_data,
+                ..*change
+            })
+        })
+        .collect::<Vec<_>>();
+    // If a position was changed twice the oldest state has to be written last.
+    previous.reverse();
+    previous
+}
+
+/// Blocks copied out of a world, stored like the `.schematic` format: indexed by `(y * length + z) * width + x`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clipboard {
+    width: usize,
+    height: usize,
+    length: usize,
+    blocks: Vec<u8>,
+    data: Vec<u8>,
+    /// Position of the corner with the smallest coordinates relative to where the clipboard was copied from.
+    pub offset: [i32; 3],
+    /// Tile entities with positions relative to the corner with the smallest coordinates.
+    pub tile_entities: Vec<TileEntity>,
+}
+
+impl Clipboard {
+    /// Creates a clipboard from raw block ids and metadata, errors if their length does not match the size.
+    pub fn from_raw(
+        [width, height, length]: [usize; 3],
+        blocks: Vec<u8>,
+        data: Vec<u8>,
+        offset: [i32; 3],
+    ) -> std::io::Result<Self> {
+        if blocks.len() != width * height * length || data.len() != blocks.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Block data does not match the clipboard size!",
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            length,
+            blocks,
+            data,
+            offset,
+            tile_entities: Vec::new(),
+        })
+    }
+
+    /// Copies the blocks and tile entities inside `cuboid`, remembering their position relative to `origin`.
+    ///
+    /// Blocks in chunks that do not exist are copied as air.
+    pub fn copy(world: &mut World, cuboid: &Cuboid, origin: [i32; 3]) -> Self {
+        let (blocks, data) = cuboid
+            .positions()
+            .map(|[x, y, z]| world.get_block_and_data(x, y, z).unwrap_or_default())
+            .unzip();
+        let mut tile_entities = Vec::new();
+        for chunk_x in cuboid.min[0] >> 4..=cuboid.max[0] >> 4 {
+            for chunk_z in cuboid.min[2] >> 4..=cuboid.max[2] >> 4 {
+                let Ok(chunk) = world.get_chunk(chunk_x, chunk_z) else {
+                    continue;
+                };
+                for tile_entity in chunk.read().unwrap().tile_entities() {
+                    let Some(position) = tile_entity_position(tile_entity) else {
+                        continue;
+                    };
+                    let inside = (0..3).all(|axis| {
+                        (cuboid.min[axis]..=cuboid.max[axis]).contains(&position[axis])
+                    });
+                    if inside {
+                        let mut tile_entity = tile_entity.clone();
+                        let relative = [0, 1, 2].map(|axis| position[axis] - cuboid.min[axis]);
+                        set_tile_entity_position(&mut tile_entity, relative);
+                        tile_entities.push(tile_entity);
+                    }
+                }
+            }
+        }
+        let [width, height, length] = cuboid.size();
+        Self {
+            width,
+            height,
+            length,
+            blocks,
+            data,
+            offset: [0, 1, 2].map(|axis| cuboid.min[axis] - origin[axis]),
+            tile_entities,
+        }
+    }
+
+    /// Number of blocks along the x, y and z axis.
+    pub fn size(&self) -> [usize; 3] {
+        [self.width, self.height, self.length]
+    }
+
+    pub fn blocks(&self) -> &[u8] {
+        &self.blocks
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the changes that paste the clipboard relative to `origin`.
+    pub fn paste(&self, origin: [i32; 3]) -> Vec<BlockChange> {
+        let [x0, y0, z0] = [0, 1, 2].map(|axis| origin[axis] + self.offset[axis]);
+        (0..self.height)
+            .flat_map(|y| {
+                (0..self.length).flat_map(move |z| (0..self.width).map(move |x| (x, y, z)))
+            })
+            .zip(self.blocks.iter().zip(&self.data))
+            .map(|((x, y, z), (id, data))| BlockChange {
+                x: x0 + x as i32,
+                y: y0 + y as i32,
+                z: z0 + z as i32,
+                id: *id,
+                data: *data,
+            })
+            .collect()
+    }
+
+    /// Returns the tile entities of the clipboard moved to where [`Clipboard::paste`] puts their blocks.
+    ///
+    /// They have to be stored after the blocks, changing a block removes its tile entity.
+    pub fn paste_tile_entities(&self, origin: [i32; 3]) -> Vec<TileEntity> {
+        self.tile_entities
+            .iter()
+            .filter_map(|tile_entity| {
+                let relative = tile_entity_position(tile_entity)?;
+                let mut tile_entity = tile_entity.clone();
+                let position =
+                    [0, 1, 2].map(|axis| origin[axis] + self.offset[axis] + relative[axis]);
+                set_tile_entity_position(&mut tile_entity, position);
+                Some(tile_entity)
+            })
+            .collect()
+    }
+
+    /// Rotates the clipboard clockwise around the y axis of the position it was copied from.
+    ///
+    /// Only positions are rotated, the metadata of directional blocks like stairs is kept as is.
+    pub fn rotate(&mut self, quarter_turns: u8) {
+        for _ in 0..quarter_turns % 4 {
+            let (width, length) = (self.length, self.width);
+            let mut blocks = vec![0; self.blocks.len()];
+            let mut data = vec![0; self.data.len()];
+            for y in 0..self.height {
+                for z in 0..self.length {
+                    for x in 0..self.width {
+                        let from = (y * self.length + z) * self.width + x;
+                        // Looking down, east turns into south: (x, z) becomes (-z, x).
+                        let to = (y * length + x) * width + (self.length - 1 - z);
+                        blocks[to] = self.blocks[from];
+                        data[to] = self.data[from];
+                    }
+                }
+            }
+            for tile_entity in &mut self.tile_entities {
+                if let Some([x, y, z]) = tile_entity_position(tile_entity) {
+                    set_tile_entity_position(tile_entity, [self.length as i32 - 1 - z, y, x]);
+                }
+            }
+            let [offset_x, offset_y, offset_z] = self.offset;
+            self.offset = [-(offset_z + self.length as i32 - 1), offset_y, offset_x];
+            (self.width, self.length, self.blocks, self.data) = (width, length, blocks, data);
+        }
+    }
+}
+
+#[test]
+fn test_edit_undo_and_rotate() {
+    use crate::world::memory::MemoryStorage;
+    use crate::world::Chunk;
+
+    let mut world = World::create(Box::new(MemoryStorage::new()), 0, [0, 64, 0]);
+    world.insert_chunk(Chunk::empty(0, 0));
+    world.insert_chunk(Chunk::empty(-1, 0));
+
+    let cuboid = Cuboid::new([-2, 10, 3], [1, 8, 5]);
+    assert_eq!(cuboid.size(), [4, 3, 3]);
+    let undo = apply(&mut world, &walls(&cuboid, 35, 14));
+    assert_eq!(undo.len(), 30);
+    assert_eq!(world.get_block_and_data(-2, 9, 4), Some((35, 14)));
+    assert_eq!(world.get_block_and_data(-1, 9, 4), Some((0, 0)));
+    apply(&mut world, &undo);
+    assert_eq!(world.get_block_and_data(-2, 9, 4), Some((0, 0)));
+
+    // An L shape along x, pointing south after a quarter turn.
+    apply(
+        &mut world,
+        &[
+            BlockChange {
+                x: 1,
+                y: 0,
+                z: 0,
+                id: 1,
+                data: 0,
+            },
+            BlockChange {
+                x: 2,
+                y: 0,
+                z: 0,
+                id: 2,
+                data: 0,
+            },
+            BlockChange {
+                x: 2,
+                y: 0,
+                z: 1,
+                id: 3,
+                data: 0,
+            },
+        ],
+    );
+    let mut clipboard = Clipboard::copy(&mut world, &Cuboid::new([1, 0, 0], [2, 0, 1]), [0, 0, 0]);
+    clipboard.rotate(1);
+    assert_eq!(clipboard.size(), [2, 1, 2]);
+    let pasted = clipboard
+        .paste([8, 0, 8])
+        .into_iter()
+        .filter(|change| change.id != 0)
+        .map(|change| (change.x, change.z, change.id))
+        .collect::<Vec<_>>();
+    assert_eq!(pasted, vec![(8, 9, 1), (7, 10, 3), (8, 10, 2)]);
+    clipboard.rotate(3);
+    assert_eq!(clipboard.offset, [1, 0, 0]);
+}
@@ -0,0 +1,80 @@
+//! Terrain for chunks that were never saved.
+use crate::world::Chunk;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Layers of the flat generator from the bottom up, as block id and thickness.
+const FLAT_LAYERS: [(u8, u8); 4] = [(7, 1), (1, 60), (3, 2), (2, 1)];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+    /// Chunks that do not exist stay missing.
+    #[default]
+    None,
+    /// Bedrock, stone, dirt and grass up to y 63.
+    Flat,
+}
+
+impl FromStr for Generator {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "none" => Ok(Self::None),
+            "flat" => Ok(Self::Flat),
+            _ => Err(format!("Unknown generator: {name}")),
+        }
+    }
+}
+
+impl Display for Generator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Flat => write!(f, "flat"),
+        }
+    }
+}
+
+impl Generator {
+    pub fn generate(&self, x: i32, z: i32) -> Option<Chunk> {
+        match self {
+            Self::None => None,
+            Self::Flat => {
+                let mut chunk = Chunk::empty(x, z);
+                let mut y = 0;
+                for (block, thickness) in FLAT_LAYERS {
+                    for _ in 0..thickness {
+                        for (x, z) in (0..16).flat_map(|x| (0..16).map(move |z| (x, z))) {
+                            chunk.set_block(x, y, z, block);
+                        }
+                        y += 1;
+                    }
+                }
+                Some(chunk)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_flat_generator() {
+    use crate::registry;
+    use crate::world::memory::MemoryStorage;
+    use crate::world::World;
+
+    assert_eq!("flat".parse::<Generator>(), Ok(Generator::Flat));
+    assert_eq!(Generator::Flat.to_string(), "flat");
+    assert!(Generator::None.generate(0, 0).is_none());
+    let chunk = Generator::Flat.generate(2, -3).unwrap();
+    assert_eq!(chunk.get_position(), (2, -3));
+    assert_eq!(chunk.get_block(5, 0, 5), Some(7));
+    assert_eq!(chunk.get_block(5, 63, 5), Some(2));
+    assert_eq!(chunk.get_block(5, 64, 5), Some(registry::block::AIR));
+    assert_eq!(chunk.height_map()[0], 64);
+
+    let mut world = World::create(Box::new(MemoryStorage::new()), 0, [0, 64, 0]);
+    assert!(world.get_chunk(0, 0).is_err());
+    world.set_generator(Generator::Flat);
+    assert_eq!(world.get_block(-20, 62, 7), Some(3));
+}
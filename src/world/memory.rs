@@ -0,0 +1,143 @@
+use crate::world::storage::{ChunkStorage, WorldStorage};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Keeps a whole world in memory, which is useful for tests and throwaway worlds.
+///
+/// Clones share the same data, so a clone can be used to inspect what a `World` saved.
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    level: Arc<RwLock<Option<nbt::Blob>>>,
+    players: Arc<RwLock<HashMap<String, nbt::Blob>>>,
+    chunks: MemoryChunkStorage,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl WorldStorage for MemoryStorage {
+    fn chunks(&self) -> &dyn ChunkStorage {
+        &self.chunks
+    }
+
+    fn read_level(&self) -> std::io::Result<nbt::Blob> {
+        self.level
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Level was never saved!",
+            ))
+    }
+
+    fn write_level(&self, blob: &nbt::Blob) -> std::io::Result<()> {
+        *self.level.write().unwrap() = Some(blob.clone());
+        Ok(())
+    }
+
+    fn read_player(&self, name: &str) -> std::io::Result<nbt::Blob> {
+        self.players
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Player was never saved!",
+            ))
+    }
+
+    fn write_player(&self, name: &str, blob: &nbt::Blob) -> std::io::Result<()> {
+        self.players
+            .write()
+            .unwrap()
+            .insert(name.to_string(), blob.clone());
+        Ok(())
+    }
+
+    fn size_on_disk(&self) -> std::io::Result<u64> {
+        Ok(0)
+    }
+}
+
+/// Every chunk with the write counter value of its last write, which serves as its timestamp.
+type Chunks = HashMap<(i32, i32), (nbt::Blob, u64)>;
+
+#[derive(Clone, Default)]
+pub struct MemoryChunkStorage {
+    chunks: Arc<RwLock<Chunks>>,
+    writes: Arc<AtomicU64>,
+}
+
+impl ChunkStorage for MemoryChunkStorage {
+    fn read_chunk(&self, x: i32, z: i32) -> std::io::Result<nbt::Blob> {
+        self.chunks
+            .read()
+            .unwrap()
+            .get(&(x, z))
+            .map(|(blob, _)| blob.clone())
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Chunk was never saved!",
+            ))
+    }
+
+    fn write_chunk(&self, x: i32, z: i32, blob: &nbt::Blob) -> std::io::Result<()> {
+        let timestamp = self.writes.fetch_add(1, Ordering::Relaxed) + 1;
+        self.chunks
+            .write()
+            .unwrap()
+            .insert((x, z), (blob.clone(), timestamp));
+        Ok(())
+    }
+
+    fn list_chunks(&self) -> std::io::Result<Vec<(i32, i32)>> {
+        Ok(self.chunks.read().unwrap().keys().copied().collect())
+    }
+
+    fn chunk_timestamp(&self, x: i32, z: i32) -> std::io::Result<u64> {
+        self.chunks
+            .read()
+            .unwrap()
+            .get(&(x, z))
+            .map(|(_, timestamp)| *timestamp)
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Chunk was never saved!",
+            ))
+    }
+}
+
+#[test]
+fn test_memory_world_round_trip() {
+    use crate::world::{Chunk, PlayerData, World};
+
+    let storage = MemoryStorage::new();
+    let mut world = World::create(Box::new(storage.clone()), 42, [8, 64, 8]);
+    world.insert_chunk(Chunk::empty(0, -1));
+    world
+        .get_chunk(0, -1)
+        .unwrap()
+        .write()
+        .unwrap()
+        .set_block(1, 2, 3, 4);
+    let player = PlayerData {
+        position: [1.5, 65.0, -3.5],
+        rotation: [90.0, 0.0],
+        on_ground: true,
+    };
+    world.save_player("Notch", &player).unwrap();
+    world.close().unwrap();
+
+    let mut world = World::load(Box::new(storage.clone())).unwrap();
+    assert_eq!(world.get_seed(), 42);
+    assert_eq!(world.get_spawn(), [8, 64, 8]);
+    assert_eq!(world.get_block(1, 2, -13), Some(4));
+    assert_eq!(world.load_player("Notch").unwrap(), player);
+    assert_eq!(storage.chunks().list_chunks().unwrap(), vec![(0, -1)]);
+}
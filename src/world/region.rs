@@ -0,0 +1,297 @@
+use crate::world::storage::{self, ChunkStorage};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const SECTOR_SIZE: usize = 4096;
+const HEADER_SECTORS: usize = 2;
+const CHUNKS_PER_REGION: usize = 32 * 32;
+
+const COMPRESSION_GZIP: u8 = 1;
+const COMPRESSION_ZLIB: u8 = 2;
+
+/// Stores chunks in McRegion files, each holding 32x32 chunks in 4KiB sectors.
+pub struct RegionStorage {
+    path: PathBuf,
+    // Region files are read and patched in place, so concurrent writers would corrupt the sector table.
+    lock: Mutex<()>,
+}
+
+impl RegionStorage {
+    pub fn new<P: AsRef<Path>>(region_path: P) -> Self {
+        Self {
+            path: region_path.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn region_path(&self, x: i32, z: i32) -> PathBuf {
+        self.path.join(format!("r.{}.{}.mcr", x >> 5, z >> 5))
+    }
+}
+
+impl ChunkStorage for RegionStorage {
+    fn read_chunk(&self, x: i32, z: i32) -> std::io::Result<nbt::Blob> {
+        let _guard = self.lock.lock().unwrap();
+        let mut region = RegionFile::open(&self.region_path(x, z), false)?;
+        region.read(chunk_index(x, z))?.ok_or(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Chunk does not exist in region!",
+        ))
+    }
+
+    fn write_chunk(&self, x: i32, z: i32, blob: &nbt::Blob) -> std::io::Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        std::fs::create_dir_all(&self.path)?;
+        let mut region = RegionFile::open(&self.region_path(x, z), true)?;
+        region.write(chunk_index(x, z), blob)
+    }
+
+    fn list_chunks(&self) -> std::io::Result<Vec<(i32, i32)>> {
+        let mut chunks = Vec::new();
+        self.each_region(|region_x, region_z, region| {
+            for index in (0..CHUNKS_PER_REGION).filter(|i| region.offsets[*i] != 0) {
+                chunks.push(chunk_coordinates(region_x, region_z, index));
+            }
+        })?;
+        Ok(chunks)
+    }
+
+    fn chunk_timestamp(&self, x: i32, z: i32) -> std::io::Result<u64> {
+        let _guard = self.lock.lock().unwrap();
+        let region = RegionFile::open(&self.region_path(x, z), false)?;
+        let index = chunk_index(x, z);
+        if region.offsets[index] == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Chunk does not exist in region!",
+            ));
+        }
+        Ok(region.timestamps[index] as u64)
+    }
+
+    fn chunk_timestamps(&self) -> std::io::Result<HashMap<(i32, i32), u64>> {
+        let mut timestamps = HashMap::new();
+        self.each_region(|region_x, region_z, region| {
+            for index in (0..CHUNKS_PER_REGION).filter(|i| region.offsets[*i] != 0) {
+                let chunk = chunk_coordinates(region_x, region_z, index);
+                timestamps.insert(chunk, region.timestamps[index] as u64);
+            }
+        })?;
+        Ok(timestamps)
+    }
+}
+
+impl RegionStorage {
+    /// Opens every region file once and hands its header to `f`.
+    fn each_region(&self, mut f: impl FnMut(i32, i32, &RegionFile)) -> std::io::Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        if !self.path.is_dir() {
+            return Ok(());
+        }
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let parts = name.split('.').collect::<Vec<_>>();
+            let ["r", region_x, region_z, "mcr"] = parts.as_slice() else {
+                continue;
+            };
+            let (Ok(region_x), Ok(region_z)) = (region_x.parse::<i32>(), region_z.parse::<i32>())
+            else {
+                continue;
+            };
+            f(region_x, region_z, &RegionFile::open(&entry.path(), false)?);
+        }
+        Ok(())
+    }
+}
+
+fn chunk_index(x: i32, z: i32) -> usize {
+    ((x & 31) + (z & 31) * 32) as usize
+}
+
+/// Inverse of [`chunk_index`] for the region at `region_x` and `region_z`.
+fn chunk_coordinates(region_x: i32, region_z: i32, index: usize) -> (i32, i32) {
+    (
+        region_x * 32 + (index % 32) as i32,
+        region_z * 32 + (index / 32) as i32,
+    )
+}
+
+struct RegionFile {
+    file: File,
+    /// Location of each chunk, the upper 24 bits are the sector offset, the lower 8 bits the sector count.
+    offsets: [u32; CHUNKS_PER_REGION],
+    timestamps: [u32; CHUNKS_PER_REGION],
+    used_sectors: Vec<bool>,
+}
+
+impl RegionFile {
+    fn open(path: &Path, create: bool) -> std::io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(create)
+            .create(create)
+            .truncate(false)
+            .open(path)?;
+        let len = file.metadata()?.len() as usize;
+
+        let mut offsets = [0u32; CHUNKS_PER_REGION];
+        let mut timestamps = [0u32; CHUNKS_PER_REGION];
+        if len < HEADER_SECTORS * SECTOR_SIZE {
+            if create {
+                file.set_len((HEADER_SECTORS * SECTOR_SIZE) as u64)?;
+            }
+        } else {
+            let mut header = vec![0u8; HEADER_SECTORS * SECTOR_SIZE];
+            file.read_exact(&mut header)?;
+            for index in 0..CHUNKS_PER_REGION {
+                let at = index * 4;
+                offsets[index] = u32::from_be_bytes(header[at..at + 4].try_into().unwrap());
+                let at = SECTOR_SIZE + index * 4;
+                timestamps[index] = u32::from_be_bytes(header[at..at + 4].try_into().unwrap());
+            }
+        }
+
+        let sector_count = len.max(HEADER_SECTORS * SECTOR_SIZE).div_ceil(SECTOR_SIZE);
+        let mut used_sectors = vec![false; sector_count];
+        used_sectors[..HEADER_SECTORS].fill(true);
+        for offset in offsets.iter().filter(|o| **o != 0) {
+            let (start, count) = ((offset >> 8) as usize, (offset & 0xFF) as usize);
+            let end = (start + count).min(sector_count);
+            used_sectors[start.min(end)..end].fill(true);
+        }
+
+        Ok(Self {
+            file,
+            offsets,
+            timestamps,
+            used_sectors,
+        })
+    }
+
+    fn read(&mut self, index: usize) -> std::io::Result<Option<nbt::Blob>> {
+        let offset = self.offsets[index];
+        if offset == 0 {
+            return Ok(None);
+        }
+        let (start, count) = ((offset >> 8) as usize, (offset & 0xFF) as usize);
+        self.file
+            .seek(SeekFrom::Start((start * SECTOR_SIZE) as u64))?;
+        let mut header = [0u8; 5];
+        self.file.read_exact(&mut header)?;
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        if length == 0 || length + 4 > count * SECTOR_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Chunk length exceeds its sectors!",
+            ));
+        }
+        let mut data = vec![0u8; length - 1];
+        self.file.read_exact(&mut data)?;
+        let mut cursor = Cursor::new(data);
+        let blob = match header[4] {
+            COMPRESSION_GZIP => nbt::Blob::from_gzip_reader(&mut cursor)?,
+            COMPRESSION_ZLIB => nbt::Blob::from_zlib_reader(&mut cursor)?,
+            other => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Unknown chunk compression: {other}"),
+                ))
+            }
+        };
+        Ok(Some(blob))
+    }
+
+    fn write(&mut self, index: usize, blob: &nbt::Blob) -> std::io::Result<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        storage::write_nbt(blob, &mut encoder)?;
+        let mut data = encoder.finish()?;
+        let needed = (data.len() + 5).div_ceil(SECTOR_SIZE);
+        if needed > u8::MAX as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Chunk is too large for a region file!",
+            ));
+        }
+
+        let offset = self.offsets[index];
+        let (old_start, old_count) = ((offset >> 8) as usize, (offset & 0xFF) as usize);
+        let start = if offset != 0 && old_count == needed {
+            old_start
+        } else {
+            let old_end = (old_start + old_count).min(self.used_sectors.len());
+            self.used_sectors[old_start.min(old_end)..old_end].fill(false);
+            self.allocate(needed)
+        };
+
+        let mut payload = Vec::with_capacity(needed * SECTOR_SIZE);
+        payload.extend_from_slice(&(data.len() as u32 + 1).to_be_bytes());
+        payload.push(COMPRESSION_ZLIB);
+        payload.append(&mut data);
+        payload.resize(needed * SECTOR_SIZE, 0);
+        self.file
+            .seek(SeekFrom::Start((start * SECTOR_SIZE) as u64))?;
+        self.file.write_all(&payload)?;
+
+        self.offsets[index] = ((start as u32) << 8) | needed as u32;
+        self.timestamps[index] = std::time::UNIX_EPOCH.elapsed().unwrap().as_secs() as u32;
+        self.file.seek(SeekFrom::Start((index * 4) as u64))?;
+        self.file.write_all(&self.offsets[index].to_be_bytes())?;
+        self.file
+            .seek(SeekFrom::Start((SECTOR_SIZE + index * 4) as u64))?;
+        self.file.write_all(&self.timestamps[index].to_be_bytes())?;
+        self.file.flush()
+    }
+
+    /// Finds the first run of `count` free sectors, growing the file if there is none.
+    fn allocate(&mut self, count: usize) -> usize {
+        let mut run = 0;
+        for (sector, used) in self.used_sectors.iter().enumerate() {
+            run = if *used { 0 } else { run + 1 };
+            if run == count {
+                let start = sector + 1 - count;
+                self.used_sectors[start..=sector].fill(true);
+                return start;
+            }
+        }
+        let start = self.used_sectors.len() - run;
+        self.used_sectors.resize(start + count, true);
+        self.used_sectors[start..].fill(true);
+        start
+    }
+}
+
+#[test]
+fn test_region_round_trip() {
+    let path = std::env::temp_dir().join(format!("betalpha-region-{}", std::process::id()));
+    let storage = RegionStorage::new(&path);
+    for (x, z, size) in [(0, 0, 10), (-1, 33, 12_000), (0, 0, 9_000), (5, 7, 1)] {
+        let mut blob = nbt::Blob::new();
+        let mut seed = size as u32;
+        let bytes = (0..size)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as i8
+            })
+            .collect();
+        blob.insert("Blocks", nbt::Value::ByteArray(bytes)).unwrap();
+        storage.write_chunk(x, z, &blob).unwrap();
+        assert_eq!(storage.read_chunk(x, z).unwrap(), blob);
+    }
+    let mut chunks = storage.list_chunks().unwrap();
+    chunks.sort();
+    assert_eq!(chunks, vec![(-1, 33), (0, 0), (5, 7)]);
+    assert!(storage.chunk_timestamp(5, 7).unwrap() > 0);
+    assert!(storage.chunk_timestamp(6, 7).is_err());
+    let timestamps = storage.chunk_timestamps().unwrap();
+    assert_eq!(timestamps.len(), 3);
+    assert_eq!(timestamps[&(5, 7)], storage.chunk_timestamp(5, 7).unwrap());
+    std::fs::remove_dir_all(path).unwrap();
+}
@@ -0,0 +1,146 @@
+//! Reading and writing of MCEdit `.schematic` files.
+//!
+//! A schematic is a gzip compressed NBT compound with the size of the build in `Width`, `Height` and
+//! `Length` and its blocks in `Blocks` and `Data`, indexed by `(y * Length + z) * Width + x`.
+//! The offset WorldEdit stores in `WEOffsetX`, `WEOffsetY` and `WEOffsetZ` is read and written as well.
+use crate::world::edit::Clipboard;
+use crate::world::util::read_nbt_byte_array;
+use std::io::{Read, Write};
+use std::path::Path;
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+/// Reads a schematic from gzip compressed NBT.
+pub fn read<R: Read>(reader: &mut R) -> std::io::Result<Clipboard> {
+    let blob = nbt::Blob::from_gzip_reader(reader)?;
+    match blob.get("Materials") {
+        Some(nbt::Value::String(materials)) if materials == "Alpha" => {}
+        None => {}
+        Some(materials) => {
+            return Err(invalid_data(format!(
+                "Unsupported schematic materials: {materials}"
+            )))
+        }
+    }
+    let dimension = |name: &'static str| match blob.get(name) {
+        Some(nbt::Value::Short(v)) if *v >= 0 => Ok(*v as usize),
+        _ => Err(invalid_data(format!("Did not find {name} in schematic!"))),
+    };
+    let size = [
+        dimension("Width")?,
+        dimension("Height")?,
+        dimension("Length")?,
+    ];
+    let offset = ["WEOffsetX", "WEOffsetY", "WEOffsetZ"].map(|name| match blob.get(name) {
+        Some(nbt::Value::Int(v)) => *v,
+        _ => 0,
+    });
+    let blocks = read_nbt_byte_array(&blob, "Blocks")?;
+    let data = read_nbt_byte_array(&blob, "Data")?
+        .into_iter()
+        .map(|data| data & 0x0F)
+        .collect();
+    let mut clipboard = Clipboard::from_raw(size, blocks, data, offset)?;
+    if let Some(nbt::Value::List(tile_entities)) = blob.get("TileEntities") {
+        clipboard.tile_entities = tile_entities
+            .iter()
+            .filter_map(|value| match value {
+                nbt::Value::Compound(tile_entity) => Some(tile_entity.clone()),
+                _ => None,
+            })
+            .collect();
+    }
+    Ok(clipboard)
+}
+
+/// Writes a schematic as gzip compressed NBT.
+///
+/// Errors if the clipboard is larger than a schematic can describe.
+pub fn write<W: Write>(clipboard: &Clipboard, writer: &mut W) -> std::io::Result<()> {
+    let to_i8 = |bytes: &[u8]| bytes.iter().map(|b| *b as i8).collect::<Vec<_>>();
+    let mut blob = nbt::Blob::named("Schematic");
+    let [width, height, length] = clipboard.size().map(i16::try_from);
+    let (Ok(width), Ok(height), Ok(length)) = (width, height, length) else {
+        return Err(invalid_data(format!(
+            "Clipboard of size {:?} is too large for a schematic!",
+            clipboard.size()
+        )));
+    };
+    blob.insert("Width", nbt::Value::Short(width))?;
+    blob.insert("Height", nbt::Value::Short(height))?;
+    blob.insert("Length", nbt::Value::Short(length))?;
+    blob.insert("Materials", nbt::Value::String("Alpha".to_string()))?;
+    blob.insert("Blocks", nbt::Value::ByteArray(to_i8(clipboard.blocks())))?;
+    blob.insert("Data", nbt::Value::ByteArray(to_i8(clipboard.data())))?;
+    blob.insert("Entities", nbt::Value::List(Vec::new()))?;
+    blob.insert(
+        "TileEntities",
+        nbt::Value::List(
+            clipboard
+                .tile_entities
+                .iter()
+                .cloned()
+                .map(nbt::Value::Compound)
+                .collect(),
+        ),
+    )?;
+    for (name, offset) in ["WEOffsetX", "WEOffsetY", "WEOffsetZ"]
+        .into_iter()
+        .zip(clipboard.offset)
+    {
+        blob.insert(name, nbt::Value::Int(offset))?;
+    }
+    blob.to_gzip_writer(writer)?;
+    Ok(())
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Clipboard> {
+    read(&mut std::io::BufReader::new(std::fs::File::open(path)?))
+}
+
+pub fn save<P: AsRef<Path>>(clipboard: &Clipboard, path: P) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write(clipboard, &mut writer)?;
+    writer.flush()
+}
+
+#[test]
+fn test_schematic_round_trip() {
+    use crate::world::edit::{apply, Cuboid};
+    use crate::world::memory::MemoryStorage;
+    use crate::world::{Chunk, World};
+    use std::collections::HashMap;
+
+    let mut world = World::create(Box::new(MemoryStorage::new()), 0, [0, 64, 0]);
+    world.insert_chunk(Chunk::empty(0, 0));
+    world.insert_chunk(Chunk::empty(1, 0));
+    world.set_block_and_data(3, 5, 7, 35, 14);
+    world.set_block_and_data(4, 5, 7, 54, 2);
+    let mut chest = HashMap::new();
+    chest.insert("id".to_string(), nbt::Value::String("Chest".to_string()));
+    chest.insert("Items".to_string(), nbt::Value::List(Vec::new()));
+    crate::world::set_tile_entity_position(&mut chest, [4, 5, 7]);
+    assert!(world.set_tile_entity(chest.clone()));
+
+    let clipboard = Clipboard::copy(&mut world, &Cuboid::new([3, 5, 7], [4, 6, 8]), [0, 5, 0]);
+    let mut bytes = Vec::new();
+    write(&clipboard, &mut bytes).unwrap();
+    let read = read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(read, clipboard);
+    assert_eq!(read.offset, [3, 0, 7]);
+
+    // Pasting moves the chest along with its block into the next chunk.
+    apply(&mut world, &read.paste([16, 5, 0]));
+    for tile_entity in read.paste_tile_entities([16, 5, 0]) {
+        assert!(world.set_tile_entity(tile_entity));
+    }
+    assert_eq!(world.get_block_and_data(20, 5, 7), Some((54, 2)));
+    crate::world::set_tile_entity_position(&mut chest, [20, 5, 7]);
+    assert_eq!(world.get_tile_entity(20, 5, 7), Some(chest));
+
+    // Replacing the chest removes its tile entity.
+    world.set_block_and_data(20, 5, 7, 0, 0);
+    assert_eq!(world.get_tile_entity(20, 5, 7), None);
+}
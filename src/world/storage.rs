@@ -0,0 +1,236 @@
+use crate::util::{base36_from_i32, base36_from_u64, i32_from_base36};
+use crate::world::region::RegionStorage;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Backend that persists the raw NBT of chunks.
+pub trait ChunkStorage: Send + Sync {
+    /// Reads the chunk at the chunk coordinates `x` and `z`.
+    ///
+    /// Errors with `ErrorKind::NotFound` if the chunk was never saved.
+    fn read_chunk(&self, x: i32, z: i32) -> std::io::Result<nbt::Blob>;
+
+    /// Writes the chunk at the chunk coordinates `x` and `z`, replacing any previous version.
+    fn write_chunk(&self, x: i32, z: i32, blob: &nbt::Blob) -> std::io::Result<()>;
+
+    /// Lists the coordinates of every chunk present in the storage.
+    fn list_chunks(&self) -> std::io::Result<Vec<(i32, i32)>>;
+
+    /// Returns when the chunk at `x` and `z` was last written.
+    ///
+    /// Only values of the same storage are comparable, a changed value means the chunk was rewritten.
+    fn chunk_timestamp(&self, x: i32, z: i32) -> std::io::Result<u64>;
+
+    /// Returns when every chunk present in the storage was last written, like [`ChunkStorage::chunk_timestamp`].
+    ///
+    /// Storages that keep the timestamps of many chunks together should read each of them once.
+    fn chunk_timestamps(&self) -> std::io::Result<HashMap<(i32, i32), u64>> {
+        Ok(self
+            .list_chunks()?
+            .par_iter()
+            .filter_map(|(x, z)| Some(((*x, *z), self.chunk_timestamp(*x, *z).ok()?)))
+            .collect())
+    }
+}
+
+/// Backend that persists everything belonging to a world: chunks, level data and player data.
+pub trait WorldStorage: Send + Sync {
+    fn chunks(&self) -> &dyn ChunkStorage;
+
+    /// Reads the level data, which is the content of `level.dat` on disk.
+    fn read_level(&self) -> std::io::Result<nbt::Blob>;
+
+    fn write_level(&self, blob: &nbt::Blob) -> std::io::Result<()>;
+
+    /// Reads the data of the player `name`.
+    ///
+    /// Errors with `ErrorKind::NotFound` if the player was never saved.
+    fn read_player(&self, name: &str) -> std::io::Result<nbt::Blob>;
+
+    fn write_player(&self, name: &str, blob: &nbt::Blob) -> std::io::Result<()>;
+
+    /// Number of bytes the world occupies.
+    fn size_on_disk(&self) -> std::io::Result<u64>;
+}
+
+/// Stores a world in a directory, the layout vanilla servers use.
+pub struct FsStorage {
+    path: PathBuf,
+    chunks: Box<dyn ChunkStorage>,
+}
+
+impl FsStorage {
+    /// Opens the world directory at `world_path`, detecting the chunk format.
+    pub fn open<P: AsRef<Path>>(world_path: P) -> Self {
+        let format = StorageFormat::detect(world_path.as_ref());
+        Self::with_format(world_path, format)
+    }
+
+    pub fn with_format<P: AsRef<Path>>(world_path: P, format: StorageFormat) -> Self {
+        Self {
+            path: world_path.as_ref().to_path_buf(),
+            chunks: format.open(world_path.as_ref()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn player_path(&self, name: &str) -> std::io::Result<PathBuf> {
+        // Names end up in a path, so anything that could escape the players directory is refused.
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid player name: {name:?}"),
+            ));
+        }
+        Ok(self.path.join("players").join(format!("{name}.dat")))
+    }
+}
+
+impl WorldStorage for FsStorage {
+    fn chunks(&self) -> &dyn ChunkStorage {
+        self.chunks.as_ref()
+    }
+
+    fn read_level(&self) -> std::io::Result<nbt::Blob> {
+        read_gzip_file(&self.path.join("level.dat"))
+    }
+
+    fn write_level(&self, blob: &nbt::Blob) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.path)?;
+        write_gzip_file(&self.path.join("level.dat"), blob)
+    }
+
+    fn read_player(&self, name: &str) -> std::io::Result<nbt::Blob> {
+        read_gzip_file(&self.player_path(name)?)
+    }
+
+    fn write_player(&self, name: &str, blob: &nbt::Blob) -> std::io::Result<()> {
+        let file_path = self.player_path(name)?;
+        std::fs::create_dir_all(self.path.join("players"))?;
+        write_gzip_file(&file_path, blob)
+    }
+
+    fn size_on_disk(&self) -> std::io::Result<u64> {
+        fs_extra::dir::get_size(&self.path).map_err(|e| std::io::Error::other(e.to_string()))
+    }
+}
+
+fn read_gzip_file(path: &Path) -> std::io::Result<nbt::Blob> {
+    let mut file = std::fs::File::open(path)?;
+    Ok(nbt::Blob::from_gzip_reader(&mut file)?)
+}
+
+fn write_gzip_file(path: &Path, blob: &nbt::Blob) -> std::io::Result<()> {
+    let mut encoder = GzEncoder::new(std::fs::File::create(path)?, Compression::default());
+    write_nbt(blob, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Writes `blob` into the compressing `encoder`.
+pub(crate) fn write_nbt(blob: &nbt::Blob, encoder: &mut impl Write) -> std::io::Result<()> {
+    // Serializing into an uncompressed buffer first is a lot faster than feeding the encoder tag by tag.
+    let mut raw = Vec::with_capacity(blob.len_bytes());
+    blob.to_writer(&mut raw)?;
+    encoder.write_all(&raw)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StorageFormat {
+    /// One gzip compressed file per chunk in base36 named directories.
+    Alpha,
+    /// 32x32 chunks per `.mcr` file in the `region` directory.
+    Region,
+}
+
+impl StorageFormat {
+    /// Guesses the format of an existing world by looking for a `region` directory.
+    pub fn detect(world_path: &Path) -> Self {
+        if world_path.join("region").is_dir() {
+            StorageFormat::Region
+        } else {
+            StorageFormat::Alpha
+        }
+    }
+
+    pub fn open(self, world_path: &Path) -> Box<dyn ChunkStorage> {
+        match self {
+            StorageFormat::Alpha => Box::new(AlphaStorage::new(world_path)),
+            StorageFormat::Region => Box::new(RegionStorage::new(world_path.join("region"))),
+        }
+    }
+}
+
+/// Opens the chunk storage of the world at `world_path` in whatever format it is saved in.
+pub fn open(world_path: &Path) -> Box<dyn ChunkStorage> {
+    StorageFormat::detect(world_path).open(world_path)
+}
+
+pub struct AlphaStorage {
+    path: PathBuf,
+}
+
+impl AlphaStorage {
+    pub fn new<P: AsRef<Path>>(world_path: P) -> Self {
+        Self {
+            path: world_path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn chunk_path(&self, x: i32, z: i32) -> PathBuf {
+        let (x_string, z_string) = (base36_from_i32(x), base36_from_i32(z));
+        let (high_level, low_level) = (
+            base36_from_u64((x & 63) as u64),
+            base36_from_u64((z & 63) as u64),
+        );
+        let file_name = format!("c.{x_string}.{z_string}.dat");
+        self.path.join(high_level).join(low_level).join(file_name)
+    }
+}
+
+impl ChunkStorage for AlphaStorage {
+    fn read_chunk(&self, x: i32, z: i32) -> std::io::Result<nbt::Blob> {
+        read_gzip_file(&self.chunk_path(x, z))
+    }
+
+    fn write_chunk(&self, x: i32, z: i32, blob: &nbt::Blob) -> std::io::Result<()> {
+        let file_path = self.chunk_path(x, z);
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_gzip_file(&file_path, blob)
+    }
+
+    fn list_chunks(&self) -> std::io::Result<Vec<(i32, i32)>> {
+        let mut chunks = Vec::new();
+        for entry in walkdir::WalkDir::new(&self.path).min_depth(3).max_depth(3) {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str() else {
+                continue;
+            };
+            let parts = name.split('.').collect::<Vec<_>>();
+            if let ["c", x, z, "dat"] = parts.as_slice() {
+                if let (Some(x), Some(z)) = (i32_from_base36(x), i32_from_base36(z)) {
+                    chunks.push((x, z));
+                }
+            }
+        }
+        Ok(chunks)
+    }
+
+    fn chunk_timestamp(&self, x: i32, z: i32) -> std::io::Result<u64> {
+        let modified = std::fs::metadata(self.chunk_path(x, z))?.modified()?;
+        // Nanoseconds, chunks are often written more than once a second.
+        Ok(modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64)
+    }
+}